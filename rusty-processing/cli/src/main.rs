@@ -1,5 +1,8 @@
+use std::io::Write;
+use std::num::NonZeroUsize;
 use std::path;
 use std::path::PathBuf;
+use std::time::Duration;
 
 use anyhow::anyhow;
 use clap::Parser;
@@ -9,7 +12,10 @@ use tap::Tap;
 use tempfile::TempPath;
 use tokio::sync::mpsc::{Receiver, Sender};
 
-use processing::processing::{ProcessContextBuilder, processor, ProcessOutput, ProcessType};
+use processing::processing::{
+    EntityRule, ExtractionLimits, HashAlgorithm, ImageFormat, MboxVariant, OcrFormat, ProcessContextBuilder, ProcessError,
+    ProcessOutput, Processor, ProcessorBuilder, ProcessType, Progress,
+};
 use services::{ArchiveBuilder, log_err};
 
 lazy_static! {
@@ -29,8 +35,16 @@ pub fn runtime() -> &'static tokio::runtime::Runtime {
 ///
 const OUTPUT_HANDLING_THREADS: usize = 1000;
 
+/// The width of the progress bar in characters.
+///
+const PROGRESS_BAR_WIDTH: usize = 30;
+
+/// Processes a file and the files embedded in it, writing the created files to an archive.
+///
 #[derive(Parser, Debug)]
 struct Args {
+    /// The file to process.
+    ///
     #[arg(
         short = 'i',
         long,
@@ -38,15 +52,21 @@ struct Args {
     )]
     input: path::PathBuf,
 
+    /// The archive to write the created files to.
+    ///
     #[arg(
         short = 'o',
         long
     )]
     output: path::PathBuf,
 
+    /// The MIME type of the file, detected from its content if not given.
+    ///
     #[arg(short = 'm', long)]
-    mimetype: String,
+    mimetype: Option<String>,
 
+    /// The types of files to create, separated by spaces.
+    ///
     #[arg(
         short = 't',
         long,
@@ -55,8 +75,117 @@ struct Args {
     )]
     types: Vec<ProcessType>,
 
+    /// Create every type of file, instead of the given types.
+    ///
     #[arg(short = 'a', long)]
     all: bool,
+
+    /// The URL of the Tika server, e.g. `http://localhost:9998`.
+    ///
+    #[arg(long)]
+    tika_url: Option<String>,
+
+    /// The directory to write temporary files to, instead of the system's temporary directory.
+    ///
+    #[arg(long)]
+    temp_dir: Option<path::PathBuf>,
+
+    /// The maximum number of processors running at the same time, at least 1.
+    ///
+    #[arg(long)]
+    concurrency: Option<NonZeroUsize>,
+
+    /// The number of seconds a processor may run before it is stopped.
+    ///
+    #[arg(long)]
+    timeout_secs: Option<u64>,
+
+    /// The maximum depth of embedded files to extract, where files embedded in the input file are at depth 1.
+    ///
+    #[arg(long)]
+    max_depth: Option<usize>,
+
+    /// The maximum number of files to extract from a single container.
+    ///
+    #[arg(long)]
+    max_entries: Option<usize>,
+
+    /// The maximum number of bytes to extract from a single container.
+    ///
+    #[arg(long)]
+    max_total_bytes: Option<u64>,
+
+    /// The maximum ratio of the uncompressed to the compressed size of an extracted file.
+    ///
+    #[arg(long)]
+    max_compression_ratio: Option<f64>,
+
+    /// The format of page images.
+    ///
+    #[arg(long)]
+    image_format: Option<ImageFormat>,
+
+    /// The resolution of page images in DPI.
+    ///
+    #[arg(long)]
+    image_resolution: Option<u32>,
+
+    /// The formats of the text recognized by OCR, separated by spaces.
+    ///
+    #[arg(
+        long,
+        num_args = 0..,
+        value_delimiter = ' ',
+    )]
+    ocr_formats: Vec<OcrFormat>,
+
+    /// The language of the text recognized by OCR, as a Tesseract language code, e.g. `eng`.
+    ///
+    #[arg(long)]
+    ocr_language: Option<String>,
+
+    /// The hash algorithms of `hashes.json`, separated by spaces.
+    ///
+    #[arg(
+        long,
+        num_args = 0..,
+        value_delimiter = ' ',
+    )]
+    hash_algorithms: Vec<HashAlgorithm>,
+
+    /// Detect the language of extracted text.
+    ///
+    #[arg(long)]
+    detect_language: bool,
+
+    /// The built-in entity types to redact, separated by spaces, e.g. `email phone`. All types if not given.
+    ///
+    #[arg(
+        long,
+        num_args = 0..,
+        value_delimiter = ' ',
+    )]
+    redact_types: Vec<String>,
+
+    /// A custom entity type to redact, given as `<type>=<regex>`, e.g. `case_number=CASE-\d+`. May be repeated.
+    ///
+    #[arg(long, value_parser = parse_redaction_rule)]
+    redact_pattern: Vec<EntityRule>,
+
+    /// The width in columns text extracted from HTML is wrapped to.
+    ///
+    #[arg(long)]
+    html_text_width: Option<usize>,
+
+    /// The variant to read mbox files as, detected from their content if not given.
+    ///
+    #[arg(long)]
+    mbox_variant: Option<MboxVariant>,
+
+    /// Show the progress of processing.
+    ///
+    #[arg(long)]
+    progress: bool,
 }
 
 fn parse_input_file(path_str: &str) -> Result<path::PathBuf, String> {
@@ -70,6 +199,14 @@ fn parse_input_file(path_str: &str) -> Result<path::PathBuf, String> {
     Ok(path)
 }
 
+/// Parses a custom redaction rule given as `<type>=<regex>`, e.g. `case_number=CASE-\d+`.
+///
+fn parse_redaction_rule(rule: &str) -> Result<EntityRule, String> {
+    let (entity_type, pattern) = rule.split_once('=')
+        .ok_or_else(|| format!("Redaction rule {} is not of the form <type>=<regex>", rule))?;
+    EntityRule::new(entity_type, pattern).map_err(|err| err.to_string())
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     simple_logger::init_with_level(log::Level::Info)?;
@@ -81,7 +218,61 @@ async fn main() -> anyhow::Result<()> {
         args.types
     };
 
-    process(args.input, args.output, args.mimetype, types, true).await?;
+    let mut builder = ProcessorBuilder::new();
+    if let Some(tika_url) = args.tika_url {
+        builder = builder.tika_url(tika_url);
+    }
+    if let Some(temp_dir) = args.temp_dir {
+        builder = builder.temp_dir(temp_dir);
+    }
+    if let Some(concurrency) = args.concurrency {
+        builder = builder.max_concurrency(concurrency);
+    }
+    if let Some(timeout_secs) = args.timeout_secs {
+        builder = builder.default_timeout(Duration::from_secs(timeout_secs));
+    }
+    if let Some(image_format) = args.image_format {
+        builder = builder.image_format(image_format);
+    }
+    if let Some(image_resolution) = args.image_resolution {
+        builder = builder.image_resolution(image_resolution);
+    }
+    if !args.ocr_formats.is_empty() {
+        builder = builder.ocr_formats(args.ocr_formats);
+    }
+    if let Some(ocr_language) = args.ocr_language {
+        builder = builder.ocr_language(ocr_language);
+    }
+    if !args.hash_algorithms.is_empty() {
+        builder = builder.hash_algorithms(args.hash_algorithms);
+    }
+    builder = builder.detect_language(args.detect_language);
+    if !args.redact_types.is_empty() || !args.redact_pattern.is_empty() {
+        let mut rules = EntityRule::defaults().into_iter()
+            .filter(|rule| {
+                args.redact_types.is_empty()
+                    || args.redact_types.iter().any(|entity_type| entity_type == rule.entity_type())
+            })
+            .collect::<Vec<_>>();
+        rules.extend(args.redact_pattern);
+        builder = builder.redaction_rules(rules);
+    }
+    if let Some(html_text_width) = args.html_text_width {
+        builder = builder.html_text_width(html_text_width);
+    }
+    if let Some(mbox_variant) = args.mbox_variant {
+        builder = builder.mbox_variant(mbox_variant);
+    }
+    builder = builder.limits(ExtractionLimits {
+        max_depth: args.max_depth,
+        max_entries: args.max_entries,
+        max_total_bytes: args.max_total_bytes,
+        max_compression_ratio: args.max_compression_ratio,
+    });
+    let processor = builder.build();
+
+    let mimetype = args.mimetype.unwrap_or_default();
+    process(&processor, args.input, args.output, mimetype, types, true, args.progress).await?;
 
     Ok(())
 }
@@ -93,9 +284,11 @@ async fn main() -> anyhow::Result<()> {
 ///
 /// # Arguments
 ///
+/// * `processor` - The processor to process the file and its embedded files with.
 /// * `stream` - The stream of bytes to process.
-/// * `mimetype` - The MIME type the stream of bytes represents.
+/// * `mimetype` - The MIME type the stream of bytes represents, or an empty string to detect it.
 /// * `process_recursively` - Whether to process embedded files recursively.
+/// * `show_progress` - Whether to show the progress of processing on the terminal.
 ///
 /// # Returns
 ///
@@ -104,13 +297,19 @@ async fn main() -> anyhow::Result<()> {
 /// * `Err(_)` - If there was an error processing the stream of bytes.
 ///
 pub async fn process(
+    processor: &Processor,
     input_path: PathBuf,
     output_path: PathBuf,
     mimetype: String,
     types: Vec<ProcessType>,
     recurse: bool,
+    show_progress: bool,
 ) -> anyhow::Result<()> {
-    info!("Processing file with MIME type {}", &mimetype);
+    if mimetype.is_empty() {
+        info!("Processing file with MIME type to be detected");
+    } else {
+        info!("Processing file with MIME type {}", &mimetype);
+    }
 
     let (output_sink, outputs) = tokio::sync::mpsc::channel(100);
     let (archive_entry_sink, archive_entries) = tokio::sync::mpsc::channel(100);
@@ -119,14 +318,21 @@ pub async fn process(
         mimetype,
         types,
         output_sink,
-    ).build();
-
-    let processing = tokio::spawn(processor().process(ctx, input_path));
-    let output_handling = tokio::spawn(handle_outputs(
-        outputs,
-        archive_entry_sink,
-        recurse,
-    ));
+    )
+        .progress(show_progress)
+        .build();
+
+    let processing = tokio::spawn({
+        let processor = processor.clone();
+        async move {
+            if recurse {
+                processor.process_recursive(ctx, input_path).await
+            } else {
+                processor.process(ctx, input_path).await
+            }
+        }
+    });
+    let output_handling = tokio::spawn(handle_outputs(outputs, archive_entry_sink));
     let archive = tokio::spawn(build_archive(archive_entries, output_path));
 
     processing.await?.map_err(|err| anyhow!(format!("{}", err)))?;
@@ -142,57 +348,93 @@ pub async fn process(
 /// Each metadata.json received is submitted to a thread pool to be handled on a separate thread. This allows us to
 /// continuing receiving processing outputs without blocking.
 ///
-/// Archive entries created from each metadata.json is sent to the archive entry sink.
+/// Archive entries created from each metadata.json is sent to the archive entry sink. Progress is shown as it is
+/// received instead.
 ///
 async fn handle_outputs(
-    mut outputs: Receiver<anyhow::Result<ProcessOutput>>,
+    mut outputs: Receiver<Result<ProcessOutput, ProcessError>>,
     archive_entry_sink: Sender<(TempPath, PathBuf)>,
-    recurse: bool,
 ) {
     let worker_pool = threadpool::ThreadPool::new(OUTPUT_HANDLING_THREADS);
 
+    let mut showed_progress = false;
     while let Some(output) = outputs.recv().await {
-        if let Ok(output) = output.tap(log_err!("Error processing")) {
-            let archive_entry_sink = archive_entry_sink.clone();
-            worker_pool.execute(move || runtime().block_on(
-                handle_process_output(output, archive_entry_sink, recurse)
-            ));
+        match output.tap(log_err!("Error processing")) {
+            Ok(ProcessOutput::Progress(_, progress)) => {
+                show_progress(&progress);
+                showed_progress = true;
+            }
+            Ok(output) => {
+                let archive_entry_sink = archive_entry_sink.clone();
+                worker_pool.execute(move || runtime().block_on(
+                    handle_process_output(output, archive_entry_sink)
+                ));
+            }
+            Err(_) => (),
         }
     }
+    if showed_progress {
+        eprintln!();
+    }
 
     worker_pool.join();
 }
 
-/// Regardless of if the metadata.json is normal or an embedded file, both will be used to create an archive entry and no additional
-/// processing will occur.
+/// Shows the progress as a bar on the current line of the terminal, or as counts if the total is unknown.
+///
+fn show_progress(progress: &Progress) {
+    let processor = progress.processor.as_deref().unwrap_or("Processing");
+    let line = match (progress.fraction(), progress.entries, progress.bytes_read) {
+        (Some(fraction), _, _) => {
+            let filled = (fraction * PROGRESS_BAR_WIDTH as f64) as usize;
+            format!(
+                "[{}{}] {:>3.0}% {}",
+                "#".repeat(filled),
+                " ".repeat(PROGRESS_BAR_WIDTH - filled),
+                fraction * 100.0,
+                processor,
+            )
+        }
+        (None, Some(entries), _) => format!("{}: {} entries", processor, entries),
+        (None, None, Some(bytes_read)) => format!("{}: {}", processor, bytesize::ByteSize(bytes_read)),
+        (None, None, None) => format!("{}...", processor),
+    };
+
+    // Clear the line first, as the previous progress may have been longer
+    eprint!("\r\x1b[2K{}", line);
+    let _ = std::io::stderr().flush();
+}
+
+/// Regardless of if the metadata.json is normal or an embedded file, both will be used to create an archive entry.
+///
+/// Embedded files are processed by [`Processor::process_recursive`] when recursing, so no additional processing will
+/// occur here.
 ///
 async fn handle_process_output(
     output: ProcessOutput,
     archive_entry_sink: Sender<(TempPath, PathBuf)>,
-    recurse: bool
 ) {
     let archive_entry: anyhow::Result<(TempPath, PathBuf)> = match output {
-        ProcessOutput::Processed(state, data) => {
+        ProcessOutput::Processed(state, data) | ProcessOutput::Report(state, data) => {
             let archive_path = build_archive_path(state.id_chain, data.name).await;
             Ok((data.path, archive_path))
         },
 
-        ProcessOutput::Embedded(state, data, output_sink) => {
+        ProcessOutput::Embedded(state, data, _) => {
             let mut id_chain = state.id_chain;
             id_chain.push(data.checksum);
 
-            if recurse {
-                let ctx = ProcessContextBuilder::new(data.mimetype, data.types, output_sink.clone())
-                    .id_chain(id_chain.clone())
-                    .build();
-                if let Err(e) = processor().process(ctx, data.path.to_path_buf()).await {
-                    warn!("Error processing: {:?}", e);
-                };
-            }
-
             let archive_path = build_archive_path(id_chain, data.name).await;
             Ok((data.path, archive_path))
+        },
+
+        ProcessOutput::Skipped(state, data) => {
+            let name = data.name.unwrap_or("<all entries>".to_string());
+            info!("Skipped {} in {:?}: {}", name, state.id_chain, data.reason);
+            return;
         }
+
+        ProcessOutput::Progress(_, _) => return,
     };
 
     match archive_entry {
@@ -222,3 +464,18 @@ async fn build_archive_path(id_chain: impl AsRef<[String]>, name: impl AsRef<str
     path.push(name.as_ref());
     path
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_zero_concurrency() {
+        let parse = |concurrency: &str| Args::try_parse_from([
+            "cli", "--input", "Cargo.toml", "--output", "out.zip", "--concurrency", concurrency,
+        ]);
+
+        assert_eq!(parse("2").unwrap().concurrency, NonZeroUsize::new(2));
+        assert!(parse("0").is_err());
+    }
+}
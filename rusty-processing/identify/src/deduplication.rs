@@ -1,12 +1,13 @@
 use std::io;
 use std::io::Cursor;
-use std::ops::{Deref, DerefMut};
 use std::path::Path;
 
 use bytesize::MB;
 use mail_parser::MessageParser;
 use tokio::io::{AsyncRead, AsyncReadExt};
 
+use crate::hashing::HashAlgorithm;
+
 /// Calculates a checksum that represents a unique identification of a file.
 ///
 /// This checksum can be used to identify duplicate files.
@@ -21,16 +22,26 @@ use tokio::io::{AsyncRead, AsyncReadExt};
 /// The checksum as a string.
 ///
 pub async fn dedupe_checksum_from_path(path: impl AsRef<Path>, mimetype: impl AsRef<str>) -> io::Result<String> {
-    let checksum = match mimetype.as_ref() {
-        "message/rfc822" => dedupe_message_from_path(path).await,
-        _ => dedupe_md5_from_path(path).await,
-    }?;
-    Ok(checksum)
+    dedupe_checksum_from_path_with(path, mimetype, HashAlgorithm::Md5).await
+}
+
+/// Calculates a checksum that represents a unique identification of a file with the given hash algorithm.
+///
+/// See [`dedupe_checksum_from_path`], which uses [`HashAlgorithm::Md5`].
+///
+pub async fn dedupe_checksum_from_path_with(
+    path: impl AsRef<Path>,
+    mimetype: impl AsRef<str>,
+    algorithm: HashAlgorithm,
+) -> io::Result<String> {
+    let mut content = tokio::fs::File::open(path).await?;
+    dedupe_checksum_with(&mut content, mimetype, algorithm).await
 }
 
 /// Calculates a checksum that represents a unique identification of a file.
 ///
-/// This checksum can be used to identify duplicate files.
+/// This checksum can be used to identify duplicate files. For files other than messages, it is the MD5 hash of the
+/// content padded to a whole number of megabytes, not the MD5 hash of the content itself.
 ///
 /// # Arguments
 ///
@@ -42,41 +53,55 @@ pub async fn dedupe_checksum_from_path(path: impl AsRef<Path>, mimetype: impl As
 /// The checksum as a string.
 ///
 pub async fn dedupe_checksum(content: &mut (impl AsyncRead + Unpin), mimetype: impl AsRef<str>) -> io::Result<String> {
+    dedupe_checksum_with(content, mimetype, HashAlgorithm::Md5).await
+}
+
+/// Calculates a checksum that represents a unique identification of a file with the given hash algorithm.
+///
+/// See [`dedupe_checksum`], which uses [`HashAlgorithm::Md5`].
+///
+pub async fn dedupe_checksum_with(
+    content: &mut (impl AsyncRead + Unpin),
+    mimetype: impl AsRef<str>,
+    algorithm: HashAlgorithm,
+) -> io::Result<String> {
     let checksum = match mimetype.as_ref() {
-        "message/rfc822" => dedupe_message(content).await,
-        _ => dedupe_md5(content).await,
+        "message/rfc822" => dedupe_message(content, algorithm).await,
+        _ => dedupe_hash(content, algorithm).await,
     }?;
     Ok(checksum)
 }
 
-/// Calculates an MD5 checksum from the contents of a file.
+/// Calculates a checksum from the provided reader.
 ///
-async fn dedupe_md5_from_path(path: impl AsRef<Path>) -> io::Result<String> {
-    let mut content = tokio::fs::File::open(path).await?;
-    dedupe_md5(&mut content).await
-}
-
-/// Calculates an MD5 checksum from the provided reader.
+/// For MD5, the content is hashed in whole buffers of a megabyte, as dedupe checksums have always been calculated: the
+/// last, partial megabyte is followed by the rest of the previous megabyte, or by zeros if the content is smaller than
+/// a megabyte. This keeps existing dedupe IDs stable, and the buffers are filled completely before they are hashed, so
+/// the checksum does not depend on how the reader splits its reads. It does mean an MD5 dedupe checksum is not the MD5
+/// hash of the content, as written to `hashes.json`. Other algorithms hash only the content.
 ///
-async fn dedupe_md5(content: &mut (impl AsyncRead + Unpin)) -> io::Result<String> {
-    let mut ctx = md5::Context::new();
+async fn dedupe_hash(content: &mut (impl AsyncRead + Unpin), algorithm: HashAlgorithm) -> io::Result<String> {
+    let mut hasher = algorithm.hasher();
     let mut buf = Box::new([0; MB as usize]);
-    while content.read(buf.deref_mut()).await? > 0 {
-        ctx.consume(buf.deref());
+    let mut filled = 0;
+    loop {
+        let read = content.read(&mut buf[filled..]).await?;
+        filled += read;
+        if filled == buf.len() || (read == 0 && filled > 0) {
+            let hashed = if algorithm == HashAlgorithm::Md5 { buf.len() } else { filled };
+            hasher.update(&buf[..hashed]);
+            filled = 0;
+        }
+        if read == 0 {
+            break;
+        }
     }
-    Ok(format!("{:x}", ctx.compute()))
-}
-
-/// Calculates an RFC822-based checksum from the contents of a file.
-///
-async fn dedupe_message_from_path(path: impl AsRef<Path>) -> io::Result<String> {
-    let mut file = tokio::fs::File::open(path).await?;
-    dedupe_message(&mut file).await
+    Ok(hasher.finalize())
 }
 
 /// Calculates an RFC822-based checksum from the provided reader.
 ///
-async fn dedupe_message(content: &mut (impl AsyncRead + Unpin)) -> io::Result<String> {
+async fn dedupe_message(content: &mut (impl AsyncRead + Unpin), algorithm: HashAlgorithm) -> io::Result<String> {
     let mut buf = vec![];
     content.read_to_end(&mut buf).await?;
 
@@ -90,14 +115,18 @@ async fn dedupe_message(content: &mut (impl AsyncRead + Unpin)) -> io::Result<St
         .map(|raw_id| Box::new(Cursor::new(raw_id)))
         .unwrap_or(Box::new(Cursor::new(buf)));
 
-    dedupe_md5(&mut content).await
+    dedupe_hash(&mut content, algorithm).await
 }
 
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
 
-    use crate::deduplication::dedupe_checksum;
+    use bytesize::MB;
+    use tokio::io::AsyncReadExt;
+
+    use crate::deduplication::{dedupe_checksum, dedupe_checksum_from_path, dedupe_checksum_with};
+    use crate::hashing::HashAlgorithm;
 
     #[tokio::test]
     async fn test_dedupe_checksum_message_no_data() {
@@ -147,4 +176,52 @@ Phillip";
 
         assert_eq!(checksum, "bccf69bd7101c797b298c8b5329b965f");
     }
+
+    #[tokio::test]
+    async fn test_dedupe_checksum_md5_split_reads() {
+        let mut content = Cursor::new(b"Hello, ".to_vec()).chain(Cursor::new(b"world!".to_vec()));
+
+        let checksum = dedupe_checksum(&mut content, "application/octet-stream").await.unwrap();
+
+        assert_eq!(checksum, "bccf69bd7101c797b298c8b5329b965f");
+    }
+
+    #[tokio::test]
+    async fn test_dedupe_checksum_md5_independent_of_reads() {
+        let data = (0..MB as usize * 2 + 1000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let path = std::env::temp_dir().join("test_dedupe_checksum_md5_independent_of_reads");
+        tokio::fs::write(&path, &data).await.unwrap();
+
+        let from_path = dedupe_checksum_from_path(&path, "application/octet-stream").await.unwrap();
+        tokio::fs::remove_file(&path).await.unwrap();
+        let mut whole = Cursor::new(data.clone());
+        let whole = dedupe_checksum(&mut whole, "application/octet-stream").await.unwrap();
+        let mut chunked = Cursor::new(data[..4096].to_vec())
+            .chain(Cursor::new(data[4096..MB as usize + 7].to_vec()))
+            .chain(Cursor::new(data[MB as usize + 7..].to_vec()));
+        let chunked = dedupe_checksum(&mut chunked, "application/octet-stream").await.unwrap();
+
+        assert_eq!(whole, from_path);
+        assert_eq!(chunked, from_path);
+    }
+
+    #[tokio::test]
+    async fn test_dedupe_checksum_md5_large() {
+        let data = (0..MB as usize * 3 / 2).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let mut chunked = Cursor::new(data[..MB as usize / 3].to_vec())
+            .chain(Cursor::new(data[MB as usize / 3..].to_vec()));
+
+        let checksum = dedupe_checksum(&mut chunked, "application/octet-stream").await.unwrap();
+
+        assert_eq!(checksum, "67f90957a026ff94bcfd070790d69e40");
+    }
+
+    #[tokio::test]
+    async fn test_dedupe_checksum_with_algorithm() {
+        let mut content = Cursor::new(b"Hello, ".to_vec()).chain(Cursor::new(b"world!".to_vec()));
+
+        let checksum = dedupe_checksum_with(&mut content, "application/octet-stream", HashAlgorithm::Sha256).await.unwrap();
+
+        assert_eq!(checksum, "315f5bdb76d078c43b8ac0064e4a0164612b1fce77c869345bfc94c75894edd3");
+    }
 }
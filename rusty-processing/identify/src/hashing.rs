@@ -0,0 +1,194 @@
+use std::fmt;
+use std::fmt::Formatter;
+use std::io;
+use std::ops::DerefMut;
+
+use bytesize::MB;
+use serde::{Deserialize, Serialize};
+use sha1::Digest;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// A hash algorithm files can be hashed with.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    /// MD5, the algorithm deduplication checksums have always used.
+    ///
+    /// Deduplication checksums pad the content before hashing it, so they differ from the MD5 hash of a file, see
+    /// [`crate::deduplication::dedupe_checksum`].
+    ///
+    #[default]
+    Md5,
+
+    /// SHA-1.
+    ///
+    Sha1,
+
+    /// SHA-256.
+    ///
+    Sha256,
+
+    /// BLAKE3.
+    ///
+    Blake3,
+}
+
+impl HashAlgorithm {
+    /// Returns all supported hash algorithms.
+    ///
+    pub fn all() -> &'static [HashAlgorithm] {
+        &[HashAlgorithm::Md5, HashAlgorithm::Sha1, HashAlgorithm::Sha256, HashAlgorithm::Blake3]
+    }
+
+    /// Returns the name of the algorithm, e.g. `sha256`.
+    ///
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Md5 => "md5",
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Sha256 => "sha256",
+            HashAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    /// Creates a hasher calculating a hash with this algorithm.
+    ///
+    pub fn hasher(&self) -> Hasher {
+        match self {
+            HashAlgorithm::Md5 => Hasher::Md5(md5::Context::new()),
+            HashAlgorithm::Sha1 => Hasher::Sha1(sha1::Sha1::new()),
+            HashAlgorithm::Sha256 => Hasher::Sha256(sha2::Sha256::new()),
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+}
+
+impl std::str::FromStr for HashAlgorithm {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('-', "").as_str() {
+            "md5" => Ok(HashAlgorithm::Md5),
+            "sha1" => Ok(HashAlgorithm::Sha1),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "blake3" => Ok(HashAlgorithm::Blake3),
+            _ => Err(format!("Can not convert {} to HashAlgorithm", s)),
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Calculates a hash incrementally with one of the [`HashAlgorithm`]s.
+///
+pub enum Hasher {
+    /// An MD5 hasher.
+    ///
+    Md5(md5::Context),
+
+    /// A SHA-1 hasher.
+    ///
+    Sha1(sha1::Sha1),
+
+    /// A SHA-256 hasher.
+    ///
+    Sha256(sha2::Sha256),
+
+    /// A BLAKE3 hasher, boxed as it is much larger than the others.
+    ///
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    /// Adds the data to the hash.
+    ///
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(ctx) => ctx.consume(data),
+            Hasher::Sha1(hasher) => hasher.update(data),
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Blake3(hasher) => {
+                hasher.update(data);
+            }
+        }
+    }
+
+    /// Returns the hash of all data added as a lowercase hexadecimal string.
+    ///
+    pub fn finalize(self) -> String {
+        match self {
+            Hasher::Md5(ctx) => format!("{:x}", ctx.compute()),
+            Hasher::Sha1(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Hasher::Blake3(hasher) => hasher.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+/// Calculates a hash of the provided reader with each of the algorithms, reading it only once.
+///
+/// # Arguments
+///
+/// * `content` - Content to calculate the hashes of.
+/// * `algorithms` - The algorithms to calculate hashes with.
+///
+/// # Returns
+///
+/// The hashes as lowercase hexadecimal strings, in the order of the algorithms.
+///
+pub async fn hash_all(
+    content: &mut (impl AsyncRead + Unpin),
+    algorithms: &[HashAlgorithm],
+) -> io::Result<Vec<(HashAlgorithm, String)>> {
+    let mut hashers = algorithms.iter()
+        .map(|algorithm| (*algorithm, algorithm.hasher()))
+        .collect::<Vec<_>>();
+
+    let mut buf = Box::new([0; MB as usize]);
+    loop {
+        let read = content.read(buf.deref_mut()).await?;
+        if read == 0 {
+            break;
+        }
+        for (_, hasher) in hashers.iter_mut() {
+            hasher.update(&buf[..read]);
+        }
+    }
+
+    Ok(hashers.into_iter()
+        .map(|(algorithm, hasher)| (algorithm, hasher.finalize()))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_hash_all() {
+        let mut content = Cursor::new(b"Hello, world!".to_vec());
+
+        let hashes = hash_all(&mut content, HashAlgorithm::all()).await.unwrap();
+
+        assert_eq!(hashes, vec![
+            (HashAlgorithm::Md5, "6cd3556deb0da54bca060b4c39479839".to_string()),
+            (HashAlgorithm::Sha1, "943a702d06f34599aee1f8da8ef9f7296031d699".to_string()),
+            (HashAlgorithm::Sha256, "315f5bdb76d078c43b8ac0064e4a0164612b1fce77c869345bfc94c75894edd3".to_string()),
+            (HashAlgorithm::Blake3, "ede5c0b10f2ec4979c69b52f61e42ff5b413519ce09be0f14d098dcfe5f6f98d".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn test_hash_algorithm_from_str() {
+        assert_eq!("SHA-256".parse(), Ok(HashAlgorithm::Sha256));
+        assert_eq!("blake3".parse(), Ok(HashAlgorithm::Blake3));
+        assert!("crc32".parse::<HashAlgorithm>().is_err());
+    }
+}
@@ -3,6 +3,7 @@
 //!
 //! "Identification" includes identifying the following:
 //! * De-duplication checksum
+//! * Hashes
 //! * MIME type
 //!
 #![warn(missing_docs)]
@@ -11,6 +12,10 @@
 ///
 pub mod deduplication;
 
+/// Hashing of files with multiple algorithms.
+///
+pub mod hashing;
+
 /// MIME type identification functionality.
 ///
 pub mod mimetype;
@@ -1,12 +1,43 @@
+use std::fmt;
+use std::fmt::Formatter;
 use std::path::Path;
 
 use anyhow::Context;
 use file_format::FileFormat;
 use log::info;
+use serde::{Deserialize, Serialize};
 
-use services::{tika, xdg_mime};
+use services::{tika, Tika, xdg_mime};
 
-/// Identifies the mimetype of a file.
+/// The method a MIME type was identified with.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentificationMethod {
+    /// Identified by `xdg-mime` from the shared MIME-info database.
+    ///
+    XdgMime,
+
+    /// Identified by Tika.
+    ///
+    Tika,
+
+    /// Identified from the magic bytes of the file by the `file-format` crate.
+    ///
+    FileFormat,
+}
+
+impl fmt::Display for IdentificationMethod {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            IdentificationMethod::XdgMime => write!(f, "'xdg-mime'"),
+            IdentificationMethod::Tika => write!(f, "Tika"),
+            IdentificationMethod::FileFormat => write!(f, "file format"),
+        }
+    }
+}
+
+/// Identifies the mimetype of a file, using the default Tika service.
 ///
 /// # Arguments
 ///
@@ -17,23 +48,40 @@ use services::{tika, xdg_mime};
 /// The mimetype of the file.
 ///
 pub async fn identify_mimetype(path: impl AsRef<Path>) -> Result<Option<String>, anyhow::Error> {
-    {
-        if let Some(mimetype) = identify_using_xdg_mime(&path).await? {
-            info!("Identified mimetype as '{}' using 'xdg-mime'", mimetype);
-            return Ok(Some(mimetype));
-        }
+    Ok(identify_mimetype_with_method(path, tika()).await?.map(|(mimetype, _)| mimetype))
+}
 
-        if let Some(mimetype) = identify_using_tika(&path).await? {
-            info!("Identified mimetype as '{}' using Tika", mimetype);
-            return Ok(Some(mimetype));
-        }
+/// Identifies the mimetype of a file, along with the method it was identified with.
+///
+/// The methods are tried in order of `xdg-mime`, Tika and the file format, until one of them identifies a MIME type
+/// more specific than `application/octet-stream`.
+///
+/// # Arguments
+///
+/// * `path` - The path to the file to identify the mimetype for.
+/// * `tika` - The Tika service to identify the mimetype with.
+///
+/// # Returns
+///
+/// The mimetype of the file and the method it was identified with, or [`None`] if no method identified it.
+///
+pub async fn identify_mimetype_with_method(
+    path: impl AsRef<Path>,
+    tika: &Tika,
+) -> Result<Option<(String, IdentificationMethod)>, anyhow::Error> {
+    {
+        let identified = if let Some(mimetype) = identify_using_xdg_mime(&path).await? {
+            Some((mimetype, IdentificationMethod::XdgMime))
+        } else if let Some(mimetype) = identify_using_tika(&path, tika).await? {
+            Some((mimetype, IdentificationMethod::Tika))
+        } else {
+            identify_using_file_format(&path).await?.map(|mimetype| (mimetype, IdentificationMethod::FileFormat))
+        };
 
-        if let Some(mimetype) = identify_using_file_format(&path).await? {
-            info!("Identified mimetype as '{}' using file format", mimetype);
-            return Ok(Some(mimetype));
+        if let Some((mimetype, method)) = &identified {
+            info!("Identified mimetype as '{}' using {}", mimetype, method);
         }
-
-        anyhow::Ok(None)
+        anyhow::Ok(identified)
     }
     .with_context(|| {
         format!(
@@ -48,8 +96,8 @@ async fn identify_using_xdg_mime(path: impl AsRef<Path>) -> Result<Option<String
     Ok((mimetype != "application/octet-stream" && mimetype != "text/plain").then_some(mimetype))
 }
 
-async fn identify_using_tika(path: impl AsRef<Path>) -> Result<Option<String>, anyhow::Error> {
-    let mimetype = tika().detect(&path).await?;
+async fn identify_using_tika(path: impl AsRef<Path>, tika: &Tika) -> Result<Option<String>, anyhow::Error> {
+    let mimetype = tika.detect(&path).await?;
     Ok((mimetype != "application/octet-stream").then_some(mimetype))
 }
 
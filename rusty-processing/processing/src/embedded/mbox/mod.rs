@@ -0,0 +1,359 @@
+use std::fmt::Debug;
+use std::io::{Cursor, Write};
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use tempfile::TempPath;
+
+use identify::deduplication::dedupe_checksum;
+
+use crate::embedded::ExtractionGuard;
+use crate::processing::{Process, ProcessContext, ProcessError, ProcessErrorKind, ProcessOutput, Progress, SkipReason};
+
+pub(crate) use reader::*;
+
+mod reader;
+
+/// The name given to each message embedded in an mbox file.
+///
+const MESSAGE_NAME: &str = "mbox-message.eml";
+
+/// MboxProcessor is responsible for processing mbox files.
+///
+/// The mbox file is read as the configured [`crate::processing::MboxVariant`], or as the variant detected from its
+/// content, so the embedded messages are identical to the messages as they were written to the file.
+/// The processor only writes out embedded messages and doesn't produce any processed metadata.json. Each message
+/// carries its mbox envelope in the state of its context, see [`crate::processing::ProcessState::envelope`].
+///
+#[derive(Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
+pub struct MboxEmbeddedProcessor;
+
+impl MboxEmbeddedProcessor {
+    /// Writes a message to the metadata.json directory.
+    ///
+    async fn process_message(
+        &self,
+        ctx: &ProcessContext,
+        message: MboxMessage,
+    ) -> Result<ProcessOutput, ProcessError> {
+        let contents = message.contents;
+        let mut file = ctx.temp_file()
+            .context("failed to create temporary file")?;
+
+        file.write_all(&contents)
+            .context("failed to write message to temporary file")?;
+
+        let mimetype = "message/rfc822";
+        let ctx = ctx.new_clone(mimetype.to_string());
+
+        let content_checksum = format!("{:x}", md5::compute(&contents));
+        let mut contents = Cursor::new(contents);
+        let checksum = dedupe_checksum(&mut contents, &mimetype).await
+            .context("failed to calculate checksum")?;
+
+        Ok(ProcessOutput::embedded(
+            &ctx,
+            MESSAGE_NAME,
+            file.into_temp_path(),
+            mimetype,
+            checksum,
+        ).with_content_checksum(content_checksum).with_envelope(message.envelope))
+    }
+}
+
+#[async_trait]
+impl Process for MboxEmbeddedProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        _: TempPath,
+        _: &str,
+    ) -> Result<(), ProcessError> {
+        let mut guard = ExtractionGuard::new(&ctx);
+        if let Err(reason) = guard.check_depth() {
+            warn!("Skipping mbox messages: {}", reason);
+            ctx.add_output(Ok(ProcessOutput::skipped(&ctx, None, reason))).await?;
+            return Ok(());
+        }
+
+        info!("Reading mbox into iterator");
+        let total_bytes = std::fs::metadata(input_path).ok().map(|metadata| metadata.len());
+        let mut reader = MboxReader::open(&ctx, input_path)
+            .context("failed to open mbox file")?;
+
+        info!("Processing embedded messages");
+        let mut index = 0;
+        loop {
+            // Messages exceeding the limits are skipped without holding them in memory
+            reader.set_max_message_size(guard.remaining_bytes());
+            let Some(message_res) = reader.next() else {
+                break;
+            };
+            if ctx.is_cancelled() {
+                return Err(ProcessError::cancelled());
+            }
+
+            let message_res = message_res
+                .map_err(|err| ProcessError::new(ProcessErrorKind::Corrupt, anyhow!("failed to read message from mbox: {}", err)));
+            match message_res {
+                Ok(message) => match guard.admit(message.size, None) {
+                    Ok(()) => ctx.add_output(self.process_message(&ctx, message).await).await?,
+                    // Every later message would be skipped as well, so they are not read at all
+                    Err(reason @ SkipReason::MaxEntries(_)) => {
+                        warn!("Skipping remaining messages: {}", reason);
+                        ctx.add_output(Ok(ProcessOutput::skipped(&ctx, Some(MESSAGE_NAME.to_string()), reason))).await?;
+                        break;
+                    },
+                    Err(reason) => {
+                        warn!("Skipping message: {}", reason);
+                        ctx.add_output(Ok(ProcessOutput::skipped(&ctx, Some(MESSAGE_NAME.to_string()), reason))).await?;
+                    },
+                },
+                Err(e) => ctx.add_output(Err(e)).await?,
+            }
+
+            index += 1;
+            let progress = Progress {
+                entries: Some(index),
+                ..Progress::bytes(reader.offset(), total_bytes)
+            };
+            ctx.progress(progress).await?;
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "Mbox Embedded"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path;
+    use std::sync::Arc;
+
+    use tokio::sync::mpsc::Receiver;
+    use tokio::task::JoinHandle;
+
+    use test_utils::temp_path;
+
+    use crate::processing::{MboxEnvelope, ProcessContextBuilder, ProcessorBuilder, SkippedOutputData};
+
+    use super::*;
+
+    type ProcessFuture = JoinHandle<Result<(), ProcessError>>;
+    type OutputReceiver = Receiver<Result<ProcessOutput, ProcessError>>;
+
+    fn processor_with_context() -> (MboxEmbeddedProcessor, ProcessContext, OutputReceiver) {
+        let (output_sink, outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("application/mbox", vec![], output_sink).build();
+        (MboxEmbeddedProcessor, ctx, outputs)
+    }
+
+    fn process(path: path::PathBuf) -> Result<(ProcessFuture, OutputReceiver), anyhow::Error> {
+        let (processor, ctx, output_rx) = processor_with_context();
+        let proc_fut = tokio::spawn(async move {
+            processor.process(ctx, &path, temp_path()?, "checksum").await
+        });
+        Ok((proc_fut, output_rx))
+    }
+
+    #[tokio::test]
+    async fn test_process() -> anyhow::Result<()> {
+        let path = path::PathBuf::from("../resources/mbox/ubuntu-no-small.mbox");
+        let (proc_fut, mut output_rx) = process(path)?;
+
+        let mut outputs = vec![];
+        let mut envelopes = vec![];
+        while let Some(output) = output_rx.recv().await {
+            match output? {
+                ProcessOutput::Processed(_, _) | ProcessOutput::Report(_, _) | ProcessOutput::Progress(_, _) => panic!("Expected embedded metadata.json"),
+                ProcessOutput::Embedded(state, data, embedded_ctx) => {
+                    envelopes.push(embedded_ctx.state.envelope);
+                    outputs.push((state, data));
+                }
+                ProcessOutput::Skipped(_, _) => panic!("Expected no skipped files"),
+            }
+        }
+        proc_fut.await??;
+
+        // Sort to make the test deterministic
+        outputs.sort_by(|o0, o1| o0.1.checksum.cmp(&o1.1.checksum));
+
+        assert_eq!(outputs.len(), 2);
+
+        let (state, ctx) = &outputs[0];
+        assert_eq!(ctx.mimetype, "message/rfc822");
+        assert_eq!(ctx.checksum, "88dde30cbe134ce0dd8aa0979546646a");
+        assert_eq!(ctx.content_checksum, Some(format!("{:x}", md5::compute(std::fs::read(&ctx.path)?))));
+        assert!(state.id_chain.is_empty());
+
+        let (state, ctx) = &outputs[1];
+        assert_eq!(ctx.mimetype, "message/rfc822");
+        assert_eq!(ctx.checksum, "c694e99230b3cbf36d8aef4131596864");
+        assert!(state.id_chain.is_empty());
+
+        assert_eq!(envelopes, vec![
+            Some(Box::new(MboxEnvelope {
+                sender: "gab@hrp.no".to_string(),
+                date: Some("2005-08-08T05:34:13Z".to_string()),
+                offset: 0,
+            })),
+            Some(Box::new(MboxEnvelope {
+                sender: "hubuntu@gmail.com".to_string(),
+                date: Some("2009-04-22T15:57:01Z".to_string()),
+                offset: 3116,
+            })),
+        ]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_progress() -> anyhow::Result<()> {
+        let (output_sink, mut output_rx) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("application/mbox", vec![], output_sink)
+            .progress(true)
+            .build();
+
+        let path = path::PathBuf::from("../resources/mbox/ubuntu-no-small.mbox");
+        let total_bytes = std::fs::metadata(&path)?.len();
+        let proc_fut = tokio::spawn(async move {
+            MboxEmbeddedProcessor.process(ctx, &path, temp_path()?, "checksum").await
+        });
+
+        let mut progress = vec![];
+        while let Some(output) = output_rx.recv().await {
+            if let ProcessOutput::Progress(_, data) = output? {
+                progress.push(data);
+            }
+        }
+        proc_fut.await??;
+
+        assert_eq!(progress.len(), 2);
+        assert_eq!(progress[0].entries, Some(1));
+        assert_eq!(progress[1], Progress {
+            entries: Some(2),
+            ..Progress::bytes(total_bytes, Some(total_bytes))
+        });
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_large_file() -> anyhow::Result<()> {
+        let path = path::PathBuf::from("../resources/mbox/ubuntu-no.mbox");
+        let (proc_fut, mut output_rx) = process(path)?;
+
+        let mut output_count = 0;
+        while let Some(output) = output_rx.recv().await {
+            match output? {
+                ProcessOutput::Processed(_, _) | ProcessOutput::Report(_, _) | ProcessOutput::Progress(_, _) => panic!("Expected embedded metadata.json"),
+                ProcessOutput::Embedded(_, data, _) => {
+                    output_count += 1;
+                    assert_eq!(data.mimetype, "message/rfc822");
+                },
+                ProcessOutput::Skipped(_, _) => panic!("Expected no skipped files"),
+            }
+        }
+        proc_fut.await??;
+
+        assert_eq!(output_count, 344);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_max_entries() -> anyhow::Result<()> {
+        let (output_sink, mut output_rx) = tokio::sync::mpsc::channel(10);
+        let config = Arc::new(ProcessorBuilder::new().max_entries(1).build().config().clone());
+        let ctx = ProcessContextBuilder::new("application/mbox", vec![], output_sink)
+            .build()
+            .with_config(config);
+
+        let path = path::PathBuf::from("../resources/mbox/ubuntu-no-small.mbox");
+        let proc_fut = tokio::spawn(async move {
+            MboxEmbeddedProcessor.process(ctx, &path, temp_path()?, "checksum").await
+        });
+
+        let mut embedded = 0;
+        let mut skipped = vec![];
+        while let Some(output) = output_rx.recv().await {
+            match output? {
+                ProcessOutput::Processed(_, _) | ProcessOutput::Report(_, _) | ProcessOutput::Progress(_, _) => panic!("Expected embedded metadata.json"),
+                ProcessOutput::Embedded(_, _, _) => embedded += 1,
+                ProcessOutput::Skipped(_, data) => skipped.push(data),
+            }
+        }
+        proc_fut.await??;
+
+        assert_eq!(embedded, 1);
+        assert_eq!(skipped, vec![SkippedOutputData {
+            name: Some("mbox-message.eml".to_string()),
+            reason: SkipReason::MaxEntries(1),
+        }]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_max_entries_stops_reading() -> anyhow::Result<()> {
+        let (output_sink, mut output_rx) = tokio::sync::mpsc::channel(10);
+        let config = Arc::new(ProcessorBuilder::new().max_entries(2).build().config().clone());
+        let ctx = ProcessContextBuilder::new("application/mbox", vec![], output_sink)
+            .build()
+            .with_config(config);
+
+        let path = path::PathBuf::from("../resources/mbox/ubuntu-no.mbox");
+        let proc_fut = tokio::spawn(async move {
+            MboxEmbeddedProcessor.process(ctx, &path, temp_path()?, "checksum").await
+        });
+
+        let mut embedded = 0;
+        let mut skipped = 0;
+        while let Some(output) = output_rx.recv().await {
+            match output? {
+                ProcessOutput::Embedded(_, _, _) => embedded += 1,
+                ProcessOutput::Skipped(_, _) => skipped += 1,
+                _ => {},
+            }
+        }
+        proc_fut.await??;
+
+        assert_eq!(embedded, 2);
+        assert_eq!(skipped, 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_max_total_bytes() -> anyhow::Result<()> {
+        let (output_sink, mut output_rx) = tokio::sync::mpsc::channel(10);
+        let config = Arc::new(ProcessorBuilder::new().max_total_bytes(3500).build().config().clone());
+        let ctx = ProcessContextBuilder::new("application/mbox", vec![], output_sink)
+            .build()
+            .with_config(config);
+
+        let path = path::PathBuf::from("../resources/mbox/ubuntu-no-small.mbox");
+        let proc_fut = tokio::spawn(async move {
+            MboxEmbeddedProcessor.process(ctx, &path, temp_path()?, "checksum").await
+        });
+
+        let mut embedded = vec![];
+        let mut skipped = vec![];
+        while let Some(output) = output_rx.recv().await {
+            match output? {
+                ProcessOutput::Embedded(_, data, _) => embedded.push(data.checksum),
+                ProcessOutput::Skipped(_, data) => skipped.push(data),
+                _ => {},
+            }
+        }
+        proc_fut.await??;
+
+        assert_eq!(embedded, vec!["c694e99230b3cbf36d8aef4131596864".to_string()]);
+        assert_eq!(skipped, vec![SkippedOutputData {
+            name: Some("mbox-message.eml".to_string()),
+            reason: SkipReason::MaxTotalBytes(3500),
+        }]);
+        Ok(())
+    }
+}
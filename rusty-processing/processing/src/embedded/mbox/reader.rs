@@ -0,0 +1,679 @@
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use log::{info, warn};
+use mail_parser::DateTime;
+
+use crate::processing::{MboxEnvelope, MboxVariant, ProcessContext};
+
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+
+/// A message read from an mbox file.
+///
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct MboxMessage {
+    /// The envelope of the message, from the `From ` line preceding it.
+    ///
+    pub(crate) envelope: MboxEnvelope,
+
+    /// The date of the envelope as a UNIX timestamp, if it has a valid one.
+    ///
+    pub(crate) envelope_timestamp: Option<i64>,
+
+    /// The content of the message, without the `From ` line and with quoted `From ` lines unquoted as its variant
+    /// requires.
+    ///
+    /// Left empty if the message is larger than the maximum size of the reader, see
+    /// [`MboxReader::set_max_message_size`].
+    ///
+    pub(crate) contents: Vec<u8>,
+
+    /// The size of the content of the message in bytes, even if it was left out.
+    ///
+    pub(crate) size: u64,
+}
+
+/// The number of bytes at the start of an mbox file its variant is detected from.
+///
+const DETECTION_SAMPLE_SIZE: u64 = 1024 * 1024;
+
+/// The maximum number of bytes of the headers of a message searched for their end when detecting the variant.
+///
+const MAX_HEADERS_SIZE: usize = 64 * 1024;
+
+/// Reads the messages of an mbox file of a [`MboxVariant`], keeping track of where each of them starts.
+///
+/// Lines before the first `From ` line are ignored. Messages of the variants separated by `Content-Length` headers
+/// fall back to being separated by `From ` lines if they have no such header, or if it does not end the message, e.g.
+/// because it is larger than the message and would take in the messages following it.
+///
+pub(crate) struct MboxReader<R> {
+    reader: R,
+    unread: io::Cursor<Vec<u8>>,
+    variant: MboxVariant,
+    offset: u64,
+    next_message: Option<MboxMessage>,
+    max_message_size: Option<u64>,
+    failed: bool,
+}
+
+impl MboxReader<BufReader<File>> {
+    /// Opens an mbox file, detecting its variant unless one is configured in the context.
+    ///
+    pub(crate) fn open(ctx: &ProcessContext, path: &Path) -> io::Result<Self> {
+        let variant = match ctx.config().mbox_variant() {
+            Some(variant) => variant,
+            None => {
+                let mut sample = vec![];
+                File::open(path)?.take(DETECTION_SAMPLE_SIZE).read_to_end(&mut sample)?;
+                detect_variant(&sample)
+            }
+        };
+        info!("Reading mbox file as {:?}", variant);
+        Ok(Self::new(BufReader::new(File::open(path)?), variant))
+    }
+}
+
+impl<R: BufRead> MboxReader<R> {
+    pub(crate) fn new(reader: R, variant: MboxVariant) -> Self {
+        Self {
+            reader,
+            unread: io::Cursor::new(vec![]),
+            variant,
+            offset: 0,
+            next_message: None,
+            max_message_size: None,
+            failed: false,
+        }
+    }
+
+    /// Returns the number of bytes read from the mbox file so far.
+    ///
+    pub(crate) fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Sets the maximum size of the contents of the messages read next, or [`None`] if unlimited.
+    ///
+    /// The contents of a larger message are not held in memory, but skipped up to the next `From ` line, regardless of
+    /// its `Content-Length` header.
+    ///
+    pub(crate) fn set_max_message_size(&mut self, max_message_size: Option<u64>) {
+        self.max_message_size = max_message_size;
+    }
+
+    /// Reads the next line with its offset, or [`None`] at the end of the file.
+    ///
+    /// A line longer than the maximum message size is cut short, skipping the rest of it.
+    ///
+    fn read_line(&mut self, line: &mut Vec<u8>) -> io::Result<Option<u64>> {
+        line.clear();
+        let line_offset = self.offset;
+        let max_line_size = self.max_message_size.map_or(u64::MAX, |max| max.saturating_add(1));
+        let mut reader = (&mut self.unread).chain(&mut self.reader);
+        let mut read = (&mut reader).take(max_line_size).read_until(b'\n', line)?;
+        if read as u64 == max_line_size && !line.ends_with(b"\n") {
+            read += reader.skip_until(b'\n')?;
+        }
+
+        match read {
+            0 => Ok(None),
+            read => {
+                self.offset += read as u64;
+                Ok(Some(line_offset))
+            }
+        }
+    }
+
+    /// Makes bytes that were read be read again, before the bytes following them.
+    ///
+    fn unread(&mut self, mut bytes: Vec<u8>) {
+        self.offset -= bytes.len() as u64;
+        let position = self.unread.position() as usize;
+        bytes.extend_from_slice(&self.unread.get_ref()[position..]);
+        self.unread = io::Cursor::new(bytes);
+    }
+
+    /// Adds a line of the message, unquoting it as the variant requires.
+    ///
+    fn push_line(&self, message: &mut MboxMessage, line: &[u8]) {
+        let quoted = match self.variant {
+            MboxVariant::Mboxo | MboxVariant::Mboxcl => line.starts_with(b">From "),
+            MboxVariant::Mboxrd => is_quoted_from(line),
+            MboxVariant::Mboxcl2 => false,
+        };
+        self.append(message, if quoted { &line[1..] } else { line });
+    }
+
+    /// Adds bytes to the contents of the message, leaving them out once it exceeds the maximum message size.
+    ///
+    fn append(&self, message: &mut MboxMessage, bytes: &[u8]) {
+        message.size += bytes.len() as u64;
+        if self.exceeds_max_size(message, 0) {
+            message.contents = vec![];
+        } else {
+            message.contents.extend_from_slice(bytes);
+        }
+    }
+
+    /// Returns whether the message exceeds the maximum message size, if the given number of bytes are added to it.
+    ///
+    fn exceeds_max_size(&self, message: &MboxMessage, additional: u64) -> bool {
+        self.max_message_size.is_some_and(|max| message.size.saturating_add(additional) > max)
+    }
+
+    /// Reads the headers of the message, returning the length of its body if it has a `Content-Length` header.
+    ///
+    /// Stops early if a `From ` line starts the next message before the headers ended.
+    ///
+    fn read_headers(&mut self, message: &mut MboxMessage) -> io::Result<Option<u64>> {
+        let mut line = Vec::with_capacity(80);
+        let mut content_length = None;
+        while let Some(line_offset) = self.read_line(&mut line)? {
+            if line.starts_with(b"From ") {
+                self.next_message = Some(MboxMessage::new(&line, line_offset));
+                return Ok(None);
+            }
+            self.append(message, &line);
+            if is_blank(&line) {
+                return Ok(content_length);
+            }
+            content_length = content_length.or_else(|| parse_content_length(&line));
+        }
+        Ok(None)
+    }
+
+    /// Reads the body of a message delimited by its `Content-Length` header, returning whether the header was right.
+    ///
+    /// If the header was wrong, the bytes read after the headers are unread, so they are split by `From ` lines instead.
+    ///
+    fn read_body(&mut self, message: &mut MboxMessage, content_length: u64) -> io::Result<bool> {
+        if self.exceeds_max_size(message, content_length) {
+            return Ok(false);
+        }
+
+        let mut body = vec![];
+        let read = (&mut self.unread).chain(&mut self.reader).take(content_length).read_to_end(&mut body)?;
+        self.offset += read as u64;
+
+        // The body is followed by blank lines separating it from the next message, which are not part of it
+        let mut line = Vec::with_capacity(80);
+        let mut separator = vec![];
+        let mut ended = read as u64 == content_length;
+        while ended {
+            let Some(line_offset) = self.read_line(&mut line)? else {
+                break;
+            };
+            if line.starts_with(b"From ") {
+                self.next_message = Some(MboxMessage::new(&line, line_offset));
+                break;
+            }
+            ended = is_blank(&line);
+            separator.append(&mut line);
+        }
+
+        if !ended {
+            warn!("Content-Length of message at offset {} does not end it", message.envelope.offset);
+            body.append(&mut separator);
+            self.unread(body);
+            return Ok(false);
+        }
+        for line in body.split_inclusive(|&byte| byte == b'\n') {
+            self.push_line(message, line);
+        }
+        Ok(true)
+    }
+
+    fn read_message(&mut self) -> io::Result<Option<MboxMessage>> {
+        let mut line = Vec::with_capacity(80);
+        let mut message = match self.next_message.take() {
+            Some(message) => message,
+            None => loop {
+                match self.read_line(&mut line)? {
+                    Some(line_offset) if line.starts_with(b"From ") => break MboxMessage::new(&line, line_offset),
+                    Some(_) => continue,
+                    None => return Ok(None),
+                }
+            },
+        };
+
+        if self.variant.uses_content_length() {
+            let content_length = self.read_headers(&mut message)?;
+            if self.next_message.is_some() {
+                return Ok(Some(message));
+            }
+            if let Some(content_length) = content_length {
+                if self.read_body(&mut message, content_length)? {
+                    return Ok(Some(message));
+                }
+            }
+        }
+
+        // A blank line before the next `From ` line or the end of the file separates the messages and is not part of
+        // the message, as for the variants separated by `Content-Length` headers
+        let mut separator: Option<Vec<u8>> = None;
+        while let Some(line_offset) = self.read_line(&mut line)? {
+            if line.starts_with(b"From ") {
+                self.next_message = Some(MboxMessage::new(&line, line_offset));
+                break;
+            }
+            if let Some(separator) = separator.take() {
+                self.push_line(&mut message, &separator);
+            }
+            if is_blank(&line) {
+                separator = Some(line.clone());
+            } else {
+                self.push_line(&mut message, &line);
+            }
+        }
+        Ok(Some(message))
+    }
+}
+
+impl<R: BufRead> Iterator for MboxReader<R> {
+    type Item = io::Result<MboxMessage>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        match self.read_message() {
+            Ok(message) => message.map(Ok),
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl MboxMessage {
+    /// Starts a message at its `From ` line.
+    ///
+    fn new(line: &[u8], offset: u64) -> Self {
+        let (envelope, envelope_timestamp) = parse_envelope(line, offset);
+        Self { envelope, envelope_timestamp, contents: Vec::with_capacity(1024), size: 0 }
+    }
+}
+
+/// Detects the variant of an mbox file from a sample of its start.
+///
+/// The file is read as [`MboxVariant::Mboxcl2`] or [`MboxVariant::Mboxcl`] if the `Content-Length` headers of its
+/// messages end them, depending on whether their bodies have unquoted or quoted `From ` lines. Otherwise, it is read as
+/// [`MboxVariant::Mboxrd`], which reads files of [`MboxVariant::Mboxo`] the same way unless they have lines starting
+/// with `From ` quoted more than once.
+///
+pub(crate) fn detect_variant(sample: &[u8]) -> MboxVariant {
+    let mut delimited = 0;
+    let mut unquoted_from = false;
+    let mut quoted_from = false;
+    let from_lines = (0..sample.len())
+        .filter(|&index| (index == 0 || sample[index - 1] == b'\n') && sample[index..].starts_with(b"From "));
+    for from_line in from_lines {
+        let Some(headers_end) = find_headers_end(&sample[from_line..]).map(|end| from_line + end) else {
+            continue;
+        };
+        let Some(content_length) = sample[from_line..headers_end]
+            .split_inclusive(|&byte| byte == b'\n')
+            .find_map(parse_content_length) else {
+            continue;
+        };
+        let body_end = headers_end.saturating_add(content_length as usize);
+        if body_end > sample.len() {
+            continue;
+        }
+
+        let rest = &sample[body_end..];
+        let separator = rest.iter().position(|byte| !byte.is_ascii_whitespace()).unwrap_or(rest.len());
+        if separator < rest.len() && !rest[separator..].starts_with(b"From ") {
+            return MboxVariant::Mboxrd;
+        }
+        delimited += 1;
+        for line in sample[headers_end..body_end].split(|&byte| byte == b'\n') {
+            unquoted_from |= line.starts_with(b"From ");
+            quoted_from |= line.starts_with(b">From ");
+        }
+    }
+
+    match (delimited, unquoted_from, quoted_from) {
+        (0, _, _) => MboxVariant::Mboxrd,
+        (_, false, true) => MboxVariant::Mboxcl,
+        _ => MboxVariant::Mboxcl2,
+    }
+}
+
+/// Returns the offset of the body of a message starting with its `From ` line, after the blank line ending its headers.
+///
+/// Gives up at the next `From ` line or after [`MAX_HEADERS_SIZE`] bytes, so a sample is not scanned to its end for
+/// each message without a blank line.
+///
+fn find_headers_end(message: &[u8]) -> Option<usize> {
+    let mut lines = message.split_inclusive(|&byte| byte == b'\n');
+    let mut offset = lines.next()?.len();
+    for line in lines {
+        if line.starts_with(b"From ") || offset > MAX_HEADERS_SIZE {
+            return None;
+        }
+        offset += line.len();
+        if is_blank(line) {
+            return Some(offset);
+        }
+    }
+    None
+}
+
+/// Parses the value of a `Content-Length` header line.
+///
+fn parse_content_length(line: &[u8]) -> Option<u64> {
+    let (name, value) = line.split_at(line.iter().position(|&byte| byte == b':')?);
+    if !name.eq_ignore_ascii_case(b"Content-Length") {
+        return None;
+    }
+    std::str::from_utf8(&value[1..]).ok()?.trim().parse().ok()
+}
+
+fn is_blank(line: &[u8]) -> bool {
+    line == b"\n" || line == b"\r\n"
+}
+
+/// Returns whether the line is a `From ` line quoted by one or more `>`.
+///
+fn is_quoted_from(line: &[u8]) -> bool {
+    let unquoted = line.iter().position(|&byte| byte != b'>').unwrap_or(line.len());
+    unquoted > 0 && line[unquoted..].starts_with(b"From ")
+}
+
+/// Parses the sender and date of a `From ` line, e.g. `From gab@hrp.no Mon Aug 08 05:34:13 2005`.
+///
+fn parse_envelope(line: &[u8], offset: u64) -> (MboxEnvelope, Option<i64>) {
+    let line = String::from_utf8_lossy(line);
+    let mut fields = line["From ".len()..].split_whitespace();
+    let sender = fields.next().unwrap_or_default().to_string();
+    let timestamp = parse_envelope_date(fields).map(|date| date.to_timestamp());
+
+    let envelope = MboxEnvelope {
+        sender,
+        date: timestamp.map(|timestamp| DateTime::from_timestamp(timestamp).to_rfc3339()),
+        offset,
+    };
+    (envelope, timestamp)
+}
+
+/// Parses the date of a `From ` line in the format of C's `asctime`, e.g. `Mon Aug  8 05:34:13 2005`, optionally with
+/// a time zone offset such as `+0200` anywhere after the weekday.
+///
+fn parse_envelope_date<'a>(fields: impl Iterator<Item = &'a str>) -> Option<DateTime> {
+    let mut date = DateTime {
+        year: 0,
+        month: 0,
+        day: 0,
+        hour: u8::MAX,
+        minute: u8::MAX,
+        second: 0,
+        tz_before_gmt: false,
+        tz_hour: 0,
+        tz_minute: 0,
+    };
+
+    for field in fields {
+        if let Some(month) = MONTHS.iter().position(|month| field.eq_ignore_ascii_case(month)) {
+            date.month = month as u8 + 1;
+        } else if field.contains(':') {
+            let mut parts = field.split(':').map(|part| part.parse::<u8>().ok());
+            date.hour = parts.next().flatten()?;
+            date.minute = parts.next().flatten()?;
+            date.second = parts.next().flatten().unwrap_or(0);
+        } else if let (Some(sign @ ('+' | '-')), 5) = (field.chars().next(), field.len()) {
+            date.tz_before_gmt = sign == '-';
+            date.tz_hour = field.get(1..3)?.parse().ok()?;
+            date.tz_minute = field.get(3..5)?.parse().ok()?;
+        } else if let Ok(number) = field.parse::<u16>() {
+            if field.len() == 4 {
+                date.year = number;
+            } else if date.day == 0 && number <= 31 {
+                date.day = number as u8;
+            }
+        }
+    }
+
+    date.is_valid().then_some(date)
+}
+
+#[cfg(test)]
+mod tests {
+    use identify::deduplication::dedupe_checksum;
+
+    use super::*;
+
+    const CONTENT_LENGTH_DELIMITED: &[u8] = b"\
+From phillip.allen@enron.com Mon May 14 16:39:00 2001
+Subject: First
+Content-Length: 26
+
+From the start
+>From kept
+
+From tim.belden@enron.com Mon May 14 17:00:00 2001
+Subject: Second
+Content-Length: 5
+
+Body
+";
+
+    fn read(content: &[u8], variant: MboxVariant) -> Vec<MboxMessage> {
+        MboxReader::new(content, variant).collect::<io::Result<Vec<_>>>().unwrap()
+    }
+
+    fn contents(messages: &[MboxMessage]) -> Vec<&[u8]> {
+        messages.iter().map(|message| message.contents.as_slice()).collect()
+    }
+
+    #[test]
+    fn test_read() {
+        let content = b"\
+preamble
+From gab@hrp.no Mon Aug  8 05:34:13 2005
+Subject: First
+
+>From the start
+>>From quoted twice
+> From not quoted
+From MAILER-DAEMON Tue Aug 09 10:00:00 +0200 2005
+Subject: Second
+
+Body
+";
+
+        let messages = read(content, MboxVariant::Mboxrd);
+
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].envelope, MboxEnvelope {
+            sender: "gab@hrp.no".to_string(),
+            date: Some("2005-08-08T05:34:13Z".to_string()),
+            offset: 9,
+        });
+        assert_eq!(messages[0].contents, b"Subject: First\n\nFrom the start\n>From quoted twice\n> From not quoted\n");
+        assert_eq!(messages[1].envelope, MboxEnvelope {
+            sender: "MAILER-DAEMON".to_string(),
+            date: Some("2005-08-09T08:00:00Z".to_string()),
+            offset: 120,
+        });
+        assert_eq!(messages[1].contents, b"Subject: Second\n\nBody\n");
+    }
+
+    #[test]
+    fn test_read_invalid_envelope() {
+        let messages = read(b"From \nSubject: Empty\n", MboxVariant::Mboxrd);
+
+        assert_eq!(messages[0].envelope, MboxEnvelope { sender: "".to_string(), date: None, offset: 0 });
+        assert_eq!(messages[0].envelope_timestamp, None);
+    }
+
+    #[test]
+    fn test_read_empty() {
+        for variant in [MboxVariant::Mboxo, MboxVariant::Mboxrd, MboxVariant::Mboxcl, MboxVariant::Mboxcl2] {
+            assert!(read(b"", variant).is_empty());
+            assert!(read(b"no messages\n", variant).is_empty());
+        }
+    }
+
+    #[test]
+    fn test_read_mboxo() {
+        let content = b"From gab@hrp.no Mon Aug  8 05:34:13 2005\n\n>From the start\n>>From quoted twice\n";
+
+        let messages = read(content, MboxVariant::Mboxo);
+
+        assert_eq!(contents(&messages), vec![b"\nFrom the start\n>>From quoted twice\n".as_slice()]);
+    }
+
+    #[test]
+    fn test_read_mboxcl2() {
+        let messages = read(CONTENT_LENGTH_DELIMITED, MboxVariant::Mboxcl2);
+
+        assert_eq!(contents(&messages), vec![
+            b"Subject: First\nContent-Length: 26\n\nFrom the start\n>From kept\n".as_slice(),
+            b"Subject: Second\nContent-Length: 5\n\nBody\n".as_slice(),
+        ]);
+        assert_eq!(messages[1].envelope.offset, 116);
+    }
+
+    #[test]
+    fn test_read_mboxcl() {
+        let messages = read(CONTENT_LENGTH_DELIMITED, MboxVariant::Mboxcl);
+
+        assert_eq!(contents(&messages)[0], b"Subject: First\nContent-Length: 26\n\nFrom the start\nFrom kept\n");
+    }
+
+    #[test]
+    fn test_read_wrong_content_length() {
+        let content = b"\
+From phillip.allen@enron.com Mon May 14 16:39:00 2001
+Content-Length: 5
+
+Hello world
+>From quoted
+
+From tim.belden@enron.com Mon May 14 17:00:00 2001
+
+Body
+";
+
+        let messages = read(content, MboxVariant::Mboxcl);
+
+        assert_eq!(contents(&messages), vec![
+            b"Content-Length: 5\n\nHello world\nFrom quoted\n".as_slice(),
+            b"\nBody\n".as_slice(),
+        ]);
+    }
+
+    #[test]
+    fn test_read_too_large_content_length() {
+        let content = b"\
+From phillip.allen@enron.com Mon May 14 16:39:00 2001
+Content-Length: 40
+
+Hello
+From tim.belden@enron.com Mon May 14 17:00:00 2001
+Subject: Second
+
+Body
+From jeff@enron.com Mon May 14 18:00:00 2001
+Content-Length: 200
+
+Last
+";
+
+        let messages = read(content, MboxVariant::Mboxcl2);
+
+        assert_eq!(contents(&messages), vec![
+            b"Content-Length: 40\n\nHello\n".as_slice(),
+            b"Subject: Second\n\nBody\n".as_slice(),
+            b"Content-Length: 200\n\nLast\n".as_slice(),
+        ]);
+        let senders = messages.iter().map(|message| message.envelope.sender.as_str()).collect::<Vec<_>>();
+        assert_eq!(senders, vec!["phillip.allen@enron.com", "tim.belden@enron.com", "jeff@enron.com"]);
+        assert_eq!(messages[1].envelope.offset, 80);
+        assert_eq!(messages[2].envelope.offset, 153);
+    }
+
+    #[test]
+    fn test_read_max_message_size() {
+        let long_line = format!("{}\n", "x".repeat(100));
+        let cases = [(MboxVariant::Mboxrd, 0), (MboxVariant::Mboxcl2, 202), (MboxVariant::Mboxcl2, u64::MAX)];
+        for (variant, content_length) in cases {
+            let content = format!("\
+From phillip.allen@enron.com Mon May 14 16:39:00 2001
+Content-Length: {content_length}
+
+{long_line}{long_line}
+From tim.belden@enron.com Mon May 14 17:00:00 2001
+Subject: Small
+
+Body
+");
+            let mut reader = MboxReader::new(content.as_bytes(), variant);
+            reader.set_max_message_size(Some(50));
+            let messages = reader.collect::<io::Result<Vec<_>>>().unwrap();
+
+            assert_eq!(messages.len(), 2, "{:?} {}", variant, content_length);
+            assert!(messages[0].contents.is_empty());
+            assert!(messages[0].size > 50);
+            assert_eq!(messages[1].contents, b"Subject: Small\n\nBody\n");
+            assert_eq!(messages[1].size, 21);
+            assert_eq!(messages[1].envelope.offset, content.find("From tim").unwrap() as u64);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_read_same_message_for_each_variant() -> anyhow::Result<()> {
+        let content = b"\
+From phillip.allen@enron.com Mon May 14 16:39:00 2001
+Subject: Same
+Content-Length: 5
+
+Body
+
+From tim.belden@enron.com Mon May 14 17:00:00 2001
+Subject: Other
+
+Body
+";
+
+        let mut checksums = vec![];
+        for variant in [MboxVariant::Mboxo, MboxVariant::Mboxrd, MboxVariant::Mboxcl, MboxVariant::Mboxcl2] {
+            let messages = read(content, variant);
+            assert_eq!(messages[0].contents, b"Subject: Same\nContent-Length: 5\n\nBody\n", "{:?}", variant);
+            assert_eq!(messages[1].contents, b"Subject: Other\n\nBody\n", "{:?}", variant);
+            checksums.push(dedupe_checksum(&mut messages[0].contents.as_slice(), "message/rfc822").await?);
+        }
+
+        checksums.dedup();
+        assert_eq!(checksums.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_variant_without_blank_lines() {
+        let content = b"From gab@hrp.no Mon Aug  8 05:34:13 2005\nSubject: No body\n".repeat(20_000);
+
+        assert_eq!(detect_variant(&content), MboxVariant::Mboxrd);
+    }
+
+    #[test]
+    fn test_detect_variant() {
+        assert_eq!(detect_variant(CONTENT_LENGTH_DELIMITED), MboxVariant::Mboxcl2);
+
+        let quoted = String::from_utf8_lossy(CONTENT_LENGTH_DELIMITED).replace("\nFrom the start", "\n>From the start");
+        let quoted = quoted.replace("Content-Length: 26", "Content-Length: 27");
+        assert_eq!(detect_variant(quoted.as_bytes()), MboxVariant::Mboxcl);
+
+        let wrong = String::from_utf8_lossy(CONTENT_LENGTH_DELIMITED).replace("Content-Length: 26", "Content-Length: 20");
+        assert_eq!(detect_variant(wrong.as_bytes()), MboxVariant::Mboxrd);
+
+        assert_eq!(detect_variant(b"From gab@hrp.no Mon Aug  8 05:34:13 2005\n\n>From the start\n"), MboxVariant::Mboxrd);
+        assert_eq!(detect_variant(b""), MboxVariant::Mboxrd);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::processing::{ExtractionLimits, ProcessContext, SkipReason};
+
 mod mbox;
 mod rfc822;
 mod zip;
@@ -5,3 +7,182 @@ mod zip;
 pub use mbox::*;
 pub use rfc822::*;
 pub use zip::*;
+
+/// Enforces the configured [`ExtractionLimits`] on the files extracted from a single container.
+///
+pub(crate) struct ExtractionGuard {
+    limits: ExtractionLimits,
+    depth: usize,
+    entries: usize,
+    total_bytes: u64,
+}
+
+impl ExtractionGuard {
+    /// Creates a guard for the container being processed in the given context.
+    ///
+    pub fn new(ctx: &ProcessContext) -> Self {
+        Self {
+            limits: ctx.config().limits().clone(),
+            depth: ctx.state.depth(),
+            entries: 0,
+            total_bytes: 0,
+        }
+    }
+
+    /// Checks whether files embedded in the container are allowed to be extracted at all.
+    ///
+    pub fn check_depth(&self) -> Result<(), SkipReason> {
+        match self.limits.max_depth {
+            Some(max_depth) if self.depth + 1 > max_depth => Err(SkipReason::MaxDepth(max_depth)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks whether an entry is allowed to be extracted, counting it towards the limits if it is.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The uncompressed size of the entry in bytes.
+    /// * `compressed_size` - The compressed size of the entry in bytes, if the entry is compressed.
+    ///
+    pub fn admit(&mut self, size: u64, compressed_size: Option<u64>) -> Result<(), SkipReason> {
+        if let Some(max_entries) = self.limits.max_entries {
+            if self.entries >= max_entries {
+                return Err(SkipReason::MaxEntries(max_entries));
+            }
+        }
+        if let (Some(max_ratio), Some(compressed_size)) = (self.limits.max_compression_ratio, compressed_size) {
+            if size as f64 / compressed_size.max(1) as f64 > max_ratio {
+                return Err(SkipReason::MaxCompressionRatio(max_ratio));
+            }
+        }
+        if let Some(max_total_bytes) = self.limits.max_total_bytes {
+            if self.total_bytes + size > max_total_bytes {
+                return Err(SkipReason::MaxTotalBytes(max_total_bytes));
+            }
+        }
+
+        self.entries += 1;
+        self.total_bytes += size;
+        Ok(())
+    }
+
+    /// Returns how many bytes an admitted entry may actually extract to, and the reason to skip it if it extracts to
+    /// more, as the sizes declared by a container can not be trusted.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - The uncompressed size of the entry in bytes it was admitted with.
+    /// * `compressed_size` - The compressed size of the entry in bytes, if the entry is compressed.
+    ///
+    pub fn max_extracted_size(&self, size: u64, compressed_size: Option<u64>) -> Option<(u64, SkipReason)> {
+        let by_ratio = match (self.limits.max_compression_ratio, compressed_size) {
+            (Some(max_ratio), Some(compressed_size)) => {
+                Some(((max_ratio * compressed_size.max(1) as f64) as u64, SkipReason::MaxCompressionRatio(max_ratio)))
+            },
+            _ => None,
+        };
+        let by_total_bytes = self.limits.max_total_bytes.map(|max_total_bytes| {
+            (max_total_bytes.saturating_sub(self.total_bytes - size), SkipReason::MaxTotalBytes(max_total_bytes))
+        });
+        [by_ratio, by_total_bytes].into_iter()
+            .flatten()
+            .min_by_key(|(max_size, _)| *max_size)
+    }
+
+    /// Returns how many bytes the entries still to be extracted may extract to in total, or [`None`] if unlimited.
+    ///
+    pub fn remaining_bytes(&self) -> Option<u64> {
+        self.limits.max_total_bytes.map(|max_total_bytes| max_total_bytes.saturating_sub(self.total_bytes))
+    }
+
+    /// Counts the number of bytes an admitted entry actually extracted to towards the limits, instead of its size.
+    ///
+    pub fn extracted(&mut self, size: u64, extracted_size: u64) {
+        self.total_bytes = self.total_bytes - size + extracted_size;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::processing::{ProcessContextBuilder, ProcessorBuilder};
+
+    use super::*;
+
+    fn guard(builder: ProcessorBuilder, id_chain: Vec<String>) -> ExtractionGuard {
+        let (output_sink, _) = tokio::sync::mpsc::channel(1);
+        let config = Arc::new(builder.build().config().clone());
+        let ctx = ProcessContextBuilder::new("application/zip", vec![], output_sink)
+            .id_chain(id_chain)
+            .build()
+            .with_config(config);
+        ExtractionGuard::new(&ctx)
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let mut guard = guard(ProcessorBuilder::new(), vec!["a".to_string(); 100]);
+
+        assert_eq!(guard.check_depth(), Ok(()));
+        for _ in 0..1000 {
+            assert_eq!(guard.admit(u32::MAX as u64, Some(1)), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let builder = ProcessorBuilder::new().max_depth(2);
+
+        assert_eq!(guard(builder.clone(), vec![]).check_depth(), Ok(()));
+        assert_eq!(guard(builder.clone(), vec!["a".to_string()]).check_depth(), Ok(()));
+        assert_eq!(
+            guard(builder, vec!["a".to_string(), "b".to_string()]).check_depth(),
+            Err(SkipReason::MaxDepth(2)),
+        );
+    }
+
+    #[test]
+    fn test_max_entries() {
+        let mut guard = guard(ProcessorBuilder::new().max_entries(2), vec![]);
+
+        assert_eq!(guard.admit(10, None), Ok(()));
+        assert_eq!(guard.admit(10, None), Ok(()));
+        assert_eq!(guard.admit(10, None), Err(SkipReason::MaxEntries(2)));
+    }
+
+    #[test]
+    fn test_max_total_bytes() {
+        let mut guard = guard(ProcessorBuilder::new().max_total_bytes(100), vec![]);
+
+        assert_eq!(guard.admit(60, None), Ok(()));
+        assert_eq!(guard.remaining_bytes(), Some(40));
+        assert_eq!(guard.admit(60, None), Err(SkipReason::MaxTotalBytes(100)));
+        assert_eq!(guard.admit(40, None), Ok(()));
+        assert_eq!(guard.remaining_bytes(), Some(0));
+    }
+
+    #[test]
+    fn test_max_compression_ratio() {
+        let mut guard = guard(ProcessorBuilder::new().max_compression_ratio(10.0), vec![]);
+
+        assert_eq!(guard.admit(100, Some(10)), Ok(()));
+        assert_eq!(guard.admit(1000, Some(10)), Err(SkipReason::MaxCompressionRatio(10.0)));
+        assert_eq!(guard.admit(1000, None), Ok(()));
+    }
+
+    #[test]
+    fn test_max_extracted_size() {
+        let mut guard = guard(ProcessorBuilder::new().max_total_bytes(100).max_compression_ratio(10.0), vec![]);
+
+        assert_eq!(guard.admit(20, Some(5)), Ok(()));
+        assert_eq!(guard.max_extracted_size(20, Some(5)), Some((50, SkipReason::MaxCompressionRatio(10.0))));
+        assert_eq!(guard.max_extracted_size(20, None), Some((100, SkipReason::MaxTotalBytes(100))));
+
+        guard.extracted(20, 70);
+        assert_eq!(guard.admit(20, None), Ok(()));
+        assert_eq!(guard.max_extracted_size(20, None), Some((30, SkipReason::MaxTotalBytes(100))));
+        assert_eq!(guard.admit(20, None), Err(SkipReason::MaxTotalBytes(100)));
+    }
+}
@@ -1,16 +1,22 @@
 use std::fmt::Debug;
-use std::io::Cursor;
 use std::path::Path;
 
 use anyhow::{anyhow, Context};
 use async_trait::async_trait;
-use mail_parser::{Message, MessageParser, MessagePartId, MimeHeaders};
-use tempfile::{NamedTempFile, TempPath};
+use log::warn;
+use mail_parser::{Message, MessageParser, MessagePart, MessagePartId, MimeHeaders};
+use tempfile::TempPath;
 
-use identify::deduplication::dedupe_checksum;
+use identify::deduplication::dedupe_checksum_from_path;
+use identify::mimetype::identify_mimetype_with_method;
 
-use crate::mimetype;
-use crate::processing::{Process, ProcessContext, ProcessOutput};
+use crate::{is_generic_mimetype, mimetype};
+use crate::embedded::ExtractionGuard;
+use crate::processing::{DetectedMimetype, Process, ProcessContext, ProcessError, ProcessErrorKind, ProcessOutput};
+
+/// The name given to attachments without a file name.
+///
+pub(crate) const UNNAMED_ATTACHMENT: &str = "message-attachment.dat";
 
 #[derive(Debug, Default)]
 pub struct Rfc822EmbeddedProcessor {
@@ -21,26 +27,106 @@ impl Rfc822EmbeddedProcessor {
     async fn process_part(
         &self,
         ctx: &ProcessContext,
+        guard: &mut ExtractionGuard,
         message: &Message<'_>,
         part_id: &MessagePartId
-    ) -> Result<ProcessOutput, anyhow::Error> {
+    ) -> Result<ProcessOutput, ProcessError> {
         let part = message
             .part(*part_id)
-            .ok_or(anyhow!("failed to get attachment part"))?;
-        let content_type = part
-            .content_type()
-            .ok_or(anyhow!("failed to get attachment content type"))?;
-        let mimetype = mimetype(content_type);
+            .ok_or_else(|| ProcessError::new(ProcessErrorKind::Corrupt, anyhow!("failed to get attachment part")))?;
+        let name = part.attachment_name().unwrap_or(UNNAMED_ATTACHMENT);
+
+        if let Err(reason) = guard.admit(part.contents().len() as u64, None) {
+            warn!("Skipping attachment {}: {}", name, reason);
+            return Ok(ProcessOutput::skipped(ctx, Some(name.to_string()), reason));
+        }
+
+        let attachment = extract_attachment(ctx, part).await?;
+        Ok(ProcessOutput::embedded(ctx, name, attachment.path, attachment.mimetype, attachment.checksum)
+            .with_content_checksum(attachment.content_checksum)
+            .with_detected_mimetype(attachment.detected_mimetype))
+    }
+}
+
+/// An attachment of a message written to a temporary file and identified.
+///
+pub(crate) struct ExtractedAttachment {
+    pub(crate) path: TempPath,
+    pub(crate) mimetype: String,
+    pub(crate) detected_mimetype: Option<DetectedMimetype>,
+    pub(crate) checksum: String,
+    pub(crate) content_checksum: String,
+}
+
+/// Writes the attachment to a temporary file, and identifies its MIME type and dedupe checksum as it is embedded.
+///
+pub(crate) async fn extract_attachment(
+    ctx: &ProcessContext,
+    part: &MessagePart<'_>,
+) -> Result<ExtractedAttachment, ProcessError> {
+    let content_type = part
+        .content_type()
+        .ok_or_else(|| ProcessError::new(ProcessErrorKind::Corrupt, anyhow!("failed to get attachment content type")))?;
+    let declared_mimetype = mimetype(content_type);
 
-        let mut reader = Cursor::new(part.contents());
-        let checksum = dedupe_checksum(&mut reader, &mimetype).await?;
-        let name = part.attachment_name().unwrap_or("message-attachment.dat");
+    let mut file = ctx.temp_file()?;
+    std::io::copy(&mut part.contents(), &mut file)?;
+    let path = file.into_temp_path();
 
-        let mut file = NamedTempFile::new()?;
-        std::io::copy(&mut part.contents(), &mut file)?;
+    let detected_mimetype = detect_attachment_mimetype(ctx, &path, &declared_mimetype).await;
+    let mimetype = detected_mimetype.as_ref()
+        .map(|detected| detected.mimetype.clone())
+        .unwrap_or(declared_mimetype);
+    let checksum = dedupe_checksum_from_path(&path, &mimetype).await?;
+    let content_checksum = format!("{:x}", md5::compute(part.contents()));
 
-        Ok(ProcessOutput::embedded(&ctx, name, file.into_temp_path(), mimetype, checksum))
+    Ok(ExtractedAttachment { path, mimetype, detected_mimetype, checksum, content_checksum })
+}
+
+/// Detects the MIME type of an attachment when its declared Content-Type can not be trusted.
+///
+/// Attachments declared as `message/*` are never detected, so they are still processed as messages, see
+/// [`overrides_declared_mimetype`].
+///
+async fn detect_attachment_mimetype(
+    ctx: &ProcessContext,
+    path: &Path,
+    declared_mimetype: &str,
+) -> Option<DetectedMimetype> {
+    if top_level_type(declared_mimetype) == "message" {
+        return None;
+    }
+
+    let (mimetype, method) = match identify_mimetype_with_method(path, ctx.config().tika()).await {
+        Ok(identified) => identified?,
+        Err(err) => {
+            warn!("Failed to detect MIME type of attachment, using declared '{}': {:?}", declared_mimetype, err);
+            return None;
+        }
+    };
+    overrides_declared_mimetype(declared_mimetype, &mimetype).then_some(DetectedMimetype { mimetype, method })
+}
+
+/// Returns whether the MIME type detected for an attachment is used instead of its declared one.
+///
+/// The detected MIME type is used when the declared one is generic, or when the two do not even agree on the top-level
+/// type and the detected one is more specific than plain text, e.g. a PDF sent as `image/jpeg`. Detection is not
+/// precise enough to overrule a declared subtype otherwise, as e.g. a DOCX file is detected as a ZIP file from its
+/// content alone, and a message is detected as plain text. Declared `message/*` types are never overruled.
+///
+fn overrides_declared_mimetype(declared_mimetype: &str, detected_mimetype: &str) -> bool {
+    if detected_mimetype.eq_ignore_ascii_case(declared_mimetype) || top_level_type(declared_mimetype) == "message" {
+        return false;
     }
+    is_generic_mimetype(declared_mimetype) || (
+        top_level_type(detected_mimetype) != top_level_type(declared_mimetype)
+            && !is_generic_mimetype(detected_mimetype)
+            && !detected_mimetype.eq_ignore_ascii_case("text/plain")
+    )
+}
+
+fn top_level_type(mimetype: &str) -> String {
+    mimetype.split('/').next().unwrap_or_default().trim().to_lowercase()
 }
 
 #[async_trait]
@@ -51,15 +137,28 @@ impl Process for Rfc822EmbeddedProcessor {
         input_path: &Path,
         _: TempPath,
         _: &str,
-    ) -> Result<(), anyhow::Error> {
+    ) -> Result<(), ProcessError> {
         let content = std::fs::read(input_path)
             .context("failed to read input file")?;
 
         let message = self.message_parser.parse(&content)
-            .context("failed to parse message")?;
+            .ok_or_else(|| ProcessError::new(ProcessErrorKind::Corrupt, anyhow!("failed to parse message")))?;
+
+        let mut guard = ExtractionGuard::new(&ctx);
+        if !message.attachments.is_empty() {
+            if let Err(reason) = guard.check_depth() {
+                warn!("Skipping attachments: {}", reason);
+                ctx.add_output(Ok(ProcessOutput::skipped(&ctx, None, reason))).await?;
+                return Ok(());
+            }
+        }
 
         for part_id in &message.attachments {
-            ctx.add_output(self.process_part(&ctx, &message, part_id).await).await?;
+            if ctx.is_cancelled() {
+                return Err(ProcessError::cancelled());
+            }
+
+            ctx.add_output(self.process_part(&ctx, &mut guard, &message, part_id).await).await?;
         }
 
         Ok(())
@@ -69,3 +168,40 @@ impl Process for Rfc822EmbeddedProcessor {
         "RFC 822 Embedded"
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::processing::ProcessContextBuilder;
+
+    use super::*;
+
+    #[test]
+    fn test_overrides_declared_mimetype() {
+        assert!(overrides_declared_mimetype("application/octet-stream", "application/pdf"));
+        assert!(overrides_declared_mimetype("application/octet-stream", "text/plain"));
+        assert!(overrides_declared_mimetype("image/jpeg", "application/pdf"));
+
+        assert!(!overrides_declared_mimetype("application/pdf", "application/pdf"));
+        let docx = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+        assert!(!overrides_declared_mimetype(docx, "application/zip"));
+        assert!(!overrides_declared_mimetype("image/jpeg", "text/plain"));
+    }
+
+    #[test]
+    fn test_overrides_declared_mimetype_of_message() {
+        assert!(!overrides_declared_mimetype("message/rfc822", "text/plain"));
+        assert!(!overrides_declared_mimetype("message/rfc822", "application/vnd.ms-outlook"));
+        assert!(!overrides_declared_mimetype("Message/RFC822", "text/plain"));
+    }
+
+    #[tokio::test]
+    async fn test_detect_attachment_mimetype_of_message() -> anyhow::Result<()> {
+        let (output_sink, _outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("message/rfc822", vec![], output_sink).build();
+        let path = tempfile::NamedTempFile::new()?.into_temp_path();
+        std::fs::write(&path, "Subject: Forwarded\n\nPlain text body\n")?;
+
+        assert_eq!(detect_attachment_mimetype(&ctx, &path, "message/rfc822").await, None);
+        Ok(())
+    }
+}
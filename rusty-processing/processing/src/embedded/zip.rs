@@ -8,17 +8,21 @@ use async_trait::async_trait;
 use futures::{pin_mut, StreamExt};
 use log::{debug, info, warn};
 use serde::{Deserialize, Serialize};
-use tempfile::{NamedTempFile, TempPath};
-use zip::ZipArchive;
+use tempfile::TempPath;
+use zip::{CompressionMethod, ZipArchive};
 
 use identify::deduplication::dedupe_checksum_from_path;
-use identify::mimetype::identify_mimetype;
+use identify::mimetype::identify_mimetype_with_method;
 
-use crate::processing::{Process, ProcessContext, ProcessOutput};
+use crate::embedded::ExtractionGuard;
+use crate::processing::{
+    DetectedMimetype, Process, ProcessContext, ProcessError, ProcessErrorKind, ProcessOutput, Progress, SkipReason,
+};
 
 enum NextArchiveEntry {
     Dir(String),
     File(ArchiveEntry),
+    Skipped(String, SkipReason),
 }
 
 struct ArchiveEntry {
@@ -26,6 +30,7 @@ struct ArchiveEntry {
     path: TempPath,
     checksum: String,
     mimetype: String,
+    detected_mimetype: Option<DetectedMimetype>,
 }
 
 #[derive(Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
@@ -39,7 +44,14 @@ impl Process for ZipEmbeddedProcessor {
         path:&Path,
         _: TempPath,
         _: &str,
-    ) -> Result<(), anyhow::Error> {
+    ) -> Result<(), ProcessError> {
+        let mut guard = ExtractionGuard::new(&ctx);
+        if let Err(reason) = guard.check_depth() {
+            warn!("Skipping zip entries: {}", reason);
+            ctx.add_output(Ok(ProcessOutput::skipped(&ctx, None, reason))).await?;
+            return Ok(());
+        }
+
         info!("Opening zip file");
         let file = std::fs::File::open(path)
             .context("failed to open zip file")?;
@@ -49,27 +61,45 @@ impl Process for ZipEmbeddedProcessor {
             .context("failed to open zip archive")?;
 
         info!("Streaming zip file entries");
+        let total_entries = archive.len() as u64;
+        let stream_ctx = &ctx;
+        let stream_guard = &mut guard;
         let output_stream = stream! {
             for i in 0..archive.len() {
-                yield next_archive_entry(&mut archive, i).await;
+                yield next_archive_entry(stream_ctx, stream_guard, &mut archive, i).await;
             }
         };
 
         pin_mut!(output_stream);
+        let mut entries = 0;
         while let Some(result) = output_stream.next().await {
+            if ctx.is_cancelled() {
+                return Err(ProcessError::cancelled());
+            }
+
+            if result.is_ok() {
+                entries += 1;
+            }
             match result {
                 Ok(NextArchiveEntry::File(entry)) => {
                     info!("Discovered entry {}", entry.name);
-                    let ArchiveEntry { name, path, checksum: dedupe_checksum, mimetype } = entry;
-                    let output = ProcessOutput::embedded(&ctx, name, path, mimetype, dedupe_checksum);
+                    let ArchiveEntry { name, path, checksum: dedupe_checksum, mimetype, detected_mimetype } = entry;
+                    let output = ProcessOutput::embedded(&ctx, name, path, mimetype, dedupe_checksum)
+                        .with_detected_mimetype(detected_mimetype);
                     ctx.add_output(Ok(output)).await?;
                 },
                 Ok(NextArchiveEntry::Dir(name)) => debug!("Discovered directory {}", name),
+                Ok(NextArchiveEntry::Skipped(name, reason)) => {
+                    warn!("Skipping entry {}: {}", name, reason);
+                    ctx.add_output(Ok(ProcessOutput::skipped(&ctx, Some(name), reason))).await?;
+                },
                 Err(e) => {
                     warn!("Failed to read entry: {}", e);
                     ctx.add_output(Err(e)).await?;
                 },
             }
+
+            ctx.progress(Progress::entries(entries, Some(total_entries))).await?;
         }
 
         Ok(())
@@ -80,7 +110,12 @@ impl Process for ZipEmbeddedProcessor {
     }
 }
 
-async fn next_archive_entry<R>(archive: &mut ZipArchive<R>, index: usize) -> Result<NextArchiveEntry, anyhow::Error>
+async fn next_archive_entry<R>(
+    ctx: &ProcessContext,
+    guard: &mut ExtractionGuard,
+    archive: &mut ZipArchive<R>,
+    index: usize,
+) -> Result<NextArchiveEntry, ProcessError>
     where R: Read + Seek
 {
     // Create an inner scope because `ZipFile` is not `Send` and must be dropped before `await`ing
@@ -91,26 +126,49 @@ async fn next_archive_entry<R>(archive: &mut ZipArchive<R>, index: usize) -> Res
         let name = zipfile.enclosed_name()
             .and_then(|name| name.file_name())
             .map(|name| name.to_string_lossy().to_string())
-            .ok_or(anyhow!("failed to get name for zip entry"))?;
+            .ok_or_else(|| ProcessError::new(ProcessErrorKind::Corrupt, anyhow!("failed to get name for zip entry")))?;
 
         if zipfile.is_dir() {
             return Ok(NextArchiveEntry::Dir(name));
         }
 
-        let emb_path = spool_read(&mut zipfile)?;
+        let compressed_size = match zipfile.compression() {
+            CompressionMethod::Stored => None,
+            _ => Some(zipfile.compressed_size()),
+        };
+        let size = zipfile.size();
+        if let Err(reason) = guard.admit(size, compressed_size) {
+            return Ok(NextArchiveEntry::Skipped(name, reason));
+        }
+
+        // The declared sizes may be false, so the limits are enforced on the bytes actually extracted as well
+        let max_extracted_size = guard.max_extracted_size(size, compressed_size);
+        let (emb_path, extracted_size) = spool_read(ctx, &mut zipfile, max_extracted_size.as_ref().map(|(max, _)| *max))?;
+        if let Some((max_size, reason)) = max_extracted_size.filter(|(max_size, _)| extracted_size > *max_size) {
+            warn!("Entry {} extracts to more than {} bytes", name, max_size);
+            guard.extracted(size, 0);
+            return Ok(NextArchiveEntry::Skipped(name, reason));
+        }
+        guard.extracted(size, extracted_size);
         (name, emb_path)
     };
 
-    let mimetype = identify_mimetype(&path).await?.unwrap_or("application/octet-stream".to_string());
+    let detected_mimetype = identify_mimetype_with_method(&path, ctx.config().tika()).await?
+        .map(|(mimetype, method)| DetectedMimetype { mimetype, method });
+    let mimetype = detected_mimetype.as_ref()
+        .map(|detected| detected.mimetype.clone())
+        .unwrap_or("application/octet-stream".to_string());
     let checksum = dedupe_checksum_from_path(&path, &mimetype).await?;
 
-    Ok(NextArchiveEntry::File(ArchiveEntry { name, path, checksum, mimetype }))
+    Ok(NextArchiveEntry::File(ArchiveEntry { name, path, checksum, mimetype, detected_mimetype }))
 }
 
-/// Write contents to a temporary file and return the temporary path.
+/// Write contents to a temporary file and return the temporary path with the number of bytes written.
+///
+/// If a maximum size is given, at most one byte more than it is written, so exceeding it can be detected.
 ///
-fn spool_read(mut reader: impl Read) -> io::Result<TempPath> {
-    let mut file = NamedTempFile::new()?;
-    std::io::copy(&mut reader, &mut file)?;
-    Ok(file.into_temp_path())
+fn spool_read(ctx: &ProcessContext, reader: impl Read, max_size: Option<u64>) -> io::Result<(TempPath, u64)> {
+    let mut file = ctx.temp_file()?;
+    let written = std::io::copy(&mut reader.take(max_size.map_or(u64::MAX, |max_size| max_size.saturating_add(1))), &mut file)?;
+    Ok((file.into_temp_path(), written))
 }
\ No newline at end of file
@@ -0,0 +1,301 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tempfile::TempPath;
+
+pub use rfc822::*;
+pub use rules::*;
+
+use crate::processing::{Process, ProcessContext, ProcessError, ProcessErrorKind, ProcessOutput};
+
+mod rfc822;
+mod rules;
+
+/// The source of entities found in the extracted text of a file.
+///
+const TEXT_SOURCE: &str = "text";
+
+/// The entities found in a file, written to `entities.json`.
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Entities {
+    /// The number of occurrences of entities of each type.
+    ///
+    pub counts: BTreeMap<String, usize>,
+
+    /// The distinct entities found, ordered by type and value.
+    ///
+    pub entities: Vec<Entity>,
+}
+
+/// A distinct entity found in a file, e.g. an email address.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Entity {
+    /// The type of the entity, as given by the [`EntityRule`] that found it.
+    ///
+    #[serde(rename = "type")]
+    pub entity_type: String,
+
+    /// The entity as it occurs in the file.
+    ///
+    pub value: String,
+
+    /// The number of times the entity occurs in the file.
+    ///
+    pub count: usize,
+
+    /// Where the entity occurs in the file.
+    ///
+    pub occurrences: Vec<Occurrence>,
+}
+
+/// An occurrence of an entity.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Occurrence {
+    /// Where the entity was found, i.e. `text` for the extracted text, or the name of a message header.
+    ///
+    pub source: String,
+
+    /// The offset in characters of the start of the entity in the source, or [`None`] if it was taken from a structured
+    /// source, such as the parsed addresses of a message header.
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<usize>,
+
+    /// The offset in characters of the end of the entity in the source, exclusive.
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<usize>,
+}
+
+/// An entity found in a text.
+///
+pub(crate) struct EntityMatch<'r> {
+    /// The type of the entity.
+    ///
+    pub(crate) entity_type: &'r str,
+
+    /// The byte range of the entity in the text.
+    ///
+    pub(crate) bytes: Range<usize>,
+
+    /// The character range of the entity in the text.
+    ///
+    pub(crate) chars: Range<usize>,
+}
+
+/// Finds the entities in the text with each rule, ordered by their position in the text.
+///
+/// Rules are applied in order, and matches overlapping an entity found by an earlier rule are discarded, e.g. a phone
+/// number found within a credit card number.
+///
+pub(crate) fn find_entities<'r>(rules: &'r [EntityRule], text: &str) -> Vec<EntityMatch<'r>> {
+    // The found entities never overlap, so only the entity starting closest before the end of a match can overlap it
+    let mut found: BTreeMap<usize, (usize, &str)> = BTreeMap::new();
+    for rule in rules {
+        for (start, end) in rule.find(text) {
+            let overlaps = found.range(..end).next_back().is_some_and(|(_, (e, _))| start < *e);
+            if !overlaps {
+                found.insert(start, (end, rule.entity_type()));
+            }
+        }
+    }
+
+    let (mut byte_offset, mut char_offset) = (0, 0);
+    found.into_iter()
+        .map(|(start, (end, entity_type))| {
+            char_offset += text[byte_offset..start].chars().count();
+            byte_offset = start;
+            EntityMatch {
+                entity_type,
+                bytes: start..end,
+                chars: char_offset..char_offset + text[start..end].chars().count(),
+            }
+        })
+        .collect()
+}
+
+/// Collects the entities found in the sources of a file.
+///
+pub(crate) struct EntityScanner<'a> {
+    rules: &'a [EntityRule],
+    entities: BTreeMap<(String, String), Vec<Occurrence>>,
+}
+
+impl<'a> EntityScanner<'a> {
+    pub(crate) fn new(rules: &'a [EntityRule]) -> Self {
+        Self {
+            rules,
+            entities: BTreeMap::new(),
+        }
+    }
+
+    /// Finds the entities in the text, see [`find_entities`].
+    ///
+    pub(crate) fn scan(&mut self, source: &str, text: &str) {
+        for found in find_entities(self.rules, text) {
+            let occurrence = Occurrence {
+                source: source.to_string(),
+                start: Some(found.chars.start),
+                end: Some(found.chars.end),
+            };
+            self.add(found.entity_type, &text[found.bytes], occurrence);
+        }
+    }
+
+    /// Adds an entity taken from a structured source.
+    ///
+    pub(crate) fn add_structured(&mut self, entity_type: &str, source: &str, value: &str) {
+        let occurrence = Occurrence {
+            source: source.to_string(),
+            start: None,
+            end: None,
+        };
+        self.add(entity_type, value, occurrence);
+    }
+
+    fn add(&mut self, entity_type: &str, value: &str, occurrence: Occurrence) {
+        self.entities
+            .entry((entity_type.to_string(), value.to_string()))
+            .or_default()
+            .push(occurrence);
+    }
+
+    pub(crate) fn finish(self) -> Entities {
+        let mut counts = BTreeMap::new();
+        let entities = self.entities.into_iter()
+            .map(|((entity_type, value), occurrences)| {
+                *counts.entry(entity_type.clone()).or_default() += occurrences.len();
+                Entity {
+                    entity_type,
+                    value,
+                    count: occurrences.len(),
+                    occurrences,
+                }
+            })
+            .collect();
+        Entities { counts, entities }
+    }
+}
+
+/// Writes the entities to `entities.json` and sends it.
+///
+pub(crate) async fn add_entities_output(
+    ctx: &ProcessContext,
+    entities: &Entities,
+    output_path: TempPath,
+    checksum: &str,
+) -> Result<(), ProcessError> {
+    let file = std::fs::File::create(&output_path)
+        .context("failed to create entities file")?;
+    serde_json::to_writer_pretty(file, entities)
+        .context("failed to write entities to file")?;
+
+    let output = ProcessOutput::processed(ctx, "entities.json", output_path, "application/json", checksum);
+    ctx.add_output(Ok(output)).await
+}
+
+/// Finds entities in the text extracted from a file by a processor of the [`crate::processing::ProcessType::Text`],
+/// using the [`crate::processing::ProcessorConfig::entity_rules`].
+///
+pub struct EntitiesProcessor {
+    text_processor: Arc<dyn Process>,
+}
+
+impl EntitiesProcessor {
+    /// Creates a processor finding entities in the text extracted by the given processor.
+    ///
+    pub fn scanning(text_processor: impl Process + 'static) -> Self {
+        Self {
+            text_processor: Arc::new(text_processor),
+        }
+    }
+}
+
+#[async_trait]
+impl Process for EntitiesProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        output_path: TempPath,
+        checksum: &str,
+    ) -> Result<(), ProcessError> {
+        let outputs = ctx.run_nested(self.text_processor.as_ref(), input_path, checksum).await?;
+        let text_path = outputs.iter()
+            .find_map(|output| match output {
+                ProcessOutput::Processed(_, data) if data.name == "extracted.txt" => Some(&data.path),
+                _ => None,
+            })
+            .ok_or_else(|| ProcessError::new(
+                ProcessErrorKind::Unsupported,
+                anyhow!("processor '{}' did not extract text", self.text_processor.name()),
+            ))?;
+        let text = std::fs::read(text_path)
+            .context("failed to read extracted text")?;
+
+        let mut scanner = EntityScanner::new(ctx.config().entity_rules());
+        scanner.scan(TEXT_SOURCE, &String::from_utf8_lossy(&text));
+        add_entities_output(&ctx, &scanner.finish(), output_path, checksum).await
+    }
+
+    fn name(&self) -> &'static str {
+        "Entities"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_entities_discards_overlaps() {
+        let rules = ["BC", "AB", "CD", "D", "A"].into_iter()
+            .map(|pattern| EntityRule::new(pattern, pattern).unwrap())
+            .collect::<Vec<_>>();
+
+        let found = find_entities(&rules, "ABCD")
+            .into_iter()
+            .map(|found| (found.entity_type, found.chars))
+            .collect::<Vec<_>>();
+
+        assert_eq!(found, vec![("A", 0..1), ("BC", 1..3), ("D", 3..4)]);
+    }
+
+    #[test]
+    fn test_scan() {
+        let rules = EntityRule::defaults();
+        let mut scanner = EntityScanner::new(&rules);
+
+        scanner.scan("text", "Café: mail bob@example.com, card 4111 1111 1111 1111. Again: bob@example.com");
+        scanner.add_structured("email", "From", "bob@example.com");
+        let entities = scanner.finish();
+
+        assert_eq!(entities.counts, BTreeMap::from([("credit_card".to_string(), 1), ("email".to_string(), 3)]));
+        assert_eq!(entities.entities, vec![
+            Entity {
+                entity_type: "credit_card".to_string(),
+                value: "4111 1111 1111 1111".to_string(),
+                count: 1,
+                occurrences: vec![Occurrence { source: "text".to_string(), start: Some(33), end: Some(52) }],
+            },
+            Entity {
+                entity_type: "email".to_string(),
+                value: "bob@example.com".to_string(),
+                count: 3,
+                occurrences: vec![
+                    Occurrence { source: "text".to_string(), start: Some(11), end: Some(26) },
+                    Occurrence { source: "text".to_string(), start: Some(61), end: Some(76) },
+                    Occurrence { source: "From".to_string(), start: None, end: None },
+                ],
+            },
+        ]);
+    }
+}
@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use mail_parser::{Address, Message, MessageParser};
+use tempfile::TempPath;
+
+use crate::entities::{add_entities_output, EntityScanner, TEXT_SOURCE};
+use crate::processing::{Process, ProcessContext, ProcessError, ProcessErrorKind};
+
+/// Finds entities in a message, taking the addresses of its address headers as parsed, and scanning its subject and
+/// text bodies with the [`crate::processing::ProcessorConfig::entity_rules`].
+///
+#[derive(Debug, Default)]
+pub struct Rfc822EntitiesProcessor {
+    message_parser: MessageParser,
+}
+
+#[async_trait]
+impl Process for Rfc822EntitiesProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        output_path: TempPath,
+        checksum: &str,
+    ) -> Result<(), ProcessError> {
+        let content = std::fs::read(input_path)
+            .context("failed to read input file")?;
+        let message = self.message_parser.parse(&content)
+            .ok_or_else(|| ProcessError::new(ProcessErrorKind::Corrupt, anyhow!("failed to parse message")))?;
+
+        let mut scanner = EntityScanner::new(ctx.config().entity_rules());
+        scan_message(&mut scanner, &message);
+        add_entities_output(&ctx, &scanner.finish(), output_path, checksum).await
+    }
+
+    fn name(&self) -> &'static str {
+        "RFC 822 Entities"
+    }
+}
+
+/// Adds the entities of the message to the scanner.
+///
+/// The text bodies are joined by blank lines, so offsets in the [`TEXT_SOURCE`] are offsets in the joined text.
+///
+fn scan_message(scanner: &mut EntityScanner, message: &Message) {
+    let address_headers = [
+        ("From", message.from()),
+        ("Sender", message.sender()),
+        ("Reply-To", message.reply_to()),
+        ("To", message.to()),
+        ("Cc", message.cc()),
+        ("Bcc", message.bcc()),
+    ];
+    for (header, address) in address_headers {
+        for email in address.into_iter().flat_map(Address::iter).filter_map(|addr| addr.address()) {
+            scanner.add_structured("email", header, email);
+        }
+    }
+
+    if let Some(subject) = message.subject() {
+        scanner.scan("Subject", subject);
+    }
+
+    let text = (0..message.text_body_count())
+        .filter_map(|pos| message.body_text(pos))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    scanner.scan(TEXT_SOURCE, &text);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::entities::EntityRule;
+
+    use super::*;
+
+    #[test]
+    fn test_scan_message() {
+        let content = b"\
+Message-ID: <1449186.1075855697095.JavaMail.evans@thyme>
+From: Phillip Allen <phillip.allen@enron.com>
+To: cbpres@austin.rr.com, Jeff <jeff@enron.com>
+Subject: Call (713) 853-6197
+Content-Type: text/plain; charset=us-ascii
+
+Send it to jeff@enron.com.";
+        let message = MessageParser::default().parse(content).unwrap();
+        let rules = EntityRule::defaults();
+        let mut scanner = EntityScanner::new(&rules);
+
+        scan_message(&mut scanner, &message);
+        let entities = scanner.finish();
+
+        let found = entities.entities.iter()
+            .map(|entity| {
+                let sources = entity.occurrences.iter().map(|occurrence| occurrence.source.as_str()).collect::<Vec<_>>();
+                (entity.entity_type.as_str(), entity.value.as_str(), sources)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![
+            ("email", "cbpres@austin.rr.com", vec!["To"]),
+            ("email", "jeff@enron.com", vec!["To", "text"]),
+            ("email", "phillip.allen@enron.com", vec!["From"]),
+            ("phone", "(713) 853-6197", vec!["Subject"]),
+        ]);
+    }
+}
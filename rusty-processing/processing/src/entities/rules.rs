@@ -0,0 +1,197 @@
+use std::net::IpAddr;
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+lazy_static! {
+    static ref DEFAULT_RULES: Vec<EntityRule> = vec![
+        EntityRule::new("email", r"(?i)\b[a-z0-9._%+-]+@[a-z0-9.-]+\.[a-z]{2,}\b").unwrap(),
+        EntityRule::new("credit_card", r"\b(?:\d[ -]?){12,18}\d\b").unwrap()
+            .with_validator(is_valid_credit_card),
+        EntityRule::new("iban", r"\b[A-Z]{2}\d{2}(?: ?[A-Z0-9]){11,30}\b").unwrap()
+            .with_validator(is_valid_iban),
+        EntityRule::new("ssn", r"\b\d{3}-\d{2}-\d{4}\b").unwrap()
+            .with_validator(is_valid_ssn),
+        EntityRule::new("ip_address", r"(?i)\b(?:\d{1,3}\.){3}\d{1,3}\b|(?:[0-9a-f]{0,4}:){2,7}[0-9a-f]{0,4}").unwrap()
+            .with_validator(is_valid_ip_address),
+        EntityRule::new("phone", r"(?:\+\d{1,3}[ .-]?)?(?:\(\d{3}\)|\b\d{3})[ .-]?\d{3}[ .-]\d{4}\b").unwrap(),
+    ];
+}
+
+/// A rule finding entities of a type in text, e.g. email addresses.
+///
+/// Entities are found with a regular expression, and optionally validated, e.g. credit card numbers with the Luhn
+/// algorithm.
+///
+#[derive(Debug, Clone)]
+pub struct EntityRule {
+    entity_type: String,
+    pattern: Regex,
+    validator: Option<fn(&str) -> bool>,
+}
+
+impl EntityRule {
+    /// Creates a rule finding entities of the type with the regular expression.
+    ///
+    /// Fails if the regular expression is invalid.
+    ///
+    pub fn new(entity_type: impl Into<String>, pattern: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            entity_type: entity_type.into(),
+            pattern: Regex::new(pattern)?,
+            validator: None,
+        })
+    }
+
+    /// Sets a function validating the matches of the regular expression, discarding matches it returns `false` for.
+    ///
+    pub fn with_validator(mut self, validator: fn(&str) -> bool) -> Self {
+        self.validator = Some(validator);
+        self
+    }
+
+    /// Returns the built-in rules finding email addresses, credit card numbers, IBANs, US social security numbers, IP
+    /// addresses and phone numbers.
+    ///
+    pub fn defaults() -> Vec<EntityRule> {
+        DEFAULT_RULES.clone()
+    }
+
+    /// The type of the entities found by this rule.
+    ///
+    pub fn entity_type(&self) -> &str {
+        &self.entity_type
+    }
+
+    /// Finds the valid matches in the text, returning their byte ranges.
+    ///
+    /// A pattern may take in words following an entity, e.g. `EUR` after an IBAN, so if a match is not valid, the
+    /// longest prefix of it ending before a space that matches the pattern and is valid is found instead.
+    ///
+    pub(crate) fn find<'a>(&'a self, text: &'a str) -> impl Iterator<Item = (usize, usize)> + 'a {
+        self.pattern.find_iter(text)
+            .filter_map(|found| {
+                let candidate = found.as_str();
+                if self.is_valid(candidate) {
+                    return Some((found.start(), found.end()));
+                }
+                candidate.match_indices(' ')
+                    .map(|(end, _)| &candidate[..end])
+                    .rev()
+                    .find(|prefix| self.matches_whole(prefix) && self.is_valid(prefix))
+                    .map(|prefix| (found.start(), found.start() + prefix.len()))
+            })
+    }
+
+    /// Returns whether the pattern matches the whole text.
+    ///
+    fn matches_whole(&self, text: &str) -> bool {
+        self.pattern.find(text).is_some_and(|found| found.start() == 0 && found.end() == text.len())
+    }
+
+    /// Returns whether the validator of the rule, if any, accepts the text.
+    ///
+    fn is_valid(&self, text: &str) -> bool {
+        self.validator.is_none_or(|validator| validator(text))
+    }
+}
+
+/// Returns whether the digits of the credit card number pass the Luhn check.
+///
+fn is_valid_credit_card(number: &str) -> bool {
+    let digits = number.chars()
+        .filter(char::is_ascii_digit)
+        .filter_map(|digit| digit.to_digit(10))
+        .collect::<Vec<_>>();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+
+    let sum = digits.iter().rev().enumerate()
+        .map(|(i, digit)| match (i % 2, digit * 2) {
+            (0, _) => *digit,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum::<u32>();
+    sum % 10 == 0
+}
+
+/// Returns whether the IBAN passes the ISO 7064 MOD 97-10 check.
+///
+fn is_valid_iban(iban: &str) -> bool {
+    let iban = iban.replace(' ', "");
+    if !(15..=34).contains(&iban.len()) {
+        return false;
+    }
+
+    let (country, account) = iban.split_at(4);
+    let mut remainder = 0;
+    for c in account.chars().chain(country.chars()) {
+        let value = match c.to_digit(36) {
+            Some(value) => value,
+            None => return false,
+        };
+        remainder = match value {
+            0..=9 => (remainder * 10 + value) % 97,
+            _ => (remainder * 100 + value) % 97,
+        };
+    }
+    remainder == 1
+}
+
+/// Returns whether the US social security number has valid area, group and serial numbers.
+///
+fn is_valid_ssn(ssn: &str) -> bool {
+    let mut parts = ssn.split('-');
+    let (Some(area), Some(group), Some(serial)) = (parts.next(), parts.next(), parts.next()) else {
+        return false;
+    };
+    area != "000" && area != "666" && !area.starts_with('9') && group != "00" && serial != "0000"
+}
+
+/// Returns whether the text is an IPv4 or IPv6 address.
+///
+fn is_valid_ip_address(address: &str) -> bool {
+    address.parse::<IpAddr>().is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find_all(rule: &EntityRule, text: &str) -> Vec<String> {
+        rule.find(text).map(|(start, end)| text[start..end].to_string()).collect()
+    }
+
+    fn default_rule(entity_type: &str) -> EntityRule {
+        EntityRule::defaults().into_iter().find(|rule| rule.entity_type() == entity_type).unwrap()
+    }
+
+    #[test]
+    fn test_default_rules() {
+        let cases = vec![
+            ("email", "Mail Phillip.Allen@enron.com or x@y", vec!["Phillip.Allen@enron.com"]),
+            ("credit_card", "Card 4111 1111 1111 1111, not 4111 1111 1111 1112", vec!["4111 1111 1111 1111"]),
+            ("iban", "Pay GB82 WEST 1234 5698 7654 32, not GB82 WEST 1234 5698 7654 33", vec!["GB82 WEST 1234 5698 7654 32"]),
+            ("iban", "Pay GB82 WEST 1234 5698 7654 32 EUR", vec!["GB82 WEST 1234 5698 7654 32"]),
+            ("ssn", "SSN 078-05-1120, not 666-05-1120", vec!["078-05-1120"]),
+            ("ip_address", "Hosts 192.168.0.1, 2001:db8::1 and 999.1.1.1 at 12:30:45", vec!["192.168.0.1", "2001:db8::1"]),
+            ("phone", "Call (713) 853-6197 or +1 713.853.6197", vec!["(713) 853-6197", "+1 713.853.6197"]),
+        ];
+
+        for (entity_type, text, expected) in cases {
+            assert_eq!(find_all(&default_rule(entity_type), text), expected, "{}", entity_type);
+        }
+    }
+
+    #[test]
+    fn test_custom_rule() {
+        let rule = EntityRule::new("case_number", r"\bCASE-\d+\b").unwrap()
+            .with_validator(|case| case != "CASE-0");
+
+        assert_eq!(rule.entity_type(), "case_number");
+        assert_eq!(find_all(&rule, "CASE-12 and CASE-0"), vec!["CASE-12"]);
+        assert!(EntityRule::new("invalid", "(").is_err());
+    }
+}
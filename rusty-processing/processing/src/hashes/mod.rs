@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use tempfile::TempPath;
+use tokio::io::AsyncRead;
+
+use identify::hashing::hash_all;
+
+use crate::processing::{InputStream, Process, ProcessContext, ProcessError, ProcessOutput, StreamedOutput};
+
+/// Hashes a file with each of the [`crate::processing::ProcessorConfig::hash_algorithms`] in a single pass, writing
+/// them to `hashes.json` keyed by the name of the algorithm, e.g. `{"md5": "…", "sha256": "…"}`.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HashesProcessor;
+
+#[async_trait]
+impl Process for HashesProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        output_path: TempPath,
+        checksum: &str,
+    ) -> Result<(), ProcessError> {
+        let mut input = tokio::fs::File::open(input_path).await
+            .context("failed to open input file")?;
+        write_hashes(&ctx, &mut input, &output_path).await?;
+
+        let output = ProcessOutput::processed(&ctx, "hashes.json", output_path, "application/json", checksum);
+        ctx.add_output(Ok(output)).await
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    async fn process_stream(
+        &self,
+        ctx: ProcessContext,
+        mut input: InputStream,
+        output_path: TempPath,
+    ) -> Result<Vec<StreamedOutput>, ProcessError> {
+        write_hashes(&ctx, &mut input, &output_path).await?;
+
+        Ok(vec![StreamedOutput {
+            name: "hashes.json".to_string(),
+            path: output_path,
+            mimetype: "application/json".to_string(),
+            kind: None,
+        }])
+    }
+
+    fn name(&self) -> &'static str {
+        "Hashes"
+    }
+}
+
+/// Hashes the input and writes the hashes to the output file as JSON.
+///
+async fn write_hashes(
+    ctx: &ProcessContext,
+    input: &mut (impl AsyncRead + Unpin),
+    output_path: &Path,
+) -> Result<(), ProcessError> {
+    let hashes = hash_all(input, ctx.config().hash_algorithms()).await
+        .context("failed to hash input")?;
+    let hashes = hashes.into_iter()
+        .map(|(algorithm, hash)| (algorithm.name(), hash))
+        .collect::<BTreeMap<_, _>>();
+
+    let file = std::fs::File::create(output_path)
+        .context("failed to create hashes file")?;
+    serde_json::to_writer_pretty(file, &hashes)
+        .context("failed to write hashes to file")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crate::processing::{ProcessContextBuilder, ProcessType};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_process() -> anyhow::Result<()> {
+        let (output_sink, mut outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("application/octet-stream", vec![ProcessType::Hashes], output_sink).build();
+
+        let mut input = tempfile::NamedTempFile::new()?;
+        input.write_all(b"Hello, world!")?;
+        let output_path = tempfile::NamedTempFile::new()?.into_temp_path();
+        HashesProcessor.process(ctx, input.path(), output_path, "checksum").await?;
+
+        let Some(Ok(ProcessOutput::Processed(_, data))) = outputs.recv().await else {
+            panic!("Expected a processed output");
+        };
+        let hashes: BTreeMap<String, String> = serde_json::from_reader(std::fs::File::open(&data.path)?)?;
+        assert_eq!(data.name, "hashes.json");
+        assert_eq!(hashes, BTreeMap::from([
+            ("md5".to_string(), "6cd3556deb0da54bca060b4c39479839".to_string()),
+            ("sha1".to_string(), "943a702d06f34599aee1f8da8ef9f7296031d699".to_string()),
+            ("sha256".to_string(), "315f5bdb76d078c43b8ac0064e4a0164612b1fce77c869345bfc94c75894edd3".to_string()),
+        ]));
+        Ok(())
+    }
+}
@@ -0,0 +1,142 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use log::info;
+use tempfile::TempPath;
+
+use services::pdf_to_image;
+
+use crate::processing::{Process, ProcessContext, ProcessError, ProcessErrorKind, ProcessOutput, Progress};
+
+/// Renders an image of each page of a PDF, named `page-<number>.<extension>`.
+///
+/// The format and resolution of the images are taken from the [`crate::processing::ProcessorConfig`]. Files other than
+/// PDFs are rendered to a PDF by another processor first, such as [`crate::pdf::Rfc822PdfProcessor`] for messages, unless
+/// the PDF was rendered for the [`crate::processing::ProcessType::Pdf`] of the file already.
+///
+#[derive(Default)]
+pub struct PageImageProcessor {
+    pdf_processor: Option<Arc<dyn Process>>,
+}
+
+impl PageImageProcessor {
+    /// Creates a processor rendering the pages of the PDF created by the given processor of the [`ProcessType::Pdf`].
+    ///
+    /// [`ProcessType::Pdf`]: crate::processing::ProcessType::Pdf
+    ///
+    pub fn rendering(pdf_processor: impl Process + 'static) -> Self {
+        Self {
+            pdf_processor: Some(Arc::new(pdf_processor)),
+        }
+    }
+}
+
+#[async_trait]
+impl Process for PageImageProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        _: TempPath,
+        checksum: &str,
+    ) -> Result<(), ProcessError> {
+        // The PDF rendered for the PDF type is reused if it was requested as well
+        let rendered_pdf = match (&self.pdf_processor, ctx.rendered_pdf().await) {
+            (Some(_), Some(rendered_pdf)) => Some(rendered_pdf),
+            (Some(pdf_processor), None) => Some(Arc::new(render_pdf(pdf_processor.as_ref(), &ctx, input_path, checksum).await?)),
+            (None, _) => None,
+        };
+        let pdf_path = rendered_pdf.as_deref().map_or(input_path, |pdf| pdf.as_ref());
+
+        let output_dir = ctx.temp_subdir()
+            .context("failed to create temporary directory")?;
+
+        let format = ctx.config().image_format();
+        let pages = pdf_to_image()
+            .render_pages(pdf_path, output_dir.path(), format, ctx.config().image_resolution()).await
+            .context("failed to render pages")?;
+        info!("Rendered {} pages", pages.len());
+
+        for (index, page) in pages.iter().enumerate() {
+            let path = ctx.temp_file()
+                .context("failed to create temporary file")?
+                .into_temp_path();
+            std::fs::rename(page, &path)
+                .context("failed to move page image")?;
+
+            let name = format!("page-{}.{}", index + 1, format.extension());
+            ctx.add_output(Ok(ProcessOutput::processed(&ctx, name, path, format.mimetype(), checksum))).await?;
+            ctx.progress(Progress::entries(index as u64 + 1, Some(pages.len() as u64))).await?;
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "Page Images"
+    }
+}
+
+/// Runs the processor rendering the file to a PDF, returning the path to the PDF.
+///
+/// The outputs of the processor are not sent, and the first error it sent is returned instead.
+///
+pub(crate) async fn render_pdf(
+    pdf_processor: &dyn Process,
+    ctx: &ProcessContext,
+    input_path: &Path,
+    checksum: &str,
+) -> Result<TempPath, ProcessError> {
+    let outputs = ctx.run_nested(pdf_processor, input_path, checksum).await?;
+
+    let rendered_pdf = outputs.into_iter().find_map(|output| match output {
+        ProcessOutput::Processed(_, data) if data.mimetype == "application/pdf" => Some(data.path),
+        _ => None,
+    });
+    rendered_pdf.ok_or_else(|| ProcessError::new(
+        ProcessErrorKind::Unsupported,
+        anyhow!("processor '{}' did not render a PDF", pdf_processor.name()),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use async_trait::async_trait;
+
+    use crate::processing::{ProcessContextBuilder, ProcessType};
+
+    use super::*;
+
+    /// Renders a copy of the input as the PDF, as if it was rendered from another type of file.
+    ///
+    struct CopyingPdfProcessor;
+
+    #[async_trait]
+    impl Process for CopyingPdfProcessor {
+        async fn process(&self, ctx: ProcessContext, input_path: &Path, output_path: TempPath, checksum: &str) -> Result<(), ProcessError> {
+            std::fs::copy(input_path, &output_path)?;
+            ctx.add_output(Ok(ProcessOutput::processed(&ctx, "rendered.pdf", output_path, "application/pdf", checksum))).await
+        }
+
+        fn name(&self) -> &'static str {
+            "copying"
+        }
+    }
+
+    #[tokio::test]
+    async fn test_render_pdf() -> anyhow::Result<()> {
+        let (output_sink, mut outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("application/pdf", vec![ProcessType::Image], output_sink).build();
+        let input_path = PathBuf::from("../resources/pdf/Espresso Machine Cleaning Guide.pdf");
+
+        let rendered_pdf = render_pdf(&CopyingPdfProcessor, &ctx, &input_path, "checksum").await?;
+        drop(ctx);
+
+        assert_eq!(std::fs::read(&rendered_pdf)?, std::fs::read(&input_path)?);
+        assert!(outputs.recv().await.is_none(), "Expected outputs of the PDF processor not to be sent");
+        Ok(())
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use whatlang::Lang;
+
+use crate::processing::{OutputKind, ProcessContext, ProcessError, StreamedOutput};
+
+/// The maximum number of bytes of text read to detect its languages.
+///
+const MAX_TEXT_SIZE: u64 = 1 << 20;
+
+/// The minimum number of characters of a segment of text to detect the language of.
+///
+/// Shorter lines are joined with the following ones, as detection is unreliable on just a few words.
+///
+const MIN_SEGMENT_CHARS: usize = 200;
+
+/// The minimum share of the text in a language for it to be reported as a secondary language.
+///
+const MIN_SECONDARY_SHARE: f64 = 0.1;
+
+/// The languages detected in a text, written to `language.json`.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DetectedLanguages {
+    /// The language most of the text is in, or [`None`] if no language could be detected.
+    ///
+    pub primary: Option<LanguageShare>,
+
+    /// Other languages a notable share of the text is in, in descending order of share.
+    ///
+    pub secondary: Vec<LanguageShare>,
+}
+
+/// A language detected in part of a text.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LanguageShare {
+    /// The ISO 639-3 code of the language, e.g. `eng`.
+    ///
+    pub code: String,
+
+    /// The English name of the language.
+    ///
+    pub name: String,
+
+    /// The fraction of the text in the language, between 0 and 1.
+    ///
+    pub share: f64,
+
+    /// The confidence in the detection of the language, between 0 and 1.
+    ///
+    pub confidence: f64,
+}
+
+/// Detects the languages of the text extracted into the file, if enabled by
+/// [`crate::processing::ProcessorConfig::detect_language`].
+///
+/// Returns `language.json` as an output of the [`OutputKind::Language`], for text processors to send along with the
+/// extracted text.
+///
+pub(crate) fn language_output(
+    ctx: &ProcessContext,
+    text_path: &Path,
+) -> Result<Option<StreamedOutput>, ProcessError> {
+    if !ctx.config().detect_language() {
+        return Ok(None);
+    }
+
+    let mut text = vec![];
+    std::fs::File::open(text_path)
+        .context("failed to open text file")?
+        .take(MAX_TEXT_SIZE)
+        .read_to_end(&mut text)
+        .context("failed to read text file")?;
+    let languages = detect_languages(&String::from_utf8_lossy(&text));
+
+    let path = ctx.temp_file()
+        .context("failed to create temporary file")?
+        .into_temp_path();
+    let file = std::fs::File::create(&path)
+        .context("failed to create language file")?;
+    serde_json::to_writer_pretty(file, &languages)
+        .context("failed to write languages to file")?;
+
+    Ok(Some(StreamedOutput {
+        name: "language.json".to_string(),
+        path,
+        mimetype: "application/json".to_string(),
+        kind: Some(OutputKind::Language),
+    }))
+}
+
+/// Detects the languages of a text by detecting the language of each segment of it.
+///
+/// The share of a language is the fraction of characters in segments detected as it, and its confidence the average
+/// confidence of those segments weighted by their length.
+///
+pub(crate) fn detect_languages(text: &str) -> DetectedLanguages {
+    let mut totals: HashMap<Lang, (usize, f64)> = HashMap::new();
+    for segment in segments(text) {
+        if let Some(info) = whatlang::detect(&segment) {
+            let chars = segment.chars().count();
+            let (total_chars, total_confidence) = totals.entry(info.lang()).or_default();
+            *total_chars += chars;
+            *total_confidence += info.confidence() * chars as f64;
+        }
+    }
+
+    let detected_chars = totals.values().map(|(chars, _)| chars).sum::<usize>();
+    let mut languages = totals.into_iter()
+        .map(|(lang, (chars, confidence))| LanguageShare {
+            code: lang.code().to_string(),
+            name: lang.eng_name().to_string(),
+            share: chars as f64 / detected_chars as f64,
+            confidence: confidence / chars as f64,
+        })
+        .collect::<Vec<_>>();
+    languages.sort_by(|l0, l1| l1.share.total_cmp(&l0.share).then_with(|| l0.code.cmp(&l1.code)));
+
+    let mut languages = languages.into_iter();
+    DetectedLanguages {
+        primary: languages.next(),
+        secondary: languages.filter(|language| language.share >= MIN_SECONDARY_SHARE).collect(),
+    }
+}
+
+/// Splits the text into segments of whole lines of at least [`MIN_SEGMENT_CHARS`] characters, except the last one.
+///
+fn segments(text: &str) -> Vec<String> {
+    let mut segments = vec![];
+    let mut segment = String::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if !segment.is_empty() {
+            segment.push(' ');
+        }
+        segment.push_str(line);
+        if segment.chars().count() >= MIN_SEGMENT_CHARS {
+            segments.push(std::mem::take(&mut segment));
+        }
+    }
+    if !segment.is_empty() {
+        segments.push(segment);
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::processing::{ProcessContextBuilder, Processor, ProcessType};
+
+    use super::*;
+
+    const ENGLISH: &str = "\
+The quick brown fox jumps over the lazy dog while the farmer watches from the porch of his old house. \
+He has seen this happen every morning for many years, and he still does not understand why the dog never moves.";
+
+    const FRENCH: &str = "\
+Le renard brun rapide saute par-dessus le chien paresseux pendant que le fermier regarde depuis le porche de sa \
+vieille maison. Il voit cela se produire chaque matin depuis de nombreuses années.";
+
+    #[test]
+    fn test_detect_languages() {
+        let text = format!("{}\n{}\n{}\n{}", ENGLISH, ENGLISH, ENGLISH, FRENCH);
+
+        let languages = detect_languages(&text);
+
+        let primary = languages.primary.unwrap();
+        assert_eq!(primary.code, "eng");
+        assert_eq!(primary.name, "English");
+        assert!(primary.share > 0.5);
+        assert!(primary.confidence > 0.5);
+        assert_eq!(languages.secondary.iter().map(|language| language.code.as_str()).collect::<Vec<_>>(), vec!["fra"]);
+    }
+
+    #[test]
+    fn test_detect_languages_no_text() {
+        let languages = detect_languages(" \n\n");
+
+        assert_eq!(languages, DetectedLanguages { primary: None, secondary: vec![] });
+    }
+
+    #[test]
+    fn test_language_output() -> anyhow::Result<()> {
+        let (output_sink, _outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("text/plain", vec![ProcessType::Text], output_sink).build();
+        let text_path = tempfile::NamedTempFile::new()?.into_temp_path();
+        std::fs::write(&text_path, ENGLISH)?;
+
+        assert!(language_output(&ctx, &text_path)?.is_none());
+
+        let config = Processor::builder().detect_language(true).build().config().clone();
+        let output = language_output(&ctx.with_config(Arc::new(config)), &text_path)?.unwrap();
+        let languages: DetectedLanguages = serde_json::from_reader(std::fs::File::open(&output.path)?)?;
+
+        assert_eq!(output.name, "language.json");
+        assert_eq!(output.kind, Some(OutputKind::Language));
+        assert_eq!(languages.primary.unwrap().code, "eng");
+        Ok(())
+    }
+
+    #[test]
+    fn test_segments() {
+        let long_line = "a".repeat(MIN_SEGMENT_CHARS);
+
+        assert_eq!(segments(&format!("one\n\n two \n{}\nthree", long_line)), vec![
+            format!("one two {}", long_line),
+            "three".to_string(),
+        ]);
+    }
+}
@@ -18,6 +18,13 @@ pub mod processing;
 pub(crate) mod text;
 pub(crate) mod metadata;
 pub(crate) mod pdf;
+pub(crate) mod image;
+pub(crate) mod ocr;
+pub(crate) mod hashes;
+pub(crate) mod language;
+pub(crate) mod entities;
+pub(crate) mod redaction;
+pub(crate) mod threading;
 pub(crate) mod embedded;
 
 /// Get the MIME type from a `mail_parser::ContentType`.
@@ -37,6 +44,18 @@ pub fn mimetype(content_type: &ContentType) -> String {
     }
 }
 
+/// Returns whether a MIME type says nothing about the content of a file, so it should be detected instead.
+///
+/// The MIME type is compared case-insensitively and without its parameters, e.g. `Application/Octet-Stream; name=x`.
+///
+pub(crate) fn is_generic_mimetype(mimetype: &str) -> bool {
+    let essence = mimetype.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+    matches!(
+        essence.as_str(),
+        "" | "application/octet-stream" | "binary/octet-stream" | "application/unknown" | "application/x-download"
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use std::borrow::Cow;
@@ -66,4 +85,15 @@ mod tests {
 
         assert_eq!(mimetype(&content_type), "text");
     }
+
+    #[test]
+    fn test_is_generic_mimetype() {
+        assert!(is_generic_mimetype(""));
+        assert!(is_generic_mimetype("application/octet-stream"));
+        assert!(is_generic_mimetype("Application/Octet-Stream"));
+        assert!(is_generic_mimetype("application/octet-stream; name=x"));
+        assert!(is_generic_mimetype(" APPLICATION/UNKNOWN ;charset=binary"));
+        assert!(!is_generic_mimetype("application/pdf"));
+        assert!(!is_generic_mimetype("application/pdf; name=octet-stream"));
+    }
 }
@@ -0,0 +1,199 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use lazy_static::lazy_static;
+use scraper::{Html, Selector};
+use serde::{Deserialize, Serialize};
+use tempfile::TempPath;
+
+use crate::processing::{Process, ProcessContext, ProcessError, ProcessOutput};
+use crate::text::charset::decode_html;
+
+lazy_static! {
+    static ref TITLE: Selector = Selector::parse("title").unwrap();
+    static ref META: Selector = Selector::parse("meta").unwrap();
+    static ref LINKS: Selector = Selector::parse("a[href], area[href]").unwrap();
+}
+
+/// The metadata of an HTML document, written to `metadata.json`.
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HtmlMetadata {
+    /// The title of the document, if it has one.
+    ///
+    pub title: Option<String>,
+
+    /// The charset the document was decoded from, declared by a `<meta>` tag or detected from its content.
+    ///
+    pub charset: String,
+
+    /// The content of the `<meta>` tags, keyed by their `name`, `property` or `http-equiv`.
+    ///
+    pub meta: BTreeMap<String, Vec<String>>,
+
+    /// The number of links in the document.
+    ///
+    pub links: LinkCounts,
+}
+
+/// The number of links of each kind in an HTML document.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinkCounts {
+    /// The number of all links.
+    ///
+    pub total: usize,
+
+    /// The number of links to other sites, i.e. with a scheme such as `https:`, or protocol-relative.
+    ///
+    pub external: usize,
+
+    /// The number of links relative to the document.
+    ///
+    pub internal: usize,
+
+    /// The number of links to fragments of the document itself, e.g. `#top`.
+    ///
+    pub fragment: usize,
+
+    /// The number of `mailto:` links.
+    ///
+    pub mailto: usize,
+}
+
+/// Extracts the [`HtmlMetadata`] of HTML documents natively.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HtmlMetadataProcessor;
+
+#[async_trait]
+impl Process for HtmlMetadataProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        output_path: TempPath,
+        checksum: &str,
+    ) -> Result<(), ProcessError> {
+        let content = std::fs::read(input_path)
+            .context("failed to read input file")?;
+        let metadata = html_metadata(&content);
+
+        let file = std::fs::File::create(&output_path)
+            .context("failed to create metadata file")?;
+        serde_json::to_writer_pretty(file, &metadata)
+            .context("failed to write metadata to file")?;
+
+        let output = ProcessOutput::processed(&ctx, "metadata.json", output_path, "application/json", checksum);
+        ctx.add_output(Ok(output)).await
+    }
+
+    fn name(&self) -> &'static str {
+        "HTML Metadata"
+    }
+}
+
+/// Extracts the metadata of the HTML document, decoding it from its declared or detected charset first.
+///
+fn html_metadata(content: &[u8]) -> HtmlMetadata {
+    let decoded = decode_html(content);
+    let document = Html::parse_document(&decoded.text);
+
+    let title = document.select(&TITLE)
+        .next()
+        .map(|title| title.text().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|title| !title.is_empty());
+
+    let mut meta: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for element in document.select(&META) {
+        let element = element.value();
+        let key = element.attr("name")
+            .or_else(|| element.attr("property"))
+            .or_else(|| element.attr("http-equiv"));
+        if let (Some(key), Some(content)) = (key, element.attr("content")) {
+            meta.entry(key.to_lowercase()).or_default().push(content.trim().to_string());
+        }
+    }
+
+    let mut links = LinkCounts::default();
+    for href in document.select(&LINKS).filter_map(|link| link.value().attr("href")) {
+        let href = href.trim();
+        links.total += 1;
+        if href.starts_with('#') {
+            links.fragment += 1;
+        } else if href.get(..7).is_some_and(|scheme| scheme.eq_ignore_ascii_case("mailto:")) {
+            links.mailto += 1;
+        } else if href.starts_with("//") || has_scheme(href) {
+            links.external += 1;
+        } else {
+            links.internal += 1;
+        }
+    }
+
+    HtmlMetadata {
+        title,
+        charset: decoded.encoding.name().to_string(),
+        meta,
+        links,
+    }
+}
+
+/// Returns whether the URL starts with a scheme, e.g. `https:`.
+///
+fn has_scheme(url: &str) -> bool {
+    url.split_once(':').is_some_and(|(scheme, _)| {
+        scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+            && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_metadata() {
+        let html = "\
+<html><head>
+<meta charset=\"utf-8\">
+<title>
+  Quarterly   report
+</title>
+<meta name=\"Author\" content=\"Phillip Allen\">
+<meta name=\"keywords\" content=\"gas\">
+<meta name=\"keywords\" content=\"power\">
+<meta property=\"og:type\" content=\"article\">
+</head><body>
+<a href=\"https://example.com\">site</a>
+<a href=\"//cdn.example.com/file\">cdn</a>
+<a href=\"reports/q3.html\">q3</a>
+<a href=\"#top\">top</a>
+<a href=\"MAILTO:phillip.allen@enron.com\">mail</a>
+<a>no link</a>
+</body></html>";
+
+        let metadata = html_metadata(html.as_bytes());
+
+        assert_eq!(metadata, HtmlMetadata {
+            title: Some("Quarterly report".to_string()),
+            charset: "UTF-8".to_string(),
+            meta: BTreeMap::from([
+                ("author".to_string(), vec!["Phillip Allen".to_string()]),
+                ("keywords".to_string(), vec!["gas".to_string(), "power".to_string()]),
+                ("og:type".to_string(), vec!["article".to_string()]),
+            ]),
+            links: LinkCounts { total: 5, external: 2, internal: 1, fragment: 1, mailto: 1 },
+        });
+    }
+
+    #[test]
+    fn test_html_metadata_empty() {
+        let metadata = html_metadata(b"");
+
+        assert_eq!(metadata.title, None);
+        assert!(metadata.meta.is_empty());
+        assert_eq!(metadata.links, LinkCounts::default());
+    }
+}
@@ -0,0 +1,162 @@
+use std::path::Path;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use mail_parser::{DateTime, MessageParser};
+use serde::{Deserialize, Serialize};
+use tempfile::TempPath;
+
+use crate::embedded::{MboxMessage, MboxReader};
+use crate::processing::{Process, ProcessContext, ProcessError, ProcessOutput};
+
+/// The metadata of an mbox file, written to `metadata.json`.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MboxMetadata {
+    /// The number of messages in the mbox file, including the ones that failed to parse.
+    ///
+    pub message_count: usize,
+
+    /// The date of the earliest message in UTC in RFC 3339 format, if any message has a date.
+    ///
+    /// The date of a message is taken from its `Date` header, or from its envelope if the header is missing or invalid.
+    ///
+    pub earliest_date: Option<String>,
+
+    /// The date of the latest message in UTC in RFC 3339 format, if any message has a date.
+    ///
+    pub latest_date: Option<String>,
+
+    /// The total size of the messages in bytes, without their `From ` lines.
+    ///
+    pub total_size: u64,
+
+    /// The number of messages whose headers failed to parse, or that could not be read from the mbox file.
+    ///
+    pub parse_failures: usize,
+}
+
+/// Extracts the [`MboxMetadata`] of mbox files natively.
+///
+/// The metadata of each message, including its envelope, is extracted from the message once it is embedded, see
+/// [`crate::metadata::Rfc822MetadataProcessor`].
+///
+#[derive(Debug, Default)]
+pub struct MboxMetadataProcessor {
+    message_parser: MessageParser,
+}
+
+impl MboxMetadataProcessor {
+    /// Adds a message to the metadata of the mbox file it was read from.
+    ///
+    fn add_message(&self, metadata: &mut MboxMetadata, dates: &mut Vec<i64>, message: &MboxMessage) {
+        metadata.total_size += message.size;
+        let Some(parsed) = self.message_parser.parse_headers(message.contents.as_slice()) else {
+            metadata.parse_failures += 1;
+            return;
+        };
+
+        let date = parsed.date()
+            .filter(|date| date.is_valid())
+            .map(|date| date.to_timestamp())
+            .or(message.envelope_timestamp);
+        dates.extend(date);
+    }
+}
+
+#[async_trait]
+impl Process for MboxMetadataProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        output_path: TempPath,
+        checksum: &str,
+    ) -> Result<(), ProcessError> {
+        let reader = MboxReader::open(&ctx, input_path)
+            .context("failed to open mbox file")?;
+
+        let mut metadata = MboxMetadata::default();
+        let mut dates = vec![];
+        for message in reader {
+            if ctx.is_cancelled() {
+                return Err(ProcessError::cancelled());
+            }
+
+            metadata.message_count += 1;
+            match message {
+                Ok(message) => self.add_message(&mut metadata, &mut dates, &message),
+                Err(_) => metadata.parse_failures += 1,
+            }
+        }
+        let format_date = |timestamp: &i64| DateTime::from_timestamp(*timestamp).to_rfc3339();
+        metadata.earliest_date = dates.iter().min().map(format_date);
+        metadata.latest_date = dates.iter().max().map(format_date);
+
+        let file = std::fs::File::create(&output_path)
+            .context("failed to create metadata file")?;
+        serde_json::to_writer_pretty(file, &metadata)
+            .context("failed to write metadata to file")?;
+
+        let output = ProcessOutput::processed(&ctx, "metadata.json", output_path, "application/json", checksum);
+        ctx.add_output(Ok(output)).await
+    }
+
+    fn name(&self) -> &'static str {
+        "Mbox Metadata"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::processing::{ProcessContextBuilder, ProcessType};
+
+    use super::*;
+
+    async fn process(content: &[u8]) -> anyhow::Result<MboxMetadata> {
+        let (output_sink, mut outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("application/mbox", vec![ProcessType::Metadata], output_sink).build();
+        let input_path = tempfile::NamedTempFile::new()?.into_temp_path();
+        std::fs::write(&input_path, content)?;
+        let output_path = tempfile::NamedTempFile::new()?.into_temp_path();
+
+        MboxMetadataProcessor::default().process(ctx, &input_path, output_path, "checksum").await?;
+
+        let Some(Ok(ProcessOutput::Processed(_, data))) = outputs.recv().await else {
+            panic!("Expected a processed output");
+        };
+        assert_eq!(data.name, "metadata.json");
+        Ok(serde_json::from_reader(std::fs::File::open(&data.path)?)?)
+    }
+
+    #[tokio::test]
+    async fn test_process() -> anyhow::Result<()> {
+        let metadata = process(b"\
+From phillip.allen@enron.com Tue May 15 09:00:00 2001
+Date: Mon, 14 May 2001 16:39:00 -0700
+Subject: First
+
+Body
+From tim.belden@enron.com Wed May 16 10:30:00 2001
+Subject: Undated
+
+Body
+From jeff@enron.com Thu May 17 08:00:00 2001
+").await?;
+
+        assert_eq!(metadata, MboxMetadata {
+            message_count: 3,
+            earliest_date: Some("2001-05-14T23:39:00Z".to_string()),
+            latest_date: Some("2001-05-16T10:30:00Z".to_string()),
+            total_size: 82,
+            parse_failures: 1,
+        });
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_empty() -> anyhow::Result<()> {
+        assert_eq!(process(b"").await?, MboxMetadata::default());
+        Ok(())
+    }
+}
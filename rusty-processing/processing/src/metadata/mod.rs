@@ -2,11 +2,19 @@ use std::path::Path;
 
 use anyhow::Context;
 use async_trait::async_trait;
+use serde_json::json;
 use tempfile::TempPath;
 
-use services::tika;
+use crate::processing::{InputStream, Process, ProcessContext, ProcessError, ProcessOutput, StreamedOutput};
+use crate::text::charset;
 
-use crate::processing::{Process, ProcessContext, ProcessOutput};
+pub use html::*;
+pub use mbox::*;
+pub use rfc822::*;
+
+mod html;
+mod mbox;
+mod rfc822;
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct DefaultMetadataProcessor;
@@ -19,9 +27,9 @@ impl Process for DefaultMetadataProcessor {
         input_path: &Path,
         output_path: TempPath,
         checksum: &str,
-    ) -> Result<(), anyhow::Error> {
-        let result = async {
-            let mut metadata = tika().metadata(input_path).await
+    ) -> Result<(), ProcessError> {
+        let result: anyhow::Result<ProcessOutput> = async {
+            let mut metadata = ctx.config().tika().metadata(input_path).await
                 .context("failed to extract metadata")?;
 
             tokio::fs::write(&output_path, &mut metadata).await
@@ -30,10 +38,111 @@ impl Process for DefaultMetadataProcessor {
             Ok(ProcessOutput::processed(&ctx, "metadata.json", output_path, "application/json", checksum))
         }.await;
 
-        ctx.add_output(result).await
+        ctx.add_output(result.map_err(ProcessError::from)).await
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    async fn process_stream(
+        &self,
+        ctx: ProcessContext,
+        input: InputStream,
+        output_path: TempPath,
+    ) -> Result<Vec<StreamedOutput>, ProcessError> {
+        let metadata = ctx.config().tika().metadata_from_reader(input).await
+            .context("failed to extract metadata")?;
+        tokio::fs::write(&output_path, metadata).await
+            .context("failed to write metadata to file")?;
+
+        Ok(vec![StreamedOutput {
+            name: "metadata.json".to_string(),
+            path: output_path,
+            mimetype: "application/json".to_string(),
+            kind: None,
+        }])
     }
 
     fn name(&self) -> &'static str {
         "Default Metadata"
     }
-}
\ No newline at end of file
+}
+
+/// Extracts the metadata of text files natively, i.e. the encoding detected by [`crate::text::NativeTextProcessor`].
+///
+/// The encoding is written as `Content-Encoding` and as the charset of the `Content-Type`, as Tika does.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TextMetadataProcessor;
+
+#[async_trait]
+impl Process for TextMetadataProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        output_path: TempPath,
+        checksum: &str,
+    ) -> Result<(), ProcessError> {
+        let content = std::fs::read(input_path)
+            .context("failed to read input file")?;
+        let decoded = charset::decode(&content);
+
+        let mimetype = ctx.mimetype.split(';').next().unwrap_or_default().trim();
+        let metadata = json!({
+            "Content-Type": format!("{}; charset={}", mimetype, decoded.encoding.name()),
+            "Content-Encoding": decoded.encoding.name(),
+            "Content-Length": content.len(),
+            "Byte-Order-Mark": decoded.bom,
+            "Decoding-Errors": decoded.had_errors,
+            "Line-Count": decoded.text.lines().count(),
+        });
+
+        let file = std::fs::File::create(&output_path)
+            .context("failed to create metadata file")?;
+        serde_json::to_writer_pretty(file, &metadata)
+            .context("failed to write metadata to file")?;
+
+        let output = ProcessOutput::processed(&ctx, "metadata.json", output_path, "application/json", checksum);
+        ctx.add_output(Ok(output)).await
+    }
+
+    fn name(&self) -> &'static str {
+        "Text Metadata"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::processing::{ProcessContextBuilder, ProcessType};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_text_metadata() -> anyhow::Result<()> {
+        let (output_sink, mut outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("text/plain; charset=unknown", vec![ProcessType::Metadata], output_sink).build();
+        let input_path = tempfile::NamedTempFile::new()?.into_temp_path();
+        let (content, _, _) = encoding_rs::WINDOWS_1252.encode("Le café coûte 3 € à la gare.\r\nTrès cher, s'il vous plaît.");
+        std::fs::write(&input_path, &content)?;
+        let output_path = tempfile::NamedTempFile::new()?.into_temp_path();
+
+        TextMetadataProcessor.process(ctx, &input_path, output_path, "checksum").await?;
+
+        let Some(Ok(ProcessOutput::Processed(_, data))) = outputs.recv().await else {
+            panic!("Expected a processed output");
+        };
+        let metadata: serde_json::Value = serde_json::from_reader(std::fs::File::open(&data.path)?)?;
+        assert_eq!(data.name, "metadata.json");
+        assert_eq!(metadata, json!({
+            "Content-Type": "text/plain; charset=windows-1252",
+            "Content-Encoding": "windows-1252",
+            "Content-Length": content.len(),
+            "Byte-Order-Mark": false,
+            "Decoding-Errors": false,
+            "Line-Count": 2,
+        }));
+        Ok(())
+    }
+}
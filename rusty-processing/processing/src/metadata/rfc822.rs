@@ -0,0 +1,413 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use mail_parser::{Addr, Address, DateTime, HeaderValue, Message, MessageParser, MimeHeaders};
+use serde::{Deserialize, Serialize};
+use tempfile::TempPath;
+
+use identify::deduplication::dedupe_checksum;
+
+use crate::embedded::{extract_attachment, ExtractionGuard, UNNAMED_ATTACHMENT};
+use crate::mimetype;
+use crate::processing::{MboxEnvelope, Process, ProcessContext, ProcessError, ProcessErrorKind, ProcessOutput};
+
+/// The metadata of a message, written to `metadata.json`.
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Rfc822Metadata {
+    /// The authors of the message.
+    ///
+    pub from: Vec<Mailbox>,
+
+    /// The primary recipients of the message.
+    ///
+    pub to: Vec<Mailbox>,
+
+    /// The secondary recipients of the message.
+    ///
+    pub cc: Vec<Mailbox>,
+
+    /// The blind recipients of the message, if it still lists them.
+    ///
+    pub bcc: Vec<Mailbox>,
+
+    /// The mailboxes replies to the message should be sent to.
+    ///
+    pub reply_to: Vec<Mailbox>,
+
+    /// The date the message was sent, if it has a valid one.
+    ///
+    pub date: Option<MessageDate>,
+
+    /// The Message-ID of the message, without angle brackets.
+    ///
+    pub message_id: Option<String>,
+
+    /// The Message-IDs of the messages this message replies to.
+    ///
+    pub in_reply_to: Vec<String>,
+
+    /// The Message-IDs of the messages in the conversation this message belongs to, oldest first.
+    ///
+    pub references: Vec<String>,
+
+    /// The subject of the message.
+    ///
+    pub subject: Option<String>,
+
+    /// The `Importance` header of the message, e.g. `high`.
+    ///
+    pub importance: Option<String>,
+
+    /// The `X-Priority` or `Priority` header of the message, e.g. `1 (Highest)`.
+    ///
+    pub priority: Option<String>,
+
+    /// The attachments of the message, in the order they occur in it.
+    ///
+    pub attachments: Vec<AttachmentMetadata>,
+
+    /// The number of body parts of the message.
+    ///
+    pub parts: PartCounts,
+
+    /// The envelope of the message, if it was read from an mbox file.
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub envelope: Option<MboxEnvelope>,
+}
+
+/// A mailbox in an address header of a message.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mailbox {
+    /// The display name of the mailbox, e.g. `Phillip Allen`.
+    ///
+    pub name: Option<String>,
+
+    /// The email address of the mailbox.
+    ///
+    pub address: Option<String>,
+
+    /// The name of the group the mailbox was listed in, e.g. `Undisclosed recipients`.
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+}
+
+/// The date of a message, normalized to UTC.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MessageDate {
+    /// The date in UTC in RFC 3339 format, e.g. `2021-02-21T15:58:00Z`.
+    ///
+    pub utc: String,
+
+    /// The date as given in the message in RFC 3339 format, e.g. `2021-02-21T07:58:00-08:00`.
+    ///
+    pub original: String,
+
+    /// The offset of the original date from UTC, e.g. `-08:00`.
+    ///
+    pub offset: String,
+}
+
+/// An attachment of a message.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AttachmentMetadata {
+    /// The file name of the attachment.
+    ///
+    pub name: String,
+
+    /// The MIME type of the attachment, detected from its content if the declared one can not be trusted.
+    ///
+    pub mimetype: String,
+
+    /// The size of the decoded attachment in bytes.
+    ///
+    pub size: u64,
+
+    /// The dedupe checksum of the attachment, as of the file embedded for it.
+    ///
+    pub checksum: String,
+}
+
+/// The number of body parts of a message.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartCounts {
+    /// The number of all MIME parts, including multipart containers.
+    ///
+    pub total: usize,
+
+    /// The number of plain text bodies.
+    ///
+    pub text: usize,
+
+    /// The number of HTML bodies, not counting text bodies `mail_parser` converts to HTML.
+    ///
+    pub html: usize,
+
+    /// The number of attachments.
+    ///
+    pub attachments: usize,
+}
+
+/// Extracts the [`Rfc822Metadata`] of a message natively.
+///
+/// Attachments are written to disk to detect their MIME type within the [`crate::processing::ExtractionLimits`], as
+/// they are when embedded. Attachments exceeding the limits are listed with their declared MIME type instead.
+///
+#[derive(Debug, Default)]
+pub struct Rfc822MetadataProcessor {
+    message_parser: MessageParser,
+}
+
+#[async_trait]
+impl Process for Rfc822MetadataProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        output_path: TempPath,
+        checksum: &str,
+    ) -> Result<(), ProcessError> {
+        let content = std::fs::read(input_path)
+            .context("failed to read input file")?;
+        let message = self.message_parser.parse(&content)
+            .ok_or_else(|| ProcessError::new(ProcessErrorKind::Corrupt, anyhow!("failed to parse message")))?;
+
+        let mut metadata = message_metadata(&message);
+        metadata.envelope = ctx.state.envelope.as_deref().cloned();
+        let mut guard = ExtractionGuard::new(&ctx);
+        let extractable = guard.check_depth().is_ok();
+        for part in message.attachments() {
+            if ctx.is_cancelled() {
+                return Err(ProcessError::cancelled());
+            }
+
+            let (mimetype, checksum) = if extractable && guard.admit(part.len() as u64, None).is_ok() {
+                let attachment = extract_attachment(&ctx, part).await?;
+                (attachment.mimetype, attachment.checksum)
+            } else {
+                // Attachments exceeding the extraction limits are not written to disk, so their MIME type is not
+                // detected from their content
+                let declared_mimetype = part.content_type().map(mimetype).unwrap_or("application/octet-stream".to_string());
+                let checksum = dedupe_checksum(&mut part.contents(), &declared_mimetype).await
+                    .context("failed to calculate checksum")?;
+                (declared_mimetype, checksum)
+            };
+            metadata.attachments.push(AttachmentMetadata {
+                name: part.attachment_name().unwrap_or(UNNAMED_ATTACHMENT).to_string(),
+                mimetype,
+                size: part.len() as u64,
+                checksum,
+            });
+        }
+
+        let file = std::fs::File::create(&output_path)
+            .context("failed to create metadata file")?;
+        serde_json::to_writer_pretty(file, &metadata)
+            .context("failed to write metadata to file")?;
+
+        let output = ProcessOutput::processed(&ctx, "metadata.json", output_path, "application/json", checksum);
+        ctx.add_output(Ok(output)).await
+    }
+
+    fn name(&self) -> &'static str {
+        "RFC 822 Metadata"
+    }
+}
+
+/// Extracts the metadata of the message from its headers and structure, without its attachments.
+///
+fn message_metadata(message: &Message) -> Rfc822Metadata {
+    let header_text = |names: &[&str]| {
+        names.iter()
+            .find_map(|name| message.header_raw(*name))
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+
+    Rfc822Metadata {
+        from: mailboxes(message.from()),
+        to: mailboxes(message.to()),
+        cc: mailboxes(message.cc()),
+        bcc: mailboxes(message.bcc()),
+        reply_to: mailboxes(message.reply_to()),
+        date: message.date().filter(|date| date.is_valid()).map(message_date),
+        message_id: message.message_id().map(str::to_string),
+        in_reply_to: text_list(message.in_reply_to()),
+        references: text_list(message.references()),
+        subject: message.subject().map(str::to_string),
+        importance: header_text(&["Importance"]),
+        priority: header_text(&["X-Priority", "Priority"]),
+        attachments: vec![],
+        parts: PartCounts {
+            total: message.parts.len(),
+            text: message.text_bodies().filter(|part| part.is_text() && !part.is_text_html()).count(),
+            html: message.html_bodies().filter(|part| part.is_text_html()).count(),
+            attachments: message.attachment_count(),
+        },
+        envelope: None,
+    }
+}
+
+fn mailboxes(address: Option<&Address>) -> Vec<Mailbox> {
+    let mailbox = |addr: &Addr, group: Option<&str>| Mailbox {
+        name: addr.name().map(str::to_string),
+        address: addr.address().map(str::to_string),
+        group: group.map(str::to_string),
+    };
+
+    match address {
+        Some(Address::List(addresses)) => addresses.iter()
+            .map(|addr| mailbox(addr, None))
+            .collect(),
+        Some(Address::Group(groups)) => groups.iter()
+            .flat_map(|group| group.addresses.iter().map(|addr| mailbox(addr, group.name.as_deref())))
+            .collect(),
+        None => vec![],
+    }
+}
+
+fn message_date(date: &DateTime) -> MessageDate {
+    let sign = if date.tz_before_gmt && (date.tz_hour > 0 || date.tz_minute > 0) { '-' } else { '+' };
+    MessageDate {
+        utc: DateTime::from_timestamp(date.to_timestamp()).to_rfc3339(),
+        original: date.to_rfc3339(),
+        offset: format!("{}{:02}:{:02}", sign, date.tz_hour, date.tz_minute),
+    }
+}
+
+fn text_list(value: &HeaderValue) -> Vec<String> {
+    match value {
+        HeaderValue::Text(text) => vec![text.to_string()],
+        HeaderValue::TextList(texts) => texts.iter().map(|text| text.to_string()).collect(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::processing::{ProcessContextBuilder, ProcessorBuilder, ProcessType};
+
+    use super::*;
+
+    const MESSAGE: &[u8] = b"\
+Message-ID: <reply-1@enron.com>
+In-Reply-To: <original-1@enron.com>
+References: <thread-1@enron.com> <original-1@enron.com>
+Date: Mon, 14 May 2001 16:39:00 -0700 (PDT)
+From: Phillip Allen <phillip.allen@enron.com>
+To: tim.belden@enron.com, Jeff <jeff@enron.com>
+Cc: Undisclosed recipients: mike@enron.com;
+Subject: Re: Forecast
+Importance: high
+X-Priority: 1 (Highest)
+Content-Type: multipart/mixed; boundary=\"outer\"
+
+--outer
+Content-Type: text/plain
+
+Here is the forecast.
+--outer
+Content-Type: text/plain; name=\"forecast.txt\"
+Content-Disposition: attachment; filename=\"forecast.txt\"
+
+gas,power
+--outer--
+";
+
+    #[test]
+    fn test_message_metadata() {
+        let message = MessageParser::default().parse(MESSAGE).unwrap();
+
+        let metadata = message_metadata(&message);
+
+        let mailbox = |name: Option<&str>, address: &str, group: Option<&str>| Mailbox {
+            name: name.map(str::to_string),
+            address: Some(address.to_string()),
+            group: group.map(str::to_string),
+        };
+        assert_eq!(metadata.from, vec![mailbox(Some("Phillip Allen"), "phillip.allen@enron.com", None)]);
+        assert_eq!(metadata.to, vec![
+            mailbox(None, "tim.belden@enron.com", None),
+            mailbox(Some("Jeff"), "jeff@enron.com", None),
+        ]);
+        assert_eq!(metadata.cc, vec![mailbox(None, "mike@enron.com", Some("Undisclosed recipients"))]);
+        assert!(metadata.bcc.is_empty());
+        assert_eq!(metadata.date, Some(MessageDate {
+            utc: "2001-05-14T23:39:00Z".to_string(),
+            original: "2001-05-14T16:39:00-07:00".to_string(),
+            offset: "-07:00".to_string(),
+        }));
+        assert_eq!(metadata.message_id.as_deref(), Some("reply-1@enron.com"));
+        assert_eq!(metadata.in_reply_to, vec!["original-1@enron.com"]);
+        assert_eq!(metadata.references, vec!["thread-1@enron.com", "original-1@enron.com"]);
+        assert_eq!(metadata.subject.as_deref(), Some("Re: Forecast"));
+        assert_eq!(metadata.importance.as_deref(), Some("high"));
+        assert_eq!(metadata.priority.as_deref(), Some("1 (Highest)"));
+        assert_eq!(metadata.parts, PartCounts { total: 3, text: 1, html: 0, attachments: 1 });
+    }
+
+    #[tokio::test]
+    async fn test_process() -> anyhow::Result<()> {
+        let (output_sink, mut outputs) = tokio::sync::mpsc::channel(10);
+        let mut ctx = ProcessContextBuilder::new("message/rfc822", vec![ProcessType::Metadata], output_sink).build();
+        let envelope = MboxEnvelope {
+            sender: "phillip.allen@enron.com".to_string(),
+            date: Some("2001-05-14T23:40:00Z".to_string()),
+            offset: 1024,
+        };
+        ctx.state.envelope = Some(Box::new(envelope.clone()));
+        let input_path = tempfile::NamedTempFile::new()?.into_temp_path();
+        std::fs::write(&input_path, MESSAGE)?;
+        let output_path = tempfile::NamedTempFile::new()?.into_temp_path();
+
+        Rfc822MetadataProcessor::default().process(ctx, &input_path, output_path, "checksum").await?;
+
+        let Some(Ok(ProcessOutput::Processed(_, data))) = outputs.recv().await else {
+            panic!("Expected a processed output");
+        };
+        let metadata: Rfc822Metadata = serde_json::from_reader(std::fs::File::open(&data.path)?)?;
+        assert_eq!(data.name, "metadata.json");
+        assert_eq!(metadata.attachments.len(), 1);
+        assert_eq!(metadata.attachments[0].name, "forecast.txt");
+        assert_eq!(metadata.attachments[0].mimetype, "text/plain");
+        assert_eq!(metadata.attachments[0].size, 9);
+        assert_eq!(metadata.attachments[0].checksum, dedupe_checksum(&mut b"gas,power".as_slice(), "text/plain").await?);
+        assert_eq!(metadata.envelope, Some(envelope));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_max_entries() -> anyhow::Result<()> {
+        let (output_sink, mut outputs) = tokio::sync::mpsc::channel(10);
+        let config = Arc::new(ProcessorBuilder::new().max_entries(0).build().config().clone());
+        let ctx = ProcessContextBuilder::new("message/rfc822", vec![ProcessType::Metadata], output_sink)
+            .build()
+            .with_config(config);
+        let input_path = tempfile::NamedTempFile::new()?.into_temp_path();
+        std::fs::write(&input_path, MESSAGE)?;
+        let output_path = tempfile::NamedTempFile::new()?.into_temp_path();
+
+        Rfc822MetadataProcessor::default().process(ctx, &input_path, output_path, "checksum").await?;
+
+        let Some(Ok(ProcessOutput::Processed(_, data))) = outputs.recv().await else {
+            panic!("Expected a processed output");
+        };
+        let metadata: Rfc822Metadata = serde_json::from_reader(std::fs::File::open(&data.path)?)?;
+        assert_eq!(metadata.attachments, vec![AttachmentMetadata {
+            name: "forecast.txt".to_string(),
+            mimetype: "text/plain".to_string(),
+            size: 9,
+            checksum: dedupe_checksum(&mut b"gas,power".as_slice(), "text/plain").await?,
+        }]);
+        Ok(())
+    }
+}
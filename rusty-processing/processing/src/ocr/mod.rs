@@ -0,0 +1,85 @@
+use std::path::Path;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use log::info;
+use tempfile::TempPath;
+
+use services::{ImageFormat, ocr, pdf_to_image};
+
+use crate::processing::{Process, ProcessContext, ProcessError, ProcessOutput};
+
+/// The resolution in dots per inch pages are rendered at before recognizing their text, which tesseract works best at.
+///
+const OCR_RESOLUTION: u32 = 300;
+
+/// Recognizes the text in an image with tesseract, creating a file named `ocr.<extension>` for each of the
+/// [`crate::processing::ProcessorConfig::ocr_formats`].
+///
+#[derive(Debug, Default)]
+pub struct OcrProcessor {
+    render_pages: bool,
+}
+
+impl OcrProcessor {
+    /// Creates a processor recognizing the text in images of each page of a PDF, e.g. a scanned document.
+    ///
+    /// The text of all pages is combined into one file of each format.
+    ///
+    pub fn rendering_pages() -> Self {
+        Self { render_pages: true }
+    }
+}
+
+#[async_trait]
+impl Process for OcrProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        _: TempPath,
+        checksum: &str,
+    ) -> Result<(), ProcessError> {
+        let work_dir = ctx.temp_subdir()
+            .context("failed to create temporary directory")?;
+
+        let image_path = if self.render_pages {
+            let pages = pdf_to_image()
+                .render_pages(input_path, work_dir.path(), ImageFormat::Png, OCR_RESOLUTION).await
+                .context("failed to render pages")?;
+            info!("Recognizing text in {} pages", pages.len());
+
+            let list = pages.iter()
+                .map(|page| page.display().to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            let list_path = work_dir.path().join("pages.txt");
+            std::fs::write(&list_path, list)
+                .context("failed to write list of pages")?;
+            list_path
+        } else {
+            input_path.to_path_buf()
+        };
+
+        let formats = ctx.config().ocr_formats();
+        let paths = ocr()
+            .recognize(image_path, work_dir.path().join("ocr"), formats, ctx.config().ocr_language()).await
+            .context("failed to recognize text")?;
+
+        for (format, recognized) in formats.iter().zip(paths) {
+            let path = ctx.temp_file()
+                .context("failed to create temporary file")?
+                .into_temp_path();
+            std::fs::rename(recognized, &path)
+                .context("failed to move recognized text")?;
+
+            let name = format!("ocr.{}", format.extension());
+            ctx.add_output(Ok(ProcessOutput::processed(&ctx, name, path, format.mimetype(), checksum))).await?;
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "OCR"
+    }
+}
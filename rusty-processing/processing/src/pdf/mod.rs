@@ -1,3 +1,3 @@
-mod rfc822;
+pub(crate) mod rfc822;
 
 pub use rfc822::*;
\ No newline at end of file
@@ -4,12 +4,34 @@ use html_escape::encode_text;
 use mail_parser::{Addr, ContentType, DateTime, Group};
 use crate::pdf::rfc822::message_formatter::MessageFormatter;
 use crate::pdf::rfc822::message_visitor::MessageVisitor;
+use crate::processing::EntityRule;
+use crate::redaction::redact;
 
 const HEADERS: [&str; 6] = ["Date", "From", "To", "CC", "BCC", "Subject"];
 
 #[derive(Default)]
 pub struct HtmlMessageVisitor {
     formatter: MessageFormatter,
+    redaction_rules: Vec<EntityRule>,
+}
+
+impl HtmlMessageVisitor {
+    /// Creates a visitor replacing the entities the rules find in header addresses, header text and text bodies with
+    /// the same placeholders as the redacted text.
+    ///
+    pub fn redacting(redaction_rules: Vec<EntityRule>) -> Self {
+        Self {
+            formatter: MessageFormatter::default(),
+            redaction_rules,
+        }
+    }
+
+    fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if self.redaction_rules.is_empty() {
+            return Cow::Borrowed(text);
+        }
+        Cow::Owned(redact(&self.redaction_rules, text).0)
+    }
 }
 
 impl MessageVisitor for HtmlMessageVisitor {
@@ -45,25 +67,25 @@ impl MessageVisitor for HtmlMessageVisitor {
     fn on_header_addresses(&self, name: &str, address_list: &[Addr]) -> Option<String> {
         self.formatter
             .format_addresses(address_list)
-            .map(|addrs| format!("<b>{}</b>: {}", name, encode_text(addrs.as_str())))
+            .map(|addrs| format!("<b>{}</b>: {}", name, encode_text(&self.redact(&addrs))))
     }
 
     fn on_header_groups(&self, name: &str, group_list: &[Group]) -> Option<String> {
         self.formatter
             .format_groups(group_list)
-            .map(|groups| format!("<b>{}</b>: {}", name, encode_text(groups.as_str())))
+            .map(|groups| format!("<b>{}</b>: {}", name, encode_text(&self.redact(&groups))))
     }
 
     fn on_header_text(&self, name: &str, text: Cow<str>) -> Option<String> {
         HEADERS
             .contains(&name)
-            .then_some(format!("<b>{}</b>: {}", name, encode_text(&text)))
+            .then(|| format!("<b>{}</b>: {}", name, encode_text(&self.redact(&text))))
     }
 
     fn on_header_text_list(&self, name: &str, text_list: &[Cow<str>]) -> Option<String> {
         self.formatter
             .format_text_list(text_list)
-            .map(|texts| format!("<b>{}</b>: {}", name, encode_text(&texts)))
+            .map(|texts| format!("<b>{}</b>: {}", name, encode_text(&self.redact(&texts))))
     }
 
     fn on_header_date_time(&self, name: &str, date_time: &DateTime) -> Option<String> {
@@ -79,7 +101,7 @@ impl MessageVisitor for HtmlMessageVisitor {
     }
 
     fn on_part_text(&self, value: Cow<str>) -> String {
-        value
+        self.redact(&value)
             .split('\n')
             .map(|line| format!("<p>{}</p>", encode_text(line)))
             .collect::<Vec<String>>()
@@ -120,4 +142,36 @@ mod test {
         assert_eq!(expected_content, String::from_utf8(content)?);
         Ok(())
     }
+
+    #[test]
+    fn test_html_message_visitor_redacting() -> anyhow::Result<()> {
+        let content = read_contents("../resources/rfc822/headers-small.eml").unwrap();
+        let message = MessageParser::default().parse(&content).ok_or(anyhow!("Failed to parse message"))?;
+        let visitor = Box::new(HtmlMessageVisitor::redacting(EntityRule::defaults()));
+        let transformer = MessageTransformer::new(visitor);
+
+        let mut content = vec![];
+        transformer.transform(&message, &mut content)?;
+
+        let content = String::from_utf8(content)?;
+        assert!(content.contains("<div><b>From</b>: &lt;[REDACTED EMAIL]&gt;</div>"), "{}", content);
+        assert!(content.contains("<div><b>To</b>: &lt;[REDACTED EMAIL]&gt;</div>"), "{}", content);
+        assert!(!content.contains("@mime.com"), "{}", content);
+        Ok(())
+    }
+
+    #[test]
+    fn test_html_message_visitor_text_only() -> anyhow::Result<()> {
+        let content = b"From: rusty.processing@mime.com\r\nSubject: Rusty\r\nContent-Type: text/html\r\n\r\n<p>Hello <b>rust</b></p>\r\n";
+        let message = MessageParser::default().parse(content.as_slice()).ok_or(anyhow!("Failed to parse message"))?;
+        let transformer = MessageTransformer::new(Box::<HtmlMessageVisitor>::default()).text_only();
+
+        let mut content = vec![];
+        transformer.transform(&message, &mut content)?;
+
+        let content = String::from_utf8(content)?;
+        assert!(content.contains("<p>Hello rust</p>"), "{}", content);
+        assert!(!content.contains("<b>rust</b>"), "{}", content);
+        Ok(())
+    }
 }
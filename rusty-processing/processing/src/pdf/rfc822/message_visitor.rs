@@ -1,6 +1,6 @@
 use std::borrow::Cow;
 
-use mail_parser::{Addr, ContentType, DateTime, Group, Received};
+use mail_parser::{Addr, ContentType, DateTime, Group, MessagePart, Received};
 
 pub trait MessageVisitor {
     fn on_header_prefix(&self) -> Option<String> {
@@ -82,4 +82,19 @@ pub trait MessageVisitor {
     fn on_part_inline_binary(&self, value: Cow<[u8]>) -> Vec<u8> {
         value.to_vec()
     }
+
+    // Attachment visitors
+
+    /// Called once with all attachments of a message, including attached messages, if it has any.
+    ///
+    fn on_attachments(&self, _attachments: &[&MessagePart<'_>]) -> Option<String> {
+        None
+    }
+
+    /// Called before each message attached to a message. The attached message is only transformed if this returns a
+    /// prefix, which may be empty.
+    ///
+    fn on_attached_message_prefix(&self) -> Option<String> {
+        None
+    }
 }
@@ -1,18 +1,21 @@
 use std::fmt::Debug;
 use std::fs::File;
 use std::path::Path;
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 
 use async_trait::async_trait;
 use mail_parser::MessageParser;
 use tempfile::TempPath;
 
-use crate::processing::{Process, ProcessContext, ProcessOutput};
+use crate::pdf::rfc822::html_message_visitor::HtmlMessageVisitor;
+use crate::pdf::rfc822::transformer::MessageTransformer;
+use crate::processing::{Process, ProcessContext, ProcessError, ProcessErrorKind, ProcessOutput};
 
 mod html_message_visitor;
-mod message_formatter;
-mod message_visitor;
-mod transformer;
+pub(crate) mod message_formatter;
+pub(crate) mod message_visitor;
+pub(crate) mod text_message_visitor;
+pub(crate) mod transformer;
 
 mod pdf;
 
@@ -29,26 +32,96 @@ impl Process for Rfc822PdfProcessor {
         input_path: &Path,
         output_path: TempPath,
         checksum: &str,
-    ) -> Result<(), anyhow::Error> {
-        let result = async {
-            let content = std::fs::read(input_path)
-                .context("failed to read input file")?;
+    ) -> Result<(), ProcessError> {
+        let transformer = MessageTransformer::new(Box::<HtmlMessageVisitor>::default());
+        let result = render(&self.message_parser, transformer, &ctx, input_path, output_path, checksum).await;
+        ctx.add_output(result).await
+    }
 
-            let message = self.message_parser.parse(&content)
-                .context("failed to parse message")?;
+    fn name(&self) -> &'static str {
+        "RFC 822 PDF"
+    }
+}
 
-            let mut writer = File::create(&output_path)
-                .context("failed to create output file")?;
+/// Fallback for [`Rfc822PdfProcessor`], rendering only the text bodies of the message.
+///
+/// HTML bodies are converted to text first, so HTML the renderer fails on does not stop the message from being
+/// rendered.
+///
+#[derive(Debug, Default)]
+pub struct Rfc822TextPdfProcessor {
+    message_parser: MessageParser,
+}
 
-            self.render_pdf(&message, &mut writer).await
-                .map(|_| ProcessOutput::processed(&ctx, "rendered.pdf", output_path, "application/pdf", checksum))
-                .context("failed to render pdf")
-        }.await;
+#[async_trait]
+impl Process for Rfc822TextPdfProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        output_path: TempPath,
+        checksum: &str,
+    ) -> Result<(), ProcessError> {
+        let transformer = MessageTransformer::new(Box::<HtmlMessageVisitor>::default()).text_only();
+        let result = render(&self.message_parser, transformer, &ctx, input_path, output_path, checksum).await;
+        ctx.add_output(result).await
+    }
 
+    fn name(&self) -> &'static str {
+        "RFC 822 Text PDF"
+    }
+}
+
+/// Renders the message like [`Rfc822TextPdfProcessor`], replacing the entities found by the
+/// [`crate::processing::ProcessorConfig::redaction_rules`] in its headers and text bodies with placeholders.
+///
+/// Header addresses are redacted as rendered, so the PDF is consistent with the redacted text of the message.
+///
+#[derive(Debug, Default)]
+pub struct Rfc822RedactedPdfProcessor {
+    message_parser: MessageParser,
+}
+
+#[async_trait]
+impl Process for Rfc822RedactedPdfProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        output_path: TempPath,
+        checksum: &str,
+    ) -> Result<(), ProcessError> {
+        let visitor = HtmlMessageVisitor::redacting(ctx.config().redaction_rules().to_vec());
+        let transformer = MessageTransformer::new(Box::new(visitor)).text_only();
+        let result = render(&self.message_parser, transformer, &ctx, input_path, output_path, checksum).await;
         ctx.add_output(result).await
     }
 
     fn name(&self) -> &'static str {
-        "RFC 822 PDF"
+        "RFC 822 Redacted PDF"
     }
 }
+
+/// Renders the message in the input file to `rendered.pdf`.
+///
+async fn render(
+    message_parser: &MessageParser,
+    transformer: MessageTransformer,
+    ctx: &ProcessContext,
+    input_path: &Path,
+    output_path: TempPath,
+    checksum: &str,
+) -> Result<ProcessOutput, ProcessError> {
+    let content = std::fs::read(input_path)
+        .context("failed to read input file")?;
+
+    let message = message_parser.parse(&content)
+        .ok_or_else(|| ProcessError::new(ProcessErrorKind::Corrupt, anyhow!("failed to parse message")))?;
+
+    let mut writer = File::create(&output_path)
+        .context("failed to create output file")?;
+
+    pdf::render_pdf(transformer, &message, &mut writer).await
+        .context("failed to render pdf")?;
+    Ok(ProcessOutput::processed(ctx, "rendered.pdf", output_path, "application/pdf", checksum))
+}
@@ -0,0 +1,190 @@
+use std::borrow::Cow;
+
+use mail_parser::{Addr, ContentType, DateTime, Group, MessagePart, MimeHeaders};
+
+use crate::embedded::UNNAMED_ATTACHMENT;
+use crate::pdf::rfc822::message_visitor::MessageVisitor;
+
+/// The headers included in the text, in the order they occur in the message.
+///
+const HEADERS: [&str; 8] = ["Date", "From", "Sender", "Reply-To", "To", "Cc", "Bcc", "Subject"];
+
+/// Transforms a message to plain text, for [`crate::text::Rfc822TextProcessor`].
+///
+/// The text has the key headers of the message, followed by its text bodies, the names of its attachments and the text
+/// of the messages attached to it. Parts are separated by blank lines.
+///
+#[derive(Default)]
+pub struct TextMessageVisitor;
+
+impl TextMessageVisitor {
+    fn format_header(&self, name: &str, value: &str) -> Option<String> {
+        HEADERS
+            .iter()
+            .find(|header| header.eq_ignore_ascii_case(name))
+            .map(|header| format!("{}: {}", header, value))
+    }
+}
+
+/// Formats an address as `Name <address>`, or just the name or address if it only has one of them.
+///
+fn format_address(address: &Addr) -> Option<String> {
+    match (address.name(), address.address()) {
+        (Some(name), Some(address)) => Some(format!("{} <{}>", name, address)),
+        (Some(name), None) => Some(name.to_string()),
+        (None, Some(address)) => Some(address.to_string()),
+        (None, None) => None,
+    }
+}
+
+fn format_addresses(addresses: &[Addr]) -> String {
+    addresses.iter().filter_map(format_address).collect::<Vec<_>>().join(", ")
+}
+
+impl MessageVisitor for TextMessageVisitor {
+    fn on_head_body_separator(&self) -> Option<String> {
+        Some("\n".to_string())
+    }
+
+    fn on_part_suffix(&self) -> Option<String> {
+        Some("\n".to_string())
+    }
+
+    fn on_header_addresses(&self, name: &str, address_list: &[Addr]) -> Option<String> {
+        self.format_header(name, &format_addresses(address_list))
+    }
+
+    fn on_header_groups(&self, name: &str, group_list: &[Group]) -> Option<String> {
+        let groups = group_list.iter()
+            .map(|group| match &group.name {
+                Some(group_name) => format!("{}: {};", group_name, format_addresses(&group.addresses)),
+                None => format_addresses(&group.addresses),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        self.format_header(name, &groups)
+    }
+
+    fn on_header_text(&self, name: &str, text: Cow<str>) -> Option<String> {
+        self.format_header(name, &text)
+    }
+
+    fn on_header_date_time(&self, name: &str, date_time: &DateTime) -> Option<String> {
+        self.format_header(name, &date_time.to_rfc3339())
+    }
+
+    fn on_header_content_type(&self, _: &ContentType) -> Option<String> {
+        None
+    }
+
+    fn on_part_text(&self, value: Cow<str>) -> String {
+        format!("{}\n", value.replace("\r\n", "\n").trim_end())
+    }
+
+    fn on_part_html(&self, value: Cow<str>) -> String {
+        self.on_part_text(value)
+    }
+
+    fn on_part_binary(&self, _: Cow<[u8]>) -> Vec<u8> {
+        vec![]
+    }
+
+    fn on_part_inline_binary(&self, _: Cow<[u8]>) -> Vec<u8> {
+        vec![]
+    }
+
+    fn on_attachments(&self, attachments: &[&MessagePart]) -> Option<String> {
+        let names = attachments.iter()
+            .map(|attachment| format!("- {}\n", attachment.attachment_name().unwrap_or(UNNAMED_ATTACHMENT)))
+            .collect::<String>();
+        Some(format!("Attachments:\n{}\n", names))
+    }
+
+    fn on_attached_message_prefix(&self) -> Option<String> {
+        Some("---------- Attached message ----------\n".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mail_parser::MessageParser;
+    use test_utils::read_contents;
+
+    use crate::pdf::rfc822::transformer::MessageTransformer;
+
+    use super::*;
+
+    fn transform(content: &[u8]) -> String {
+        let message = MessageParser::default().parse(content).unwrap();
+        let transformer = MessageTransformer::new(Box::new(TextMessageVisitor)).text_only();
+
+        let mut text = vec![];
+        transformer.transform(&message, &mut text).unwrap();
+        String::from_utf8(text).unwrap()
+    }
+
+    #[test]
+    fn test_text_message_visitor() {
+        let content = read_contents("../resources/rfc822/headers-small.eml").unwrap();
+
+        assert_eq!(transform(&content), "\
+Date: 2021-02-21T07:58:00-08:00
+From: rusty.processing@mime.com
+To: processing.rusty@emim.com
+Subject: Now THATS A LOT OF RUST
+
+This is a rusty email
+
+;)
+
+");
+    }
+
+    #[test]
+    fn test_text_message_visitor_attachments() {
+        let content = b"\
+From: Phillip Allen <phillip.allen@enron.com>
+To: Undisclosed recipients:;
+Subject: Forecast
+Content-Type: multipart/mixed; boundary=\"outer\"
+
+--outer
+Content-Type: text/html
+
+<p>See <b>attached</b></p>
+--outer
+Content-Type: application/pdf; name=\"forecast.pdf\"
+Content-Disposition: attachment; filename=\"forecast.pdf\"
+Content-Transfer-Encoding: base64
+
+JVBERi0xLjQK
+--outer
+Content-Type: message/rfc822
+
+From: jeff@enron.com
+Subject: Original
+
+Original text
+--outer--
+";
+
+        assert_eq!(transform(content), "\
+From: Phillip Allen <phillip.allen@enron.com>
+To: Undisclosed recipients: ;
+Subject: Forecast
+
+See attached
+
+Attachments:
+- forecast.pdf
+- message-attachment.dat
+
+---------- Attached message ----------
+From: jeff@enron.com
+Subject: Original
+
+Original text
+
+");
+    }
+}
@@ -3,6 +3,7 @@ use std::io;
 use std::io::Write;
 
 use mail_parser::{Address, HeaderValue, Message, MessagePart, PartType};
+use mail_parser::decoders::html::html_to_text;
 
 use crate::pdf::rfc822::message_visitor::MessageVisitor;
 
@@ -10,13 +11,23 @@ use crate::pdf::rfc822::message_visitor::MessageVisitor;
 ///
 pub struct MessageTransformer {
     visitor: Box<dyn MessageVisitor + Send + Sync>,
+    text_only: bool,
 }
 
 impl MessageTransformer {
     /// Creates a new transformer that will use the provided visitor to transform the message.
     ///
     pub fn new(visitor: Box<dyn MessageVisitor + Send + Sync>) -> Self {
-        Self { visitor }
+        Self { visitor, text_only: false }
+    }
+
+    /// Transforms the text bodies of the message only, converting HTML bodies to text.
+    ///
+    /// By default, the HTML bodies of the message are transformed if it has any.
+    ///
+    pub fn text_only(mut self) -> Self {
+        self.text_only = true;
+        self
     }
 
     /// Transforms the message and writes the result to the provided writer.
@@ -41,7 +52,7 @@ impl MessageTransformer {
 
         self.write_if_some(writer, self.visitor.on_head_body_separator())?;
 
-        let bodies = if message.html_body_count() > 0 {
+        let bodies = if !self.text_only && message.html_body_count() > 0 {
             message.html_bodies()
         } else {
             message.text_bodies()
@@ -52,6 +63,17 @@ impl MessageTransformer {
             self.write_if_some(writer, self.visitor.on_part_suffix())?;
         }
 
+        let attachments = message.attachments().collect::<Vec<_>>();
+        if !attachments.is_empty() {
+            self.write_if_some(writer, self.visitor.on_attachments(&attachments))?;
+        }
+        for attached in attachments.iter().filter_map(|attachment| attachment.message()) {
+            if let Some(prefix) = self.visitor.on_attached_message_prefix() {
+                writer.write_all(prefix.as_bytes())?;
+                self.transform(attached, writer)?;
+            }
+        }
+
         Ok(())
     }
 
@@ -98,6 +120,11 @@ impl MessageTransformer {
                 writer.write_all(text.as_bytes())?;
             }
 
+            PartType::Html(html) if self.text_only => {
+                let text = self.visitor.on_part_text(Cow::Owned(html_to_text(html)));
+                writer.write_all(text.as_bytes())?;
+            }
+
             PartType::Html(html) => {
                 let html = self.visitor.on_part_html(Cow::to_owned(html));
                 writer.write_all(html.as_bytes())?;
@@ -0,0 +1,567 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+use tempfile::{NamedTempFile, TempDir};
+
+use identify::hashing::HashAlgorithm;
+use services::{ImageFormat, OcrFormat, Tika};
+
+use crate::processing::{EntityRule, Processor, ProcessorRegistry};
+
+lazy_static! {
+    static ref DEFAULT_CONFIG: Arc<ProcessorConfig> = Arc::new(ProcessorConfig::default());
+}
+
+/// Returns the configuration used when none was provided, shared between all users of it.
+///
+pub(crate) fn default_config() -> Arc<ProcessorConfig> {
+    DEFAULT_CONFIG.clone()
+}
+
+/// The default resolution of page images in dots per inch.
+///
+pub const DEFAULT_IMAGE_RESOLUTION: u32 = 150;
+
+/// The default width in columns text extracted from HTML is wrapped to.
+///
+pub const DEFAULT_HTML_TEXT_WIDTH: usize = 100;
+
+/// Settings of a [`Processor`].
+///
+/// The configuration is made available to each [`crate::processing::Process`] implementation through the
+/// [`crate::processing::ProcessContext`] of the processing operation.
+///
+#[derive(Debug, Clone)]
+pub struct ProcessorConfig {
+    temp_dir: Option<PathBuf>,
+    disabled_processors: HashSet<String>,
+    max_concurrency: Option<NonZeroUsize>,
+    max_input_size: Option<u64>,
+    default_timeout: Option<Duration>,
+    timeouts: HashMap<String, Duration>,
+    limits: ExtractionLimits,
+    image_format: ImageFormat,
+    image_resolution: u32,
+    ocr_formats: Vec<OcrFormat>,
+    ocr_language: Option<String>,
+    hash_algorithms: Vec<HashAlgorithm>,
+    detect_language: bool,
+    entity_rules: Vec<EntityRule>,
+    redaction_rules: Vec<EntityRule>,
+    html_text_width: usize,
+    mbox_variant: Option<MboxVariant>,
+    tika: Arc<Tika>,
+}
+
+/// Limits on extracting embedded files, guarding against archive bombs and unbounded recursion.
+///
+/// All limits are unlimited by default.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExtractionLimits {
+    /// The maximum depth of embedded files, where the root file has a depth of 0.
+    ///
+    pub max_depth: Option<usize>,
+
+    /// The maximum number of files extracted from a single container.
+    ///
+    pub max_entries: Option<usize>,
+
+    /// The maximum number of uncompressed bytes extracted from a single container.
+    ///
+    pub max_total_bytes: Option<u64>,
+
+    /// The maximum ratio of uncompressed to compressed size of a single compressed entry.
+    ///
+    pub max_compression_ratio: Option<f64>,
+}
+
+/// A variant of the mbox format, which differ in how messages are separated and how lines starting with `From ` in
+/// messages are quoted.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MboxVariant {
+    /// Messages are separated by `From ` lines, and `From ` lines in messages are quoted as `>From `.
+    ///
+    /// Lines that started with `>From ` before quoting can not be told apart from quoted lines, and are unquoted too.
+    ///
+    Mboxo,
+
+    /// Messages are separated by `From ` lines, and lines in messages starting with `From ` preceded by any number of
+    /// `>` are quoted with another `>`, so unquoting restores the original lines.
+    ///
+    Mboxrd,
+
+    /// Messages are separated by their `Content-Length` headers, and `From ` lines in messages are quoted like in
+    /// [`MboxVariant::Mboxo`].
+    ///
+    Mboxcl,
+
+    /// Messages are separated by their `Content-Length` headers, and lines in messages are not quoted.
+    ///
+    Mboxcl2,
+}
+
+impl MboxVariant {
+    /// Returns whether messages of the variant are separated by their `Content-Length` headers.
+    ///
+    pub fn uses_content_length(&self) -> bool {
+        matches!(self, MboxVariant::Mboxcl | MboxVariant::Mboxcl2)
+    }
+}
+
+impl FromStr for MboxVariant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mboxo" => Ok(MboxVariant::Mboxo),
+            "mboxrd" => Ok(MboxVariant::Mboxrd),
+            "mboxcl" => Ok(MboxVariant::Mboxcl),
+            "mboxcl2" => Ok(MboxVariant::Mboxcl2),
+            _ => Err(format!("Can not convert {} to MboxVariant", s)),
+        }
+    }
+}
+
+impl Default for ProcessorConfig {
+    fn default() -> Self {
+        Self {
+            temp_dir: None,
+            disabled_processors: HashSet::new(),
+            max_concurrency: None,
+            max_input_size: None,
+            default_timeout: None,
+            timeouts: HashMap::new(),
+            limits: ExtractionLimits::default(),
+            image_format: ImageFormat::default(),
+            image_resolution: DEFAULT_IMAGE_RESOLUTION,
+            ocr_formats: vec![OcrFormat::Text],
+            ocr_language: None,
+            hash_algorithms: vec![HashAlgorithm::Md5, HashAlgorithm::Sha1, HashAlgorithm::Sha256],
+            detect_language: false,
+            entity_rules: EntityRule::defaults(),
+            redaction_rules: EntityRule::defaults(),
+            html_text_width: DEFAULT_HTML_TEXT_WIDTH,
+            mbox_variant: None,
+            tika: Arc::new(Tika::default()),
+        }
+    }
+}
+
+impl ProcessorConfig {
+    /// The directory temporary files are created in, or [`None`] to use the system's temporary directory.
+    ///
+    pub fn temp_dir(&self) -> Option<&Path> {
+        self.temp_dir.as_deref()
+    }
+
+    /// Returns whether the processor with the given name is enabled.
+    ///
+    pub fn is_enabled(&self, name: &str) -> bool {
+        !self.disabled_processors.contains(name)
+    }
+
+    /// The maximum number of processors allowed to run at the same time, or [`None`] if unlimited.
+    ///
+    pub fn max_concurrency(&self) -> Option<NonZeroUsize> {
+        self.max_concurrency
+    }
+
+    /// The maximum size in bytes of a file to process, or [`None`] if unlimited.
+    ///
+    pub fn max_input_size(&self) -> Option<u64> {
+        self.max_input_size
+    }
+
+    /// The maximum time the processor with the given name is allowed to run, or [`None`] if unlimited.
+    ///
+    /// Falls back to the default timeout if no timeout was set for the processor specifically.
+    ///
+    pub fn timeout(&self, name: &str) -> Option<Duration> {
+        self.timeouts.get(name).copied().or(self.default_timeout)
+    }
+
+    /// Limits on extracting embedded files.
+    ///
+    pub fn limits(&self) -> &ExtractionLimits {
+        &self.limits
+    }
+
+    /// The format of page images.
+    ///
+    pub fn image_format(&self) -> ImageFormat {
+        self.image_format
+    }
+
+    /// The resolution of page images in dots per inch.
+    ///
+    pub fn image_resolution(&self) -> u32 {
+        self.image_resolution
+    }
+
+    /// The formats of the files created from the text recognized in images.
+    ///
+    pub fn ocr_formats(&self) -> &[OcrFormat] {
+        &self.ocr_formats
+    }
+
+    /// The languages to recognize in images, e.g. `eng+deu`, or [`None`] for tesseract's default.
+    ///
+    pub fn ocr_language(&self) -> Option<&str> {
+        self.ocr_language.as_deref()
+    }
+
+    /// The algorithms files are hashed with for [`crate::processing::ProcessType::Hashes`].
+    ///
+    pub fn hash_algorithms(&self) -> &[HashAlgorithm] {
+        &self.hash_algorithms
+    }
+
+    /// Whether the languages of extracted text are detected, creating a `language.json` along with the text.
+    ///
+    pub fn detect_language(&self) -> bool {
+        self.detect_language
+    }
+
+    /// The rules finding entities for [`crate::processing::ProcessType::Entities`], applied in order.
+    ///
+    pub fn entity_rules(&self) -> &[EntityRule] {
+        &self.entity_rules
+    }
+
+    /// The rules finding the entities redacted for [`crate::processing::ProcessType::Redaction`], applied in order.
+    ///
+    pub fn redaction_rules(&self) -> &[EntityRule] {
+        &self.redaction_rules
+    }
+
+    /// The width in columns text extracted from HTML is wrapped to.
+    ///
+    pub fn html_text_width(&self) -> usize {
+        self.html_text_width
+    }
+
+    /// The variant mbox files are read as, or [`None`] to detect the variant of each file from its content.
+    ///
+    pub fn mbox_variant(&self) -> Option<MboxVariant> {
+        self.mbox_variant
+    }
+
+    /// The Tika service processors use.
+    ///
+    pub fn tika(&self) -> &Tika {
+        &self.tika
+    }
+
+    /// Creates a temporary file in the configured temporary directory.
+    ///
+    pub fn temp_file(&self) -> io::Result<NamedTempFile> {
+        match &self.temp_dir {
+            Some(dir) => NamedTempFile::new_in(dir),
+            None => NamedTempFile::new(),
+        }
+    }
+
+    /// Creates a temporary directory in the configured temporary directory.
+    ///
+    pub fn temp_subdir(&self) -> io::Result<TempDir> {
+        match &self.temp_dir {
+            Some(dir) => TempDir::new_in(dir),
+            None => TempDir::new(),
+        }
+    }
+}
+
+/// Builder for [`Processor`].
+///
+#[derive(Debug, Clone, Default)]
+pub struct ProcessorBuilder {
+    config: ProcessorConfig,
+    registry: Option<ProcessorRegistry>,
+}
+
+impl ProcessorBuilder {
+    /// Creates a new builder using the default configuration and built-in processors.
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the registry to look up processors in.
+    ///
+    pub fn registry(mut self, registry: ProcessorRegistry) -> Self {
+        self.registry = Some(registry);
+        self
+    }
+
+    /// Set the directory to create temporary files in.
+    ///
+    pub fn temp_dir(mut self, temp_dir: impl Into<PathBuf>) -> Self {
+        self.config.temp_dir = Some(temp_dir.into());
+        self
+    }
+
+    /// Disables the processor with the given name, as returned by [`crate::processing::Process::name`].
+    ///
+    /// When a processor is disabled, the next processor registered for the same MIME type and process type is used.
+    ///
+    pub fn disable_processor(mut self, name: impl Into<String>) -> Self {
+        self.config.disabled_processors.insert(name.into());
+        self
+    }
+
+    /// Set the maximum number of processors allowed to run at the same time.
+    ///
+    pub fn max_concurrency(mut self, max_concurrency: NonZeroUsize) -> Self {
+        self.config.max_concurrency = Some(max_concurrency);
+        self
+    }
+
+    /// Set the maximum size in bytes of a file to process.
+    ///
+    pub fn max_input_size(mut self, max_input_size: u64) -> Self {
+        self.config.max_input_size = Some(max_input_size);
+        self
+    }
+
+    /// Set the maximum time each processor is allowed to run.
+    ///
+    pub fn default_timeout(mut self, timeout: Duration) -> Self {
+        self.config.default_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the maximum time the processor with the given name is allowed to run, overriding the default timeout.
+    ///
+    pub fn processor_timeout(mut self, name: impl Into<String>, timeout: Duration) -> Self {
+        self.config.timeouts.insert(name.into(), timeout);
+        self
+    }
+
+    /// Set all limits on extracting embedded files.
+    ///
+    pub fn limits(mut self, limits: ExtractionLimits) -> Self {
+        self.config.limits = limits;
+        self
+    }
+
+    /// Set the maximum depth of embedded files, where the root file has a depth of 0.
+    ///
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.config.limits.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Set the maximum number of files extracted from a single container.
+    ///
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.config.limits.max_entries = Some(max_entries);
+        self
+    }
+
+    /// Set the maximum number of uncompressed bytes extracted from a single container.
+    ///
+    pub fn max_total_bytes(mut self, max_total_bytes: u64) -> Self {
+        self.config.limits.max_total_bytes = Some(max_total_bytes);
+        self
+    }
+
+    /// Set the maximum ratio of uncompressed to compressed size of a single compressed entry.
+    ///
+    pub fn max_compression_ratio(mut self, max_compression_ratio: f64) -> Self {
+        self.config.limits.max_compression_ratio = Some(max_compression_ratio);
+        self
+    }
+
+    /// Set the format of page images.
+    ///
+    pub fn image_format(mut self, image_format: ImageFormat) -> Self {
+        self.config.image_format = image_format;
+        self
+    }
+
+    /// Set the resolution of page images in dots per inch.
+    ///
+    pub fn image_resolution(mut self, image_resolution: u32) -> Self {
+        self.config.image_resolution = image_resolution;
+        self
+    }
+
+    /// Set the formats of the files created from the text recognized in images.
+    ///
+    /// Defaults to plain text only.
+    ///
+    pub fn ocr_formats(mut self, ocr_formats: Vec<OcrFormat>) -> Self {
+        self.config.ocr_formats = ocr_formats;
+        self
+    }
+
+    /// Set the languages to recognize in images, e.g. `eng+deu`.
+    ///
+    pub fn ocr_language(mut self, ocr_language: impl Into<String>) -> Self {
+        self.config.ocr_language = Some(ocr_language.into());
+        self
+    }
+
+    /// Set the algorithms files are hashed with for [`crate::processing::ProcessType::Hashes`].
+    ///
+    /// Defaults to MD5, SHA-1 and SHA-256.
+    ///
+    pub fn hash_algorithms(mut self, hash_algorithms: Vec<HashAlgorithm>) -> Self {
+        self.config.hash_algorithms = hash_algorithms;
+        self
+    }
+
+    /// Set whether the languages of extracted text are detected, creating a `language.json` along with the text.
+    ///
+    pub fn detect_language(mut self, detect_language: bool) -> Self {
+        self.config.detect_language = detect_language;
+        self
+    }
+
+    /// Set the rules finding entities for [`crate::processing::ProcessType::Entities`], applied in order.
+    ///
+    /// Defaults to [`EntityRule::defaults`]. Matches overlapping an entity found by an earlier rule are discarded.
+    ///
+    pub fn entity_rules(mut self, entity_rules: Vec<EntityRule>) -> Self {
+        self.config.entity_rules = entity_rules;
+        self
+    }
+
+    /// Set the rules finding the entities redacted for [`crate::processing::ProcessType::Redaction`], applied in
+    /// order.
+    ///
+    /// Defaults to [`EntityRule::defaults`]. Use a subset of them to only redact some entity types, or custom rules to
+    /// redact other patterns.
+    ///
+    pub fn redaction_rules(mut self, redaction_rules: Vec<EntityRule>) -> Self {
+        self.config.redaction_rules = redaction_rules;
+        self
+    }
+
+    /// Set the width in columns text extracted from HTML is wrapped to.
+    ///
+    /// Tables wider than this are laid out with narrower columns, so the width should not be set too small.
+    ///
+    pub fn html_text_width(mut self, html_text_width: usize) -> Self {
+        self.config.html_text_width = html_text_width;
+        self
+    }
+
+    /// Set the variant to read mbox files as, instead of detecting the variant of each file from its content.
+    ///
+    /// Files of the `mboxo` variant are detected as `mboxrd`, which only reads them differently if they have lines
+    /// starting with `From ` quoted more than once.
+    ///
+    pub fn mbox_variant(mut self, mbox_variant: MboxVariant) -> Self {
+        self.config.mbox_variant = Some(mbox_variant);
+        self
+    }
+
+    /// Set the URL of the Tika server to use, e.g. `http://localhost:9998`.
+    ///
+    pub fn tika_url(mut self, tika_url: impl Into<String>) -> Self {
+        self.config.tika = Arc::new(Tika::new(tika_url));
+        self
+    }
+
+    /// Build the Processor.
+    ///
+    pub fn build(self) -> Processor {
+        Processor::with_config(self.config, self.registry.unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config() {
+        let config = ProcessorConfig::default();
+
+        assert_eq!(config.temp_dir(), None);
+        assert_eq!(config.max_concurrency(), None);
+        assert_eq!(config.max_input_size(), None);
+        assert_eq!(config.timeout("zip"), None);
+        assert_eq!(config.limits(), &ExtractionLimits::default());
+        assert_eq!(config.image_format(), ImageFormat::Png);
+        assert_eq!(config.image_resolution(), DEFAULT_IMAGE_RESOLUTION);
+        assert_eq!(config.ocr_formats(), &[OcrFormat::Text]);
+        assert_eq!(config.ocr_language(), None);
+        assert_eq!(config.hash_algorithms(), &[HashAlgorithm::Md5, HashAlgorithm::Sha1, HashAlgorithm::Sha256]);
+        assert!(!config.detect_language());
+        assert_eq!(config.entity_rules().len(), EntityRule::defaults().len());
+        assert_eq!(config.redaction_rules().len(), EntityRule::defaults().len());
+        assert_eq!(config.html_text_width(), DEFAULT_HTML_TEXT_WIDTH);
+        assert_eq!(config.mbox_variant(), None);
+        assert!(config.is_enabled("zip"));
+    }
+
+    #[test]
+    fn test_builder() {
+        let temp_dir = tempfile::tempdir().unwrap();
+
+        let processor = ProcessorBuilder::new()
+            .temp_dir(temp_dir.path())
+            .disable_processor("zip")
+            .max_concurrency(NonZeroUsize::new(4).unwrap())
+            .max_input_size(1024)
+            .default_timeout(Duration::from_secs(60))
+            .processor_timeout("RFC 822 PDF", Duration::from_secs(5))
+            .max_depth(3)
+            .max_entries(100)
+            .max_total_bytes(1 << 30)
+            .max_compression_ratio(50.0)
+            .image_format(ImageFormat::Jpeg)
+            .image_resolution(72)
+            .ocr_formats(vec![OcrFormat::Text, OcrFormat::Pdf])
+            .ocr_language("eng+deu")
+            .hash_algorithms(vec![HashAlgorithm::Blake3])
+            .detect_language(true)
+            .entity_rules(vec![EntityRule::new("case_number", r"CASE-\d+").unwrap()])
+            .redaction_rules(vec![EntityRule::new("name", r"Phillip Allen").unwrap()])
+            .html_text_width(72)
+            .mbox_variant(MboxVariant::Mboxcl2)
+            .tika_url("http://tika.internal:9998")
+            .build();
+
+        let config = processor.config();
+        assert_eq!(config.temp_dir(), Some(temp_dir.path()));
+        assert!(!config.is_enabled("zip"));
+        assert!(config.is_enabled("Default Text"));
+        assert_eq!(config.max_concurrency(), NonZeroUsize::new(4));
+        assert_eq!(config.max_input_size(), Some(1024));
+        assert_eq!(config.timeout("RFC 822 PDF"), Some(Duration::from_secs(5)));
+        assert_eq!(config.timeout("zip"), Some(Duration::from_secs(60)));
+        assert_eq!(config.limits(), &ExtractionLimits {
+            max_depth: Some(3),
+            max_entries: Some(100),
+            max_total_bytes: Some(1 << 30),
+            max_compression_ratio: Some(50.0),
+        });
+        assert_eq!(config.image_format(), ImageFormat::Jpeg);
+        assert_eq!(config.image_resolution(), 72);
+        assert_eq!(config.ocr_formats(), &[OcrFormat::Text, OcrFormat::Pdf]);
+        assert_eq!(config.ocr_language(), Some("eng+deu"));
+        assert_eq!(config.hash_algorithms(), &[HashAlgorithm::Blake3]);
+        assert!(config.detect_language());
+        assert_eq!(config.entity_rules().iter().map(EntityRule::entity_type).collect::<Vec<_>>(), vec!["case_number"]);
+        assert_eq!(config.redaction_rules().iter().map(EntityRule::entity_type).collect::<Vec<_>>(), vec!["name"]);
+        assert_eq!(config.html_text_width(), 72);
+        assert_eq!(config.mbox_variant(), Some(MboxVariant::Mboxcl2));
+        assert_eq!(config.tika().tika_url(), "http://tika.internal:9998");
+
+        let file = config.temp_file().unwrap();
+        assert_eq!(file.path().parent(), Some(temp_dir.path()));
+        let dir = config.temp_subdir().unwrap();
+        assert_eq!(dir.path().parent(), Some(temp_dir.path()));
+    }
+}
@@ -0,0 +1,307 @@
+use std::fmt;
+use std::fmt::Formatter;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+use zip::result::ZipError;
+
+use services::{CommandError, ServiceError, ServiceErrorKind};
+
+use crate::processing::ProcessContext;
+
+/// The class of failure of processing a file.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ProcessErrorKind {
+    /// The file is malformed and could not be parsed.
+    ///
+    Corrupt,
+
+    /// The file is encrypted and cannot be read without a password.
+    ///
+    Encrypted,
+
+    /// The file, or a feature it uses, is not supported.
+    ///
+    Unsupported,
+
+    /// A service required for processing could not be reached or is temporarily unable to handle requests.
+    ///
+    ServiceUnavailable,
+
+    /// The file exceeds a configured limit.
+    ///
+    LimitExceeded,
+
+    /// A processor did not finish within its timeout or the deadline of the processing operation.
+    ///
+    Timeout,
+
+    /// The processing operation was cancelled.
+    ///
+    Cancelled,
+
+    /// Reading or writing a file failed.
+    ///
+    Io,
+
+    /// Any other failure.
+    ///
+    Internal,
+}
+
+impl ProcessErrorKind {
+    /// Returns whether processing the file again may succeed.
+    ///
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, ProcessErrorKind::ServiceUnavailable | ProcessErrorKind::Timeout | ProcessErrorKind::Io)
+    }
+
+    /// Classifies an error by the first error with a known class in its chain of sources.
+    ///
+    pub fn classify(err: &anyhow::Error) -> Self {
+        err.chain()
+            .find_map(|err| {
+                if let Some(err) = err.downcast_ref::<ProcessError>() {
+                    Some(err.kind)
+                } else if let Some(err) = err.downcast_ref::<ServiceError>() {
+                    Some(ProcessErrorKind::from(err.kind))
+                } else if let Some(err) = err.downcast_ref::<CommandError>() {
+                    err.exit_status.is_none().then_some(ProcessErrorKind::ServiceUnavailable)
+                } else if let Some(err) = err.downcast_ref::<ZipError>() {
+                    Some(ProcessErrorKind::from(err))
+                } else {
+                    err.downcast_ref::<io::Error>().map(ProcessErrorKind::from)
+                }
+            })
+            .unwrap_or(ProcessErrorKind::Internal)
+    }
+}
+
+impl fmt::Display for ProcessErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessErrorKind::Corrupt => write!(f, "corrupt file"),
+            ProcessErrorKind::Encrypted => write!(f, "encrypted file"),
+            ProcessErrorKind::Unsupported => write!(f, "unsupported file"),
+            ProcessErrorKind::ServiceUnavailable => write!(f, "service unavailable"),
+            ProcessErrorKind::LimitExceeded => write!(f, "limit exceeded"),
+            ProcessErrorKind::Timeout => write!(f, "timed out"),
+            ProcessErrorKind::Cancelled => write!(f, "cancelled"),
+            ProcessErrorKind::Io => write!(f, "I/O error"),
+            ProcessErrorKind::Internal => write!(f, "internal error"),
+        }
+    }
+}
+
+impl From<ServiceErrorKind> for ProcessErrorKind {
+    fn from(kind: ServiceErrorKind) -> Self {
+        match kind {
+            ServiceErrorKind::Unavailable => ProcessErrorKind::ServiceUnavailable,
+            ServiceErrorKind::Unsupported => ProcessErrorKind::Unsupported,
+            ServiceErrorKind::Encrypted => ProcessErrorKind::Encrypted,
+            ServiceErrorKind::Unprocessable => ProcessErrorKind::Corrupt,
+            ServiceErrorKind::Failed => ProcessErrorKind::Internal,
+        }
+    }
+}
+
+impl From<&ZipError> for ProcessErrorKind {
+    fn from(err: &ZipError) -> Self {
+        match err {
+            ZipError::Io(err) => ProcessErrorKind::from(err),
+            ZipError::InvalidArchive(_) | ZipError::FileNotFound => ProcessErrorKind::Corrupt,
+            ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED) => ProcessErrorKind::Encrypted,
+            ZipError::UnsupportedArchive(_) => ProcessErrorKind::Unsupported,
+        }
+    }
+}
+
+impl From<&io::Error> for ProcessErrorKind {
+    fn from(err: &io::Error) -> Self {
+        match err.kind() {
+            io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof => ProcessErrorKind::Corrupt,
+            _ => ProcessErrorKind::Io,
+        }
+    }
+}
+
+/// Error type for when processing a file fails.
+///
+/// Besides the class of failure, the error records where it happened: the processor that failed, and the ID chain and
+/// MIME type of the file being processed. These are filled in when the error is sent through a [`ProcessContext`] or
+/// returned from a [`crate::processing::Processor`].
+///
+#[derive(Debug)]
+pub struct ProcessError {
+    /// The class of failure.
+    ///
+    pub kind: ProcessErrorKind,
+
+    /// The name of the processor that failed, if the error happened within a processor.
+    ///
+    pub processor: Option<String>,
+
+    /// The ID chain of the file being processed.
+    ///
+    /// See `ProcessState.id_chain` for more information.
+    ///
+    pub id_chain: Vec<String>,
+
+    /// The MIME type of the file being processed.
+    ///
+    pub mimetype: String,
+
+    inner: anyhow::Error,
+}
+
+impl ProcessError {
+    /// Create a new [`ProcessError`] of the given kind.
+    ///
+    pub fn new(kind: ProcessErrorKind, err: impl Into<anyhow::Error>) -> Self {
+        Self {
+            kind,
+            processor: None,
+            id_chain: vec![],
+            mimetype: String::new(),
+            inner: err.into(),
+        }
+    }
+
+    /// Create a new [`ProcessError`] for a cancelled processing operation.
+    ///
+    pub fn cancelled() -> Self {
+        Self::new(ProcessErrorKind::Cancelled, anyhow::anyhow!("processing was cancelled"))
+    }
+
+    /// Wraps the underlying error with additional context, keeping the kind of failure.
+    ///
+    pub fn context<C>(self, context: C) -> Self
+    where
+        C: fmt::Display + Send + Sync + 'static,
+    {
+        Self {
+            inner: self.inner.context(context),
+            ..self
+        }
+    }
+
+    /// Sets the ID chain and MIME type of the file being processed, unless they are already set.
+    ///
+    pub fn with_context(mut self, ctx: &ProcessContext) -> Self {
+        if self.mimetype.is_empty() {
+            self.id_chain = ctx.state.id_chain.clone();
+            self.mimetype = ctx.mimetype.clone();
+        }
+        self
+    }
+
+    /// Sets the name of the processor that failed, unless it is already set.
+    ///
+    pub fn with_processor(mut self, processor: impl Into<String>) -> Self {
+        if self.processor.is_none() {
+            self.processor = Some(processor.into());
+        }
+        self
+    }
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(processor) = &self.processor {
+            write!(f, " in processor '{}'", processor)?;
+        }
+        if !self.mimetype.is_empty() {
+            write!(f, " processing {}", self.mimetype)?;
+        }
+        if !self.id_chain.is_empty() {
+            write!(f, " at {}", self.id_chain.join("/"))?;
+        }
+        write!(f, ": {}", self.inner)
+    }
+}
+
+impl std::error::Error for ProcessError {
+    /// The underlying error is part of the message, so the source continues from its source instead.
+    ///
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.inner.source()
+    }
+}
+
+impl From<anyhow::Error> for ProcessError {
+    fn from(err: anyhow::Error) -> Self {
+        match err.downcast::<ProcessError>() {
+            Ok(err) => err,
+            Err(err) => Self::new(ProcessErrorKind::classify(&err), err),
+        }
+    }
+}
+
+impl From<ServiceError> for ProcessError {
+    fn from(err: ServiceError) -> Self {
+        Self::new(ProcessErrorKind::from(err.kind), err)
+    }
+}
+
+impl From<io::Error> for ProcessError {
+    fn from(err: io::Error) -> Self {
+        Self::new(ProcessErrorKind::from(&err), err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::{anyhow, Context};
+
+    use crate::processing::ProcessContextBuilder;
+
+    use super::*;
+
+    #[test]
+    fn test_classify() {
+        let cases = vec![
+            (anyhow!("unknown"), ProcessErrorKind::Internal),
+            (anyhow::Error::new(io::Error::from(io::ErrorKind::NotFound)), ProcessErrorKind::Io),
+            (anyhow::Error::new(io::Error::from(io::ErrorKind::InvalidData)), ProcessErrorKind::Corrupt),
+            (anyhow::Error::new(ZipError::InvalidArchive("Invalid zip header")), ProcessErrorKind::Corrupt),
+            (anyhow::Error::new(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED)), ProcessErrorKind::Encrypted),
+            (anyhow::Error::new(ServiceError::new(ServiceErrorKind::Unavailable, anyhow!("down"))), ProcessErrorKind::ServiceUnavailable),
+            (anyhow::Error::new(ProcessError::cancelled()), ProcessErrorKind::Cancelled),
+        ];
+
+        for (err, expected) in cases {
+            let err = err.context("failed to process");
+            assert_eq!(ProcessErrorKind::classify(&err), expected, "{:?}", err);
+        }
+    }
+
+    #[test]
+    fn test_from_anyhow_keeps_process_error() {
+        let err = ProcessError::new(ProcessErrorKind::Unsupported, anyhow!("no parser")).with_processor("zip");
+
+        let err = ProcessError::from(anyhow::Error::new(err));
+
+        assert_eq!(err.kind, ProcessErrorKind::Unsupported);
+        assert_eq!(err.processor.as_deref(), Some("zip"));
+    }
+
+    #[test]
+    fn test_display() {
+        let (output_sink, _) = tokio::sync::mpsc::channel(1);
+        let ctx = ProcessContextBuilder::new("application/zip", vec![], output_sink)
+            .id_chain(vec!["a".to_string(), "b".to_string()])
+            .build();
+
+        let err = Err::<(), _>(io::Error::from(io::ErrorKind::InvalidData))
+            .context("failed to read zip entry")
+            .map_err(ProcessError::from)
+            .unwrap_err()
+            .with_context(&ctx)
+            .with_processor("zip");
+
+        assert_eq!(err.to_string(), "corrupt file in processor 'zip' processing application/zip at a/b: failed to read zip entry");
+        assert_eq!(std::error::Error::source(&err).map(|err| err.to_string()), Some("invalid data".to_string()));
+    }
+}
@@ -1,12 +1,41 @@
+use std::fmt;
+use std::fmt::Formatter;
+use std::io;
+use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::anyhow;
+use log::warn;
 use serde::{Deserialize, Serialize};
+pub use identify::hashing::HashAlgorithm;
+pub use identify::mimetype::IdentificationMethod;
+pub use services::{ImageFormat, OcrFormat};
+use tempfile::{NamedTempFile, TempDir, TempPath};
 use tokio::sync::mpsc::Sender;
+use tokio::time::Instant;
+pub use tokio_util::sync::CancellationToken;
 
+pub use crate::entities::EntityRule;
+
+pub use self::config::*;
+pub use self::error::*;
 pub use self::processor::*;
+pub use self::progress::*;
+pub use self::registry::*;
+pub use self::report::*;
+pub(crate) use self::shared_pdf::*;
+pub use self::stream::*;
 
+mod config;
+mod error;
 mod processor;
+mod progress;
+mod registry;
+mod report;
+mod shared_pdf;
+mod stream;
 
 /// The type of metadata.json to produce from processing.
 ///
@@ -24,6 +53,33 @@ pub enum ProcessType {
     ///
     Pdf,
 
+    /// Images of each page of a file, rendered from the file itself if it is a PDF, or from its rendered PDF.
+    ///
+    Image,
+
+    /// Text recognized in images of a file, rendered from its pages if it is a PDF.
+    ///
+    Ocr,
+
+    /// Hashes of a file with multiple algorithms.
+    ///
+    Hashes,
+
+    /// Sensitive entities found in a file, such as email addresses and credit card numbers.
+    ///
+    Entities,
+
+    /// A copy of the extracted text of a file with sensitive entities replaced by placeholders, and a log of what was
+    /// replaced.
+    ///
+    Redaction,
+
+    /// Conversation threads of the messages in a file, such as an mbox file.
+    ///
+    /// When processing recursively, the threads of all messages in the tree are also created for the root file.
+    ///
+    Threads,
+
     /// Files embedded in the original.
     ///
     Embedded
@@ -37,6 +93,12 @@ impl ProcessType {
             ProcessType::Text,
             ProcessType::Metadata,
             ProcessType::Pdf,
+            ProcessType::Image,
+            ProcessType::Ocr,
+            ProcessType::Hashes,
+            ProcessType::Entities,
+            ProcessType::Redaction,
+            ProcessType::Threads,
             ProcessType::Embedded,
         ]
     }
@@ -50,12 +112,114 @@ impl FromStr for ProcessType {
             "text" => Ok(ProcessType::Text),
             "metadata" => Ok(ProcessType::Metadata),
             "pdf" => Ok(ProcessType::Pdf),
+            "image" => Ok(ProcessType::Image),
+            "ocr" => Ok(ProcessType::Ocr),
+            "hashes" => Ok(ProcessType::Hashes),
+            "entities" => Ok(ProcessType::Entities),
+            "redaction" => Ok(ProcessType::Redaction),
+            "threads" => Ok(ProcessType::Threads),
             "embedded" => Ok(ProcessType::Embedded),
             _ => Err(format!("Can not convert {} to OutputType", s)),
         }
     }
 }
 
+/// The kind of file a processing output is, to route outputs without matching on their names.
+///
+/// Processed files have the kind of the [`ProcessType`] of the processor that created them.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum OutputKind {
+    /// Extracted text of a file.
+    ///
+    Text,
+
+    /// Metadata of a file.
+    ///
+    Metadata,
+
+    /// A rendered version of a file as a PDF.
+    ///
+    Pdf,
+
+    /// An image of a page of a file.
+    ///
+    Image,
+
+    /// Text recognized in images of a file, or a searchable PDF of them.
+    ///
+    Ocr,
+
+    /// Hashes of a file.
+    ///
+    Hashes,
+
+    /// The languages detected in the extracted text of a file, created along with the text.
+    ///
+    Language,
+
+    /// Sensitive entities found in a file.
+    ///
+    Entities,
+
+    /// Redacted text of a file, a log of the redactions, or a redacted rendering of a file.
+    ///
+    Redaction,
+
+    /// Conversation threads of messages.
+    ///
+    Threads,
+
+    /// A file embedded in the original.
+    ///
+    Embedded,
+
+    /// The report of processing a file, see [`ProcessOutput::Report`].
+    ///
+    Report,
+
+    /// A file created outside of a processor running for a process type, e.g. by calling a processor directly.
+    ///
+    Other,
+}
+
+impl OutputKind {
+    /// Returns the process type producing files of this kind, if any.
+    ///
+    pub fn process_type(&self) -> Option<ProcessType> {
+        match self {
+            OutputKind::Text | OutputKind::Language => Some(ProcessType::Text),
+            OutputKind::Metadata => Some(ProcessType::Metadata),
+            OutputKind::Pdf => Some(ProcessType::Pdf),
+            OutputKind::Image => Some(ProcessType::Image),
+            OutputKind::Ocr => Some(ProcessType::Ocr),
+            OutputKind::Hashes => Some(ProcessType::Hashes),
+            OutputKind::Entities => Some(ProcessType::Entities),
+            OutputKind::Redaction => Some(ProcessType::Redaction),
+            OutputKind::Threads => Some(ProcessType::Threads),
+            OutputKind::Embedded => Some(ProcessType::Embedded),
+            OutputKind::Report | OutputKind::Other => None,
+        }
+    }
+}
+
+impl From<&ProcessType> for OutputKind {
+    fn from(process_type: &ProcessType) -> Self {
+        match process_type {
+            ProcessType::Text => OutputKind::Text,
+            ProcessType::Metadata => OutputKind::Metadata,
+            ProcessType::Pdf => OutputKind::Pdf,
+            ProcessType::Image => OutputKind::Image,
+            ProcessType::Ocr => OutputKind::Ocr,
+            ProcessType::Hashes => OutputKind::Hashes,
+            ProcessType::Entities => OutputKind::Entities,
+            ProcessType::Redaction => OutputKind::Redaction,
+            ProcessType::Threads => OutputKind::Threads,
+            ProcessType::Embedded => OutputKind::Embedded,
+        }
+    }
+}
+
 /// Represents the state of a processing operation.
 ///
 /// This is built and modified during processing and is provided with the final processing metadata.json.
@@ -68,6 +232,59 @@ pub struct ProcessState {
     /// a root file. This structure is a tree, where the embedded files are branches and the processed files are leaves.
     ///
     pub id_chain: Vec<String>,
+
+    /// How the MIME type of the current file was detected, or [`None`] if the given MIME type was used.
+    ///
+    #[serde(default)]
+    pub detected_mimetype: Option<DetectedMimetype>,
+
+    /// The envelope of the current file if it is a message read from an mbox file.
+    ///
+    #[serde(default)]
+    pub envelope: Option<Box<MboxEnvelope>>,
+}
+
+/// A MIME type detected from the content of a file, rather than given by the caller or its container.
+///
+#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
+pub struct DetectedMimetype {
+    /// The detected MIME type.
+    ///
+    pub mimetype: String,
+
+    /// The method the MIME type was detected with.
+    ///
+    pub method: IdentificationMethod,
+}
+
+/// The envelope of a message in an mbox file, taken from the `From ` line preceding it.
+///
+/// The line is not part of the message itself, so it is not included in the file embedded for the message.
+///
+#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
+pub struct MboxEnvelope {
+    /// The envelope sender of the message, e.g. `phillip.allen@enron.com` or `MAILER-DAEMON`.
+    ///
+    pub sender: String,
+
+    /// The date the message was delivered in RFC 3339 format, if the line has a valid one, e.g.
+    /// `2005-08-08T05:34:13Z`.
+    ///
+    /// The date of the line has no time zone, so it is assumed to be in UTC.
+    ///
+    pub date: Option<String>,
+
+    /// The byte offset of the `From ` line in the mbox file.
+    ///
+    pub offset: u64,
+}
+
+impl ProcessState {
+    /// Returns how deeply the current file is embedded in the root file, where the root file has a depth of 0.
+    ///
+    pub fn depth(&self) -> usize {
+        self.id_chain.len()
+    }
 }
 
 /// Defines the context for a processing operation.
@@ -89,7 +306,16 @@ pub struct ProcessContext {
     ///
     pub state: ProcessState,
 
-    output_sink: Sender<anyhow::Result<ProcessOutput>>,
+    output_sink: Sender<Result<ProcessOutput, ProcessError>>,
+    config: Arc<ProcessorConfig>,
+    cancellation: CancellationToken,
+    deadline: Option<Instant>,
+    processor: Option<&'static str>,
+    process_type: Option<ProcessType>,
+    fallback_level: usize,
+    recorder: Option<Arc<OutputRecorder>>,
+    shared_pdf: Option<Arc<SharedPdf>>,
+    progress: bool,
 }
 
 impl ProcessContext {
@@ -103,14 +329,247 @@ impl ProcessContext {
             types: self.types.clone(),
             output_sink: self.output_sink.clone(),
             state: self.state.clone(),
+            config: self.config.clone(),
+            cancellation: self.cancellation.clone(),
+            deadline: self.deadline,
+            processor: self.processor,
+            process_type: self.process_type.clone(),
+            fallback_level: self.fallback_level,
+            recorder: self.recorder.clone(),
+            shared_pdf: None,
+            progress: self.progress,
+        }
+    }
+
+    /// Creates a new ProcessContext for processing a file embedded in the current one.
+    ///
+    /// The child has its own cancellation token, which is cancelled when the current one is, but cancelling the child
+    /// does not affect the current context.
+    ///
+    pub fn child(&self) -> Self {
+        Self {
+            state: ProcessState {
+                detected_mimetype: None,
+                envelope: None,
+                ..self.state.clone()
+            },
+            cancellation: self.cancellation.child_token(),
+            processor: None,
+            process_type: None,
+            fallback_level: 0,
+            recorder: None,
+            shared_pdf: None,
+            ..self.clone()
         }
     }
 
+    /// Returns the token used to cancel the processing operation.
+    ///
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancellation
+    }
+
+    /// Returns whether the processing operation was cancelled or its deadline has passed.
+    ///
+    /// Long-running processors should check this periodically and stop early when it returns `true`.
+    ///
+    pub fn is_cancelled(&self) -> bool {
+        self.cancellation.is_cancelled() || self.deadline.is_some_and(|deadline| deadline <= Instant::now())
+    }
+
+    /// Returns the instant the processing operation must complete by, if any.
+    ///
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Returns the configuration of the processor running the processing operation.
+    ///
+    pub fn config(&self) -> &ProcessorConfig {
+        &self.config
+    }
+
+    /// Returns the position of the processor running with this context in the fallback chain of its process type.
+    ///
+    /// The processor with the highest precedence has a level of 0. Each fallback running because the previous processor
+    /// failed has a level one higher.
+    ///
+    pub fn fallback_level(&self) -> usize {
+        self.fallback_level
+    }
+
+    /// Creates a temporary file in the temporary directory configured for the processing operation.
+    ///
+    pub fn temp_file(&self) -> io::Result<NamedTempFile> {
+        self.config.temp_file()
+    }
+
+    /// Creates a temporary directory in the temporary directory configured for the processing operation.
+    ///
+    pub fn temp_subdir(&self) -> io::Result<TempDir> {
+        self.config.temp_subdir()
+    }
+
+    /// Replaces the configuration with the one of the processor running the processing operation.
+    ///
+    pub(crate) fn with_config(mut self, config: Arc<ProcessorConfig>) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sets the name of the processor running with this context.
+    ///
+    pub(crate) fn with_processor(mut self, processor: &'static str) -> Self {
+        self.processor = Some(processor);
+        self
+    }
+
+    /// Sets the process type the processor running with this context creates files for.
+    ///
+    pub(crate) fn with_process_type(mut self, process_type: ProcessType) -> Self {
+        self.process_type = Some(process_type);
+        self
+    }
+
+    /// Sets the position of the processor running with this context in the fallback chain of its process type.
+    ///
+    pub(crate) fn with_fallback_level(mut self, fallback_level: usize) -> Self {
+        self.fallback_level = fallback_level;
+        self
+    }
+
+    /// Shares the PDF rendered for the file between the processors of the PDF and the image type, see [`SharedPdf`].
+    ///
+    pub(crate) fn with_shared_pdf(mut self, shared_pdf: Option<Arc<SharedPdf>>) -> Self {
+        self.shared_pdf = shared_pdf;
+        self
+    }
+
+    /// Waits for the PDF rendered for the file by the processors of the [`ProcessType::Pdf`], if they were requested.
+    ///
+    /// Returns [`None`] if they were not requested, or did not render a PDF.
+    ///
+    pub(crate) async fn rendered_pdf(&self) -> Option<Arc<TempPath>> {
+        self.shared_pdf.as_ref()?.receive().await
+    }
+
+    /// Runs another processor as part of the one running with this context, returning the outputs of the other
+    /// processor instead of sending them.
+    ///
+    /// Fails with the first error the other processor sent, if any. The other processor must not create embedded
+    /// files, as their contexts would keep the channel its outputs are collected from open.
+    ///
+    pub(crate) async fn run_nested(
+        &self,
+        processor: &dyn Process,
+        input_path: &Path,
+        checksum: &str,
+    ) -> Result<Vec<ProcessOutput>, ProcessError> {
+        let output_path = self.temp_file()?.into_temp_path();
+        let (output_sink, mut outputs) = tokio::sync::mpsc::channel(10);
+        let nested = Self {
+            output_sink,
+            recorder: None,
+            ..self.clone()
+        };
+
+        let processing = processor.process(nested, input_path, output_path, checksum);
+        let collecting = async {
+            let mut collected = vec![];
+            while let Some(output) = outputs.recv().await {
+                collected.push(output);
+            }
+            collected
+        };
+        let (result, collected) = tokio::join!(processing, collecting);
+        result?;
+        collected.into_iter().collect()
+    }
+
+    /// Replaces the channel outputs are sent through.
+    ///
+    pub(crate) fn with_output_sink(mut self, output_sink: Sender<Result<ProcessOutput, ProcessError>>) -> Self {
+        self.output_sink = output_sink;
+        self
+    }
+
+    /// Records the outputs added through this context for the report of the processor running with it.
+    ///
+    pub(crate) fn with_recorder(mut self, recorder: Arc<OutputRecorder>) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
     /// Adds an metadata.json to be sent through the metadata.json transfer channel created by the caller of the processing operation.
     ///
-    pub async fn add_output(&self, result: anyhow::Result<ProcessOutput>) -> anyhow::Result<()> {
+    /// The content checksum of created and embedded files is calculated if it is not known yet. Errors are sent with
+    /// the ID chain and MIME type of this context, and the name of the processor running with it.
+    /// Errors of a processor with a fallback are held back until it is known whether the fallback runs.
+    ///
+    pub async fn add_output(&self, result: Result<ProcessOutput, ProcessError>) -> Result<(), ProcessError> {
+        let result = match result {
+            Ok(output) => Ok(output.with_calculated_content_checksum().await),
+            Err(err) => Err(self.error(err)),
+        };
+        if let (Some(shared_pdf), Ok(ProcessOutput::Processed(_, data))) = (&self.shared_pdf, &result) {
+            if data.mimetype == "application/pdf" && shared_pdf.is_waiting() {
+                self.share_pdf(shared_pdf, &data.path).await;
+            }
+        }
+        if let Some(recorder) = &self.recorder {
+            recorder.record(&result);
+            if recorder.defers_errors() {
+                if let Err(err) = result {
+                    recorder.defer(err);
+                    return Ok(());
+                }
+            }
+        }
         self.output_sink.send(result).await
-            .map_err(|e| anyhow!(e))
+            .map_err(|_| ProcessError::new(ProcessErrorKind::Cancelled, anyhow!("output receiver was dropped")))
+    }
+
+    /// Shares a copy of the PDF, as the PDF itself belongs to the receiver of the outputs.
+    ///
+    async fn share_pdf(&self, shared_pdf: &SharedPdf, path: &Path) {
+        let copy = match self.temp_file() {
+            Ok(file) => file.into_temp_path(),
+            Err(err) => {
+                warn!("Failed to create temporary file to share rendered PDF: {}", err);
+                return;
+            }
+        };
+        match tokio::fs::copy(path, &copy).await {
+            Ok(_) => shared_pdf.send(copy),
+            Err(err) => warn!("Failed to copy rendered PDF to share it: {}", err),
+        }
+    }
+
+    /// Sends the progress of the processor running with this context, if progress was requested.
+    ///
+    /// Progress is not recorded in the report of the processor.
+    ///
+    pub async fn progress(&self, progress: Progress) -> Result<(), ProcessError> {
+        if !self.progress {
+            return Ok(());
+        }
+
+        let progress = Progress {
+            processor: self.processor.map(str::to_string),
+            ..progress
+        };
+        self.output_sink.send(Ok(ProcessOutput::Progress(self.state.clone(), progress))).await
+            .map_err(|_| ProcessError::new(ProcessErrorKind::Cancelled, anyhow!("output receiver was dropped")))
+    }
+
+    /// Fills in where the error happened from this context.
+    ///
+    pub fn error(&self, err: impl Into<ProcessError>) -> ProcessError {
+        let err = err.into().with_context(self);
+        match self.processor {
+            Some(processor) => err.with_processor(processor),
+            None => err,
+        }
     }
 
     /// Returns the current ID chain.
@@ -128,8 +587,12 @@ impl ProcessContext {
 pub struct ProcessContextBuilder {
     mimetype: String,
     types: Vec<ProcessType>,
-    output_sink: Sender<anyhow::Result<ProcessOutput>>,
+    output_sink: Sender<Result<ProcessOutput, ProcessError>>,
     state: ProcessState,
+    config: Arc<ProcessorConfig>,
+    cancellation: CancellationToken,
+    deadline: Option<Instant>,
+    progress: bool,
 }
 
 impl ProcessContextBuilder {
@@ -144,7 +607,7 @@ impl ProcessContextBuilder {
     pub fn new(
         mimetype: impl Into<String>,
         types: Vec<ProcessType>,
-        output_sink: Sender<anyhow::Result<ProcessOutput>>,
+        output_sink: Sender<Result<ProcessOutput, ProcessError>>,
     ) -> Self {
         ProcessContextBuilder {
             mimetype: mimetype.into(),
@@ -152,7 +615,13 @@ impl ProcessContextBuilder {
             output_sink,
             state: ProcessState {
                 id_chain: Vec::new(),
-            }
+                detected_mimetype: None,
+                envelope: None,
+            },
+            config: default_config(),
+            cancellation: CancellationToken::new(),
+            deadline: None,
+            progress: false,
         }
     }
 
@@ -179,6 +648,35 @@ impl ProcessContextBuilder {
         self
     }
 
+    /// Sets the token used to cancel the processing operation.
+    ///
+    pub fn cancellation_token(mut self, cancellation: CancellationToken) -> Self {
+        self.cancellation = cancellation;
+        self
+    }
+
+    /// Sets the instant the processing operation must complete by.
+    ///
+    /// Processors still running when the deadline passes are stopped and report a timeout.
+    ///
+    pub fn deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Sets the deadline of the processing operation to the given duration from now.
+    ///
+    pub fn timeout(self, timeout: Duration) -> Self {
+        self.deadline(Instant::now() + timeout)
+    }
+
+    /// Sets whether to send [`ProcessOutput::Progress`] outputs while processing.
+    ///
+    pub fn progress(mut self, progress: bool) -> Self {
+        self.progress = progress;
+        self
+    }
+
     /// Build the ProcessContext.
     ///
     pub fn build(self) -> ProcessContext {
@@ -187,6 +685,15 @@ impl ProcessContextBuilder {
             types: self.types,
             output_sink: self.output_sink,
             state: self.state,
+            config: self.config,
+            cancellation: self.cancellation,
+            deadline: self.deadline,
+            processor: None,
+            process_type: None,
+            fallback_level: 0,
+            recorder: None,
+            shared_pdf: None,
+            progress: self.progress,
         }
     }
 }
@@ -198,6 +705,10 @@ impl From<ProcessContext> for ProcessContextBuilder {
             types: context.types,
             output_sink: context.output_sink,
             state: context.state,
+            config: context.config,
+            cancellation: context.cancellation,
+            deadline: context.deadline,
+            progress: context.progress,
         }
     }
 }
@@ -214,7 +725,74 @@ pub enum ProcessOutput {
 
     /// A file discovered during the processing of the original file.
     ///
-    Embedded(ProcessState, ProcessOutputData, Sender<anyhow::Result<ProcessOutput>>),
+    /// The context is a child of the one the embedded file was discovered with, and can be used to process the
+    /// embedded file as part of the same processing operation.
+    ///
+    Embedded(ProcessState, ProcessOutputData, ProcessContext),
+
+    /// A file that was not extracted from the original file because it exceeded a configured limit.
+    ///
+    Skipped(ProcessState, SkippedOutputData),
+
+    /// The `report.json` of processing the original file, sent once all processors finished.
+    ///
+    /// The file contains a [`ProcessReport`].
+    ///
+    Report(ProcessState, ProcessOutputData),
+
+    /// The progress of a processor, only sent if requested with [`ProcessContextBuilder::progress`].
+    ///
+    Progress(ProcessState, Progress),
+}
+
+/// Data associated with an embedded file that was skipped.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkippedOutputData {
+    /// The name of the skipped file, or [`None`] if all embedded files of the original file were skipped.
+    ///
+    pub name: Option<String>,
+
+    /// Why the file was skipped.
+    ///
+    pub reason: SkipReason,
+}
+
+/// The reason an embedded file was skipped.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SkipReason {
+    /// Extracting the file would exceed the maximum recursion depth.
+    ///
+    MaxDepth(usize),
+
+    /// The container has more entries than the maximum allowed.
+    ///
+    MaxEntries(usize),
+
+    /// Extracting the file would exceed the maximum number of bytes extracted from the container.
+    ///
+    MaxTotalBytes(u64),
+
+    /// The compression ratio of the file exceeds the maximum allowed.
+    ///
+    MaxCompressionRatio(f64),
+
+    /// A file with the same checksum was already processed in the same tree of files.
+    ///
+    Duplicate(String),
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::MaxDepth(max) => write!(f, "exceeds maximum depth of {}", max),
+            SkipReason::MaxEntries(max) => write!(f, "exceeds maximum of {} entries", max),
+            SkipReason::MaxTotalBytes(max) => write!(f, "exceeds maximum of {} total bytes", max),
+            SkipReason::MaxCompressionRatio(max) => write!(f, "exceeds maximum compression ratio of {}", max),
+            SkipReason::Duplicate(checksum) => write!(f, "duplicate of {}", checksum),
+        }
+    }
 }
 
 /// Data associated with the file created.
@@ -235,18 +813,38 @@ pub struct ProcessOutputData {
     ///
     pub mimetype: String,
 
+    /// The kind of file.
+    ///
+    pub kind: OutputKind,
+
     /// The types of metadata.json generated.
     ///
     pub types: Vec<ProcessType>,
 
     /// Deduplication ID of the metadata.json file.
     ///
+    /// For processed files and reports, this is the checksum of the original file they were created from.
+    ///
     pub checksum: String,
+
+    /// The MD5 checksum of the content of the file itself, or [`None`] if it could not be read.
+    ///
+    /// Processors knowing the checksum set it with [`ProcessOutput::with_content_checksum`], otherwise it is calculated
+    /// when the output is added with [`ProcessContext::add_output`].
+    ///
+    pub content_checksum: Option<String>,
+
+    /// The position in the fallback chain of the processor that created the file, where 0 is the processor with the
+    /// highest precedence.
+    ///
+    pub fallback_level: usize,
 }
 
 impl ProcessOutput {
     /// Creates a new ProcessOutput representing a newly created file.
     ///
+    /// The kind of the file is the process type of the processor running with the context.
+    ///
     /// # Arguments
     ///
     /// * `ctx` - The ProcessContext of the processing operation.
@@ -261,18 +859,33 @@ impl ProcessOutput {
         mimetype: impl Into<String>,
         checksum: impl Into<String>,
     ) -> Self {
+        let kind = ctx.process_type.as_ref().map_or(OutputKind::Other, OutputKind::from);
         Self::Processed(
             ctx.state.clone(),
             ProcessOutputData {
                 name: name.into(),
+                content_checksum: None,
                 path,
                 mimetype: mimetype.into(),
+                kind,
                 types: ctx.types.clone(),
                 checksum: checksum.into(),
+                fallback_level: ctx.fallback_level,
             }
         )
     }
 
+    /// Sets the kind of a processed file, for processors creating files of a different kind than their process type.
+    ///
+    /// Other outputs are returned unchanged.
+    ///
+    pub fn with_kind(self, kind: OutputKind) -> Self {
+        match self {
+            Self::Processed(state, data) => Self::Processed(state, ProcessOutputData { kind, ..data }),
+            output => output,
+        }
+    }
+
     /// Creates a new ProcessOutput representing an embedded file.
     ///
     /// # Arguments
@@ -293,12 +906,132 @@ impl ProcessOutput {
             ctx.state.clone(),
             ProcessOutputData {
                 name: name.into(),
+                content_checksum: None,
                 path,
                 mimetype: mimetype.into(),
+                kind: OutputKind::Embedded,
                 types: ctx.types.clone(),
                 checksum: checksum.into(),
+                fallback_level: ctx.fallback_level,
             },
-            ctx.output_sink.clone(),
+            ctx.child(),
+        )
+    }
+
+    /// Records that the MIME type of an embedded file was detected from its content.
+    ///
+    /// The detection is set on the state of the context to process the embedded file with. Other outputs are
+    /// returned unchanged.
+    ///
+    pub fn with_detected_mimetype(self, detected_mimetype: Option<DetectedMimetype>) -> Self {
+        match self {
+            Self::Embedded(state, data, mut ctx) => {
+                ctx.state.detected_mimetype = detected_mimetype;
+                Self::Embedded(state, data, ctx)
+            }
+            output => output,
+        }
+    }
+
+    /// Sets the MD5 checksum of the content of a created or embedded file, for processors that calculated it while
+    /// writing the file, so it is not read again.
+    ///
+    /// Other outputs are returned unchanged.
+    ///
+    pub fn with_content_checksum(self, content_checksum: impl Into<String>) -> Self {
+        let content_checksum = Some(content_checksum.into());
+        match self {
+            Self::Processed(state, data) => Self::Processed(state, ProcessOutputData { content_checksum, ..data }),
+            Self::Embedded(state, data, ctx) => {
+                Self::Embedded(state, ProcessOutputData { content_checksum, ..data }, ctx)
+            }
+            Self::Report(state, data) => Self::Report(state, ProcessOutputData { content_checksum, ..data }),
+            output => output,
+        }
+    }
+
+    /// Calculates the MD5 checksum of the content of a created or embedded file on the blocking thread pool, unless it
+    /// is already known.
+    ///
+    async fn with_calculated_content_checksum(self) -> Self {
+        let path = match &self {
+            Self::Processed(_, data) | Self::Embedded(_, data, _) | Self::Report(_, data)
+                if data.content_checksum.is_none() => data.path.to_path_buf(),
+            _ => return self,
+        };
+        match tokio::task::spawn_blocking(move || content_checksum(&path)).await {
+            Ok(Some(checksum)) => self.with_content_checksum(checksum),
+            _ => self,
+        }
+    }
+
+    /// Records the mbox envelope of an embedded message.
+    ///
+    /// Like [`ProcessOutput::with_detected_mimetype`], the envelope is set on the state of the context to process the
+    /// embedded file with. Other outputs are returned unchanged.
+    ///
+    pub fn with_envelope(self, envelope: MboxEnvelope) -> Self {
+        match self {
+            Self::Embedded(state, data, mut ctx) => {
+                ctx.state.envelope = Some(Box::new(envelope));
+                Self::Embedded(state, data, ctx)
+            }
+            output => output,
+        }
+    }
+
+    /// Creates a new ProcessOutput representing a skipped embedded file.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The ProcessContext of the processing operation.
+    /// * `name` - The name of the skipped file, or [`None`] if all embedded files were skipped.
+    /// * `reason` - Why the file was skipped.
+    ///
+    pub fn skipped(
+        ctx: &ProcessContext,
+        name: Option<String>,
+        reason: SkipReason,
+    ) -> Self {
+        Self::Skipped(ctx.state.clone(), SkippedOutputData { name, reason })
+    }
+
+    /// Creates a new ProcessOutput representing the report of processing a file.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The ProcessContext of the processing operation.
+    /// * `path` - The path to the report.json file.
+    /// * `checksum` - The dupe ID of the processed file.
+    ///
+    pub fn report(
+        ctx: &ProcessContext,
+        path: tempfile::TempPath,
+        checksum: impl Into<String>,
+    ) -> Self {
+        Self::Report(
+            ctx.state.clone(),
+            ProcessOutputData {
+                name: "report.json".to_string(),
+                content_checksum: None,
+                path,
+                mimetype: "application/json".to_string(),
+                kind: OutputKind::Report,
+                types: ctx.types.clone(),
+                checksum: checksum.into(),
+                fallback_level: ctx.fallback_level,
+            }
         )
     }
 }
+
+/// Calculates the MD5 checksum of the content of a file, or [`None`] if it could not be read.
+///
+/// Blocks while reading the file, so it must not be called on the async runtime directly.
+///
+fn content_checksum(path: &Path) -> Option<String> {
+    let mut file = std::fs::File::open(path).ok()?;
+    let mut checksum = md5::Context::new();
+    io::copy(&mut file, &mut checksum).ok()?;
+    Some(format!("{:x}", checksum.compute()))
+}
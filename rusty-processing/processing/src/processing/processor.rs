@@ -1,24 +1,41 @@
+use std::collections::HashSet;
 use std::fmt::Debug;
-use std::io;
+use std::future::Future;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use async_trait::async_trait;
-use futures::future::try_join_all;
+use futures::future::{BoxFuture, Shared, try_join_all};
+use futures::stream::FuturesUnordered;
+use futures::{FutureExt, StreamExt};
 use lazy_static::lazy_static;
-use serde::{Deserialize, Serialize};
-use tempfile::{NamedTempFile, TempPath};
+use log::{info, warn};
+use tempfile::TempPath;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio::time::Instant;
 
-use identify::deduplication::dedupe_checksum_from_path;
+use identify::deduplication::{dedupe_checksum, dedupe_checksum_from_path};
+use identify::mimetype::identify_mimetype_with_method;
 
-use crate::processing::{ProcessContext, ProcessType};
+use crate::is_generic_mimetype;
+use crate::processing::stream::tee;
+use crate::threading::{ThreadBuilder, ThreadMessage};
+use crate::processing::{
+    DetectedMimetype, InputStream, OutputRecorder, ProcessContext, ProcessContextBuilder, ProcessError, ProcessErrorKind, ProcessOutput,
+    ProcessOutputData, ProcessorBuilder, ProcessorConfig, ProcessorRegistry, ProcessorReport, ProcessorStatus, ProcessReport,
+    Progress, ProcessState, ProcessType, ReportError, SharedPdf, SkipReason, SkippedOutputData, StreamedOutput,
+};
 
 lazy_static! {
-    static ref PROCESSOR: Processor = Processor;
+    static ref PROCESSOR: Processor = Processor::default();
 }
 
 /// Returns a reference to the global processor instance.
 ///
+/// The global processor uses the default [`ProcessorRegistry`] containing all built-in processors.
+///
 pub fn processor() -> &'static Processor {
     &PROCESSOR
 }
@@ -27,8 +44,10 @@ pub fn processor() -> &'static Processor {
 ///
 /// Process implementations are required to be thread safe.
 ///
+/// Implementations can be registered with a [`ProcessorRegistry`] to extend the MIME types a [`Processor`] handles.
+///
 #[async_trait]
-pub(crate) trait Process: Send + Sync {
+pub trait Process: Send + Sync {
     /// Process a stream of bytes.
     ///
     /// # Arguments
@@ -37,19 +56,64 @@ pub(crate) trait Process: Send + Sync {
     /// * `input_path` - The path to the input file.
     /// * `output_path` - The path to the metadata.json file.
     ///
+    /// Errors returned are sent as an error output of the processing operation, except for a
+    /// [`ProcessErrorKind::Cancelled`] error, which stops the processing operation.
+    ///
     async fn process(
         &self,
         ctx: ProcessContext,
         input_path: &Path,
         output_path: TempPath,
         checksum: &str,
-    ) -> Result<(), anyhow::Error>;
+    ) -> Result<(), ProcessError>;
+
+    /// Returns whether the processor can process a stream of the file with [`Process::process_stream`], reading it
+    /// once from start to end instead of needing the file on disk.
+    ///
+    fn supports_streaming(&self) -> bool {
+        false
+    }
+
+    /// Process a stream of bytes of the file, read once from start to end.
+    ///
+    /// Only called if [`Process::supports_streaming`] returns `true`. The files created are returned instead of being
+    /// sent as outputs, as the checksum of the file is only known once the whole stream was read.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - The context of the processing operation.
+    /// * `input` - The stream of the input file.
+    /// * `output_path` - The path to the metadata.json file.
+    ///
+    async fn process_stream(
+        &self,
+        _ctx: ProcessContext,
+        _input: InputStream,
+        _output_path: TempPath,
+    ) -> Result<Vec<StreamedOutput>, ProcessError> {
+        Err(ProcessError::new(
+            ProcessErrorKind::Unsupported,
+            anyhow!("processor '{}' does not support streaming", self.name()),
+        ))
+    }
 
     /// Returns the name of the processor.
     ///
     fn name(&self) -> &'static str;
 }
 
+/// The input of a single processor.
+///
+enum ProcessorInput<'a> {
+    /// The path to the input file and its checksum.
+    ///
+    File(&'a Path, &'a str),
+
+    /// A stream of the input file and the checksum calculated while it is read.
+    ///
+    Stream(InputStream, Shared<BoxFuture<'static, Option<String>>>),
+}
+
 
 /// Structure defining the core processor.
 ///
@@ -57,117 +121,1362 @@ pub(crate) trait Process: Send + Sync {
 /// determining the correct processor to use for a given MIME type, and then
 /// delegating to that processor.
 ///
-#[derive(Debug, Default, PartialEq, PartialOrd, Eq, Ord, Hash, Serialize, Deserialize)]
-pub struct Processor;
+/// Use [`ProcessorBuilder`] to create a processor with a custom configuration.
+///
+#[derive(Debug, Clone)]
+pub struct Processor {
+    config: Arc<ProcessorConfig>,
+    registry: Arc<ProcessorRegistry>,
+    permits: Option<Arc<Semaphore>>,
+}
+
+impl Default for Processor {
+    fn default() -> Self {
+        Self::new(ProcessorRegistry::default())
+    }
+}
 
 impl Processor {
+    /// Creates a new processor that looks up processors in the given registry.
+    ///
+    pub fn new(registry: ProcessorRegistry) -> Self {
+        Self::with_config(ProcessorConfig::default(), registry)
+    }
+
+    /// Creates a new processor with the given configuration that looks up processors in the given registry.
+    ///
+    pub fn with_config(config: ProcessorConfig, registry: ProcessorRegistry) -> Self {
+        let permits = config.max_concurrency().map(|permits| Arc::new(Semaphore::new(permits.get())));
+        Self {
+            config: Arc::new(config),
+            registry: Arc::new(registry),
+            permits,
+        }
+    }
+
+    /// Returns a builder to create a configured processor.
+    ///
+    pub fn builder() -> ProcessorBuilder {
+        ProcessorBuilder::new()
+    }
+
+    /// Returns the configuration of the processor.
+    ///
+    pub fn config(&self) -> &ProcessorConfig {
+        &self.config
+    }
+
+    /// Returns the registry used to look up processors.
+    ///
+    pub fn registry(&self) -> &ProcessorRegistry {
+        &self.registry
+    }
+
     /// Processes a stream of data.
     ///
     /// This method will determine the correct processor to use for the given
     /// MIME type, and then delegate to that processor.
     ///
+    /// If the MIME type of the context is empty or generic, such as `application/octet-stream`, it is detected from
+    /// the content of the file first, and recorded in `ProcessState.detected_mimetype`.
+    ///
     /// # Arguments
     ///
     /// * `ctx` - Context of the processing operation.
     /// * `stream` - Stream of data in `bytes::Bytes` of the content to process.
     ///
+    /// Once all processors finished, a [`ProcessReport`] of the file is sent as the last output.
+    ///
+    /// # Returns
+    ///
+    /// An error if the file could not be processed at all or processing was cancelled. A processor failing does not
+    /// stop the other processors, and is sent as an error output instead.
+    ///
     pub async fn process(
         &self,
         ctx: ProcessContext,
         input_path: PathBuf,
-    ) -> Result<(), anyhow::Error> {
-        let checksum = dedupe_checksum_from_path(&input_path, &ctx.mimetype).await
-            .context("failed to calculate checksum")?;
+    ) -> Result<(), ProcessError> {
+        self.process_file(ctx, input_path, None).await
+    }
+
+    /// Processes a stream of a file, such as the body of a download, without writing it to disk first where possible.
+    ///
+    /// The stream is read once and passed through to all processors if each requested type has a single processor and
+    /// all of them support streaming, see [`Process::supports_streaming`]. Otherwise, for instance if a processor needs
+    /// random access to the file or the MIME type has to be detected, the stream is written to a temporary file which
+    /// is processed like with [`Processor::process`].
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Context of the processing operation.
+    /// * `reader` - The stream of the file to process.
+    /// * `size_hint` - The size of the file if known, such as the content length of a download, to reject files
+    ///   exceeding the maximum input size before reading them.
+    ///
+    /// # Returns
+    ///
+    /// An error if the file could not be processed at all or processing was cancelled, like [`Processor::process`].
+    ///
+    pub async fn process_reader(
+        &self,
+        ctx: ProcessContext,
+        reader: impl AsyncRead + Send + Unpin + 'static,
+        size_hint: Option<u64>,
+    ) -> Result<(), ProcessError> {
+        let ctx = ctx.with_config(self.config.clone());
+        if let (Some(size), Some(max_input_size)) = (size_hint, self.config.max_input_size()) {
+            if size > max_input_size {
+                let err = ProcessError::new(
+                    ProcessErrorKind::LimitExceeded,
+                    anyhow!("input file size {} exceeds the maximum of {}", size, max_input_size),
+                );
+                return self.finish(&ctx, ProcessReport::new(&ctx), Err(err)).await;
+            }
+        }
+
+        if let Some(chains) = self.determine_streaming_processors(&ctx) {
+            let mut report = ProcessReport::new(&ctx);
+            let result = self.stream_with_config(&ctx, reader, chains, &mut report).await;
+            return self.finish(&ctx, report, result).await;
+        }
+
+        let input_path = match self.spool(reader).await {
+            Ok(input_path) => input_path,
+            Err(err) => return self.finish(&ctx, ProcessReport::new(&ctx), Err(err)).await,
+        };
+        self.process_file(ctx, input_path.to_path_buf(), None).await
+    }
+
+    /// Processes a file and all files embedded in it, recursively.
+    ///
+    /// Each embedded file is processed with the same types as the file it was embedded in, and its outputs are sent
+    /// through the same channel, with an ID chain locating it in the tree of files. The [`ProcessOutput::Embedded`]
+    /// output of an embedded file is sent once the embedded file was processed.
+    ///
+    /// An embedded file with a checksum that was already processed in the same tree, or that is deeper than the
+    /// maximum depth of the [`crate::processing::ExtractionLimits`], is sent as [`ProcessOutput::Skipped`] instead.
+    ///
+    /// If [`ProcessType::Threads`] is requested, the conversation threads of all messages in the tree are sent as
+    /// `tree-threads.json` of the original file once the whole tree was processed.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Context of the processing operation.
+    /// * `input_path` - The path to the file to process.
+    ///
+    /// # Returns
+    ///
+    /// An error if the original file could not be processed at all or processing was cancelled. Errors processing
+    /// embedded files are sent as error outputs instead.
+    ///
+    pub async fn process_recursive(
+        &self,
+        ctx: ProcessContext,
+        input_path: PathBuf,
+    ) -> Result<(), ProcessError> {
+        let ctx = self.detect_mimetype(ctx, &input_path).await;
+        let checksum = dedupe_checksum_from_path(&input_path, &ctx.mimetype).await.ok();
+        let tree = Arc::new(ProcessTree::new(&ctx, checksum.clone()));
+        if let Some(checksum) = &checksum {
+            tree.add_message(&ctx.mimetype, &input_path, checksum).await;
+        }
+
+        self.process_tree(ctx.clone(), input_path, checksum.clone(), tree.clone()).await?;
+        self.add_tree_threads(&ctx, &tree, checksum).await
+    }
+
+    /// Sends the threads of the messages found in a tree as a processed file of its original file.
+    ///
+    async fn add_tree_threads(
+        &self,
+        ctx: &ProcessContext,
+        tree: &ProcessTree,
+        checksum: Option<String>,
+    ) -> Result<(), ProcessError> {
+        let (Some(threads), Some(checksum)) = (&tree.threads, checksum) else {
+            return Ok(());
+        };
+        let threads = {
+            let threads = threads.lock().unwrap();
+            if threads.is_empty() {
+                return Ok(());
+            }
+            threads.build()
+        };
+
+        let output_path = self.config.temp_file()
+            .context("failed to create temporary file")?
+            .into_temp_path();
+        let file = std::fs::File::create(&output_path)
+            .context("failed to create threads file")?;
+        serde_json::to_writer_pretty(file, &threads)
+            .context("failed to write threads to file")?;
+
+        let ctx = ctx.clone().with_process_type(ProcessType::Threads);
+        let output = ProcessOutput::processed(&ctx, "tree-threads.json", output_path, "application/json", checksum);
+        ctx.add_output(Ok(output)).await
+    }
+
+    /// Processes a file in a tree of files, processing its embedded files concurrently as they are discovered.
+    ///
+    fn process_tree(
+        &self,
+        ctx: ProcessContext,
+        input_path: PathBuf,
+        checksum: Option<String>,
+        tree: Arc<ProcessTree>,
+    ) -> BoxFuture<'_, Result<(), ProcessError>> {
+        async move {
+            let (output_sink, mut outputs) = tokio::sync::mpsc::channel(100);
+            let output_ctx = ctx.clone();
+            let processing = self.process_file(ctx.with_output_sink(output_sink), input_path, checksum);
+            tokio::pin!(processing);
+
+            let mut result = None;
+            let mut cancelled = None;
+            let mut children = FuturesUnordered::new();
+            loop {
+                tokio::select! {
+                    processed = &mut processing, if result.is_none() => result = Some(processed),
+                    Some(output) = outputs.recv() => match output {
+                        Ok(ProcessOutput::Embedded(state, data, embedded_ctx)) => {
+                            let embedded_ctx = embedded_ctx.with_output_sink(output_ctx.output_sink.clone());
+                            match self.admit_embedded(&state, &data, &tree.seen) {
+                                Ok(()) => {
+                                    tree.add_message(&data.mimetype, &data.path, &data.checksum).await;
+                                    children.push(self.process_embedded(state, data, embedded_ctx, tree.clone()));
+                                }
+                                Err(reason) => {
+                                    info!("Skipping embedded file {}: {}", data.name, reason);
+                                    let output = ProcessOutput::Skipped(state, SkippedOutputData {
+                                        name: Some(data.name),
+                                        reason,
+                                    });
+                                    output_ctx.add_output(Ok(output)).await?;
+                                }
+                            }
+                        }
+                        output => output_ctx.add_output(output).await?,
+                    },
+                    Some((embedded_result, output)) = children.next() => {
+                        match embedded_result {
+                            Err(err) if err.kind == ProcessErrorKind::Cancelled => cancelled = Some(err),
+                            Err(err) => output_ctx.add_output(Err(err)).await?,
+                            Ok(()) => (),
+                        }
+                        output_ctx.add_output(Ok(output)).await?;
+                    },
+                    else => break,
+                }
+            }
+
+            match cancelled {
+                Some(err) => result.unwrap_or(Ok(())).and(Err(err)),
+                None => result.unwrap_or(Ok(())),
+            }
+        }.boxed()
+    }
+
+    /// Checks whether an embedded file should be processed, marking its checksum as seen if it should.
+    ///
+    fn admit_embedded(
+        &self,
+        state: &ProcessState,
+        data: &ProcessOutputData,
+        seen: &Mutex<HashSet<String>>,
+    ) -> Result<(), SkipReason> {
+        if let Some(max_depth) = self.config.limits().max_depth {
+            if state.depth() + 1 > max_depth {
+                return Err(SkipReason::MaxDepth(max_depth));
+            }
+        }
+        if !seen.lock().unwrap().insert(data.checksum.clone()) {
+            return Err(SkipReason::Duplicate(data.checksum.clone()));
+        }
+        Ok(())
+    }
+
+    /// Processes an embedded file as part of a tree, returning its output to send once it was processed.
+    ///
+    async fn process_embedded(
+        &self,
+        state: ProcessState,
+        data: ProcessOutputData,
+        embedded_ctx: ProcessContext,
+        tree: Arc<ProcessTree>,
+    ) -> (Result<(), ProcessError>, ProcessOutput) {
+        let mut id_chain = state.id_chain.clone();
+        id_chain.push(data.checksum.clone());
+        let ctx = ProcessContextBuilder::from(embedded_ctx.clone())
+            .mimetype(data.mimetype.clone())
+            .types(data.types.clone())
+            .id_chain(id_chain)
+            .build();
+
+        let result = self.process_tree(ctx, data.path.to_path_buf(), Some(data.checksum.clone()), tree).await;
+        (result, ProcessOutput::Embedded(state, data, embedded_ctx))
+    }
+
+    /// Processes a single file, calculating its checksum unless it is already known.
+    ///
+    async fn process_file(
+        &self,
+        ctx: ProcessContext,
+        input_path: PathBuf,
+        checksum: Option<String>,
+    ) -> Result<(), ProcessError> {
+        let ctx = self.detect_mimetype(ctx.with_config(self.config.clone()), &input_path).await;
+        let mut report = ProcessReport::new(&ctx);
+        let result = self.process_with_config(&ctx, input_path, checksum, &mut report).await;
+        self.finish(&ctx, report, result).await
+    }
+
+    /// Records the result of processing a file in its report, and sends the report as the last output.
+    ///
+    async fn finish(
+        &self,
+        ctx: &ProcessContext,
+        mut report: ProcessReport,
+        result: Result<(), ProcessError>,
+    ) -> Result<(), ProcessError> {
+        let result = result.map_err(|err| ctx.error(err));
+        if let Err(err) = &result {
+            report.error = Some(ReportError::from(err));
+        }
+
+        let report_result = self.add_report(ctx, &report).await;
+        result.and(report_result)
+    }
+
+    /// Writes the stream to a temporary file, stopping once it exceeds the maximum input size.
+    ///
+    async fn spool(&self, reader: impl AsyncRead + Unpin) -> Result<TempPath, ProcessError> {
+        let input_path = self.config.temp_file()
+            .context("failed to create temporary file")?
+            .into_temp_path();
+        let mut file = tokio::fs::File::create(&input_path).await
+            .context("failed to open temporary file")?;
+
+        // Reading past the maximum is enough for the size check when processing the file to reject it
+        let limit = self.config.max_input_size().map_or(u64::MAX, |max_input_size| max_input_size.saturating_add(1));
+        tokio::io::copy(&mut reader.take(limit), &mut file).await
+            .context("failed to write input to temporary file")?;
+        file.flush().await
+            .context("failed to write input to temporary file")?;
+        Ok(input_path)
+    }
+
+    /// Detects the MIME type of the file from its content if the context has none, or only a generic one.
+    ///
+    /// The given MIME type is kept if detection fails, and processing continues with `application/octet-stream` if
+    /// there was none.
+    ///
+    async fn detect_mimetype(&self, mut ctx: ProcessContext, input_path: &Path) -> ProcessContext {
+        if !is_generic_mimetype(&ctx.mimetype) {
+            return ctx;
+        }
+
+        let identified = identify_mimetype_with_method(input_path, ctx.config().tika()).await;
+        match identified {
+            Ok(Some((mimetype, method))) => {
+                ctx.mimetype = mimetype.clone();
+                ctx.state.detected_mimetype = Some(DetectedMimetype { mimetype, method });
+            }
+            Ok(None) => info!("Could not detect MIME type of {}", input_path.display()),
+            Err(err) => warn!("Failed to detect MIME type: {:?}", err),
+        }
+        if ctx.mimetype.is_empty() {
+            ctx.mimetype = "application/octet-stream".to_string();
+        }
+        ctx
+    }
+
+    /// Processes the file once the context uses the configuration of this processor.
+    ///
+    async fn process_with_config(
+        &self,
+        ctx: &ProcessContext,
+        input_path: PathBuf,
+        checksum: Option<String>,
+        report: &mut ProcessReport,
+    ) -> Result<(), ProcessError> {
+        if let Some(max_input_size) = self.config.max_input_size() {
+            let size = tokio::fs::metadata(&input_path).await
+                .context("failed to read input file metadata")?
+                .len();
+            if size > max_input_size {
+                return Err(ProcessError::new(
+                    ProcessErrorKind::LimitExceeded,
+                    anyhow!("input file size {} exceeds the maximum of {}", size, max_input_size),
+                ));
+            }
+        }
+
+        let checksum = match checksum {
+            Some(checksum) => checksum,
+            None => dedupe_checksum_from_path(&input_path, &ctx.mimetype).await
+                .context("failed to calculate checksum")?,
+        };
+        report.checksum = Some(checksum.clone());
+
+        // The PDF rendered for the PDF type is reused to render the pages of the image type
+        let shares_pdf = ctx.types.contains(&ProcessType::Pdf) && ctx.types.contains(&ProcessType::Image);
+        let (mut sending_pdf, mut receiving_pdf) = match shares_pdf.then(SharedPdf::new) {
+            Some((sending, receiving)) => (Some(sending), Some(receiving)),
+            None => (None, None),
+        };
 
         let mut futures = vec![];
-        for processor in self.determine_processors(&ctx.mimetype, &ctx.types) {
-            let inner_ctx = ctx.clone();
+        for (process_type, chain) in self.determine_processors(&ctx.mimetype, &ctx.types) {
+            let shared_pdf = match process_type {
+                ProcessType::Pdf => sending_pdf.take(),
+                ProcessType::Image => receiving_pdf.take(),
+                _ => None,
+            }.map(Arc::new);
+            let inner_ctx = ctx.clone().with_shared_pdf(shared_pdf.clone());
             let input_path_ref = &input_path;
             let checksum = &checksum;
-            let output_path = temp_path().context("failed to create temporary file")?;
 
             futures.push(async move {
-                processor.process(inner_ctx, input_path_ref, output_path, checksum).await
+                // The PDF is waited for before taking a permit, as rendering it may need one
+                if let Some(shared_pdf) = shared_pdf {
+                    shared_pdf.receive().await;
+                }
+                self.run_chain(chain, process_type, inner_ctx, input_path_ref, checksum).await
             });
         }
+        // Without processors of the PDF type, the image type stops waiting for a PDF
+        drop(sending_pdf);
 
-        try_join_all(futures).await.map(|_| ())
+        report.processors = try_join_all(futures).await?.into_iter().flatten().collect();
+        Ok(())
     }
 
-    fn determine_processors(&self, mimetype: &str, types: &[ProcessType]) -> Vec<Box<dyn Process>> {
-        let mut processors = vec![];
+    /// Processes a stream of the file with the given processors, reading it once while calculating its checksum.
+    ///
+    /// The processors read the stream in lockstep, so they run under a single permit instead of one each.
+    ///
+    async fn stream_with_config(
+        &self,
+        ctx: &ProcessContext,
+        reader: impl AsyncRead + Send + Unpin + 'static,
+        processors: Vec<(ProcessType, Arc<dyn Process>)>,
+        report: &mut ProcessReport,
+    ) -> Result<(), ProcessError> {
+        let (reading, mut streams) = tee(reader, processors.len() + 1, self.config.max_input_size());
+        let mut checksum_stream = streams.pop().expect("tee should return the requested number of streams");
+        let mimetype = ctx.mimetype.clone();
+        let checksum_task = tokio::spawn(async move { dedupe_checksum(&mut checksum_stream, mimetype).await });
+        let checksum_abort = checksum_task.abort_handle();
+        let checksum = checksum_task
+            .map(|result| result.ok().and_then(Result::ok))
+            .boxed()
+            .shared();
 
-        if types.contains(&ProcessType::Text) {
-            if let Some(processor) = self.text_processor(mimetype) {
-                processors.push(processor);
+        let processing = async {
+            let _permit = self.acquire_permit().await?;
+            let mut futures = vec![];
+            for ((process_type, processor), stream) in processors.into_iter().zip(streams) {
+                let output_path = self.config.temp_file()
+                    .context("failed to create temporary file")?
+                    .into_temp_path();
+                let input = ProcessorInput::Stream(stream, checksum.clone());
+                futures.push(self.run_processor(processor, process_type, ctx.clone(), input, output_path, false));
             }
-        }
-        if types.contains(&ProcessType::Metadata) {
-            if let Some(processor) = self.metadata_processor(mimetype) {
-                processors.push(processor);
+            try_join_all(futures).await
+        };
+
+        let processors = match processing.await {
+            Ok(processors) => processors,
+            Err(err) => {
+                reading.abort();
+                checksum_abort.abort();
+                return Err(err);
             }
-        }
-        if types.contains(&ProcessType::Pdf) {
-            if let Some(processor) = self.pdf_processor(mimetype) {
-                processors.push(processor);
+        };
+        reading.await.context("failed to read input")??;
+
+        report.checksum = checksum.await;
+        report.processors = processors;
+        Ok(())
+    }
+
+    /// Runs the processors of a fallback chain in order, until one of them does not fail.
+    ///
+    /// A processor is considered failed when it had errors and produced no outputs. Errors of a processor followed by a
+    /// fallback are only recorded in its report, whereas errors of the last processor to run are sent as outputs.
+    ///
+    /// Returns the reports of all processors that ran.
+    ///
+    async fn run_chain(
+        &self,
+        chain: Vec<Arc<dyn Process>>,
+        process_type: ProcessType,
+        ctx: ProcessContext,
+        input_path: &Path,
+        checksum: &str,
+    ) -> Result<Vec<ProcessorReport>, ProcessError> {
+        let mut reports = vec![];
+        let last_level = chain.len().saturating_sub(1);
+        for (level, processor) in chain.into_iter().enumerate() {
+            let output_path = self.config.temp_file()
+                .context("failed to create temporary file")?
+                .into_temp_path();
+            let ctx = ctx.clone().with_fallback_level(level);
+            let has_fallback = level < last_level;
+
+            let _permit = self.acquire_permit().await?;
+            let input = ProcessorInput::File(input_path, checksum);
+            let report = self.run_processor(processor, process_type.clone(), ctx, input, output_path, has_fallback).await?;
+            let failed = report.status == ProcessorStatus::Failed;
+            if failed && has_fallback {
+                info!("Processor '{}' failed, falling back to the next processor", report.name);
+            }
+            reports.push(report);
+            if !failed {
+                break;
             }
         }
-        if types.contains(&ProcessType::Embedded) {
-            if let Some(processor) = self.embedded_processor(mimetype) {
-                processors.push(processor);
+        Ok(reports)
+    }
+
+    /// Sends the report as an output of the processing operation.
+    ///
+    async fn add_report(&self, ctx: &ProcessContext, report: &ProcessReport) -> Result<(), ProcessError> {
+        let file = self.config.temp_file()
+            .context("failed to create temporary file")?;
+        serde_json::to_writer_pretty(&file, report)
+            .context("failed to write report to file")?;
+
+        let checksum = report.checksum.clone().unwrap_or_default();
+        ctx.add_output(Ok(ProcessOutput::report(ctx, file.into_temp_path(), checksum))).await
+    }
+
+    /// Runs a single processor, stopping it if it runs past its timeout or the processing operation is cancelled.
+    ///
+    /// Errors, including a timeout, are sent as an error output of the processor and do not stop any other processors,
+    /// whereas a cancellation is returned as an error. If the processor has a fallback, its errors are only sent if it
+    /// did not fail entirely, as the fallback runs otherwise.
+    ///
+    /// Returns the report of the processor, built from the outputs it sent.
+    ///
+    async fn run_processor(
+        &self,
+        processor: Arc<dyn Process>,
+        process_type: ProcessType,
+        ctx: ProcessContext,
+        input: ProcessorInput<'_>,
+        output_path: TempPath,
+        has_fallback: bool,
+    ) -> Result<ProcessorReport, ProcessError> {
+        let name = processor.name();
+        let timeout = self.config.timeout(name);
+        let timeout_at = timeout.map(|timeout| Instant::now() + timeout);
+        let deadline = match (timeout_at, ctx.deadline()) {
+            (Some(timeout_at), Some(deadline)) => Some(timeout_at.min(deadline)),
+            (timeout_at, deadline) => timeout_at.or(deadline),
+        };
+
+        let started = Instant::now();
+        let fallback_level = ctx.fallback_level();
+        let recorder = Arc::new(match has_fallback {
+            true => OutputRecorder::deferring_errors(),
+            false => OutputRecorder::default(),
+        });
+        let ctx = ctx.with_processor(name).with_process_type(process_type.clone());
+        let unrecorded_ctx = ctx.clone();
+        let ctx = ctx.with_recorder(recorder.clone());
+        let cancellation = ctx.cancellation_token().clone();
+        let output_ctx = ctx.clone();
+        output_ctx.progress(Progress::default()).await?;
+        let processing = async move {
+            match input {
+                ProcessorInput::File(input_path, checksum) => processor.process(ctx, input_path, output_path, checksum).await,
+                ProcessorInput::Stream(stream, checksum) => process_stream(processor.as_ref(), ctx, stream, output_path, checksum).await,
             }
+        };
+        let result = tokio::select! {
+            _ = cancellation.cancelled() => return Err(output_ctx.error(ProcessError::cancelled())),
+            result = run_until(deadline, processing) => result,
+        };
+
+        let err = match result {
+            Some(Ok(())) => None,
+            Some(Err(err)) if err.kind == ProcessErrorKind::Cancelled => return Err(output_ctx.error(err)),
+            Some(Err(err)) => Some(err),
+            None => {
+                let err = match timeout.filter(|_| deadline == timeout_at) {
+                    Some(timeout) => anyhow!("processor did not finish within {:?}", timeout),
+                    None => anyhow!("processor did not finish before the deadline"),
+                };
+                Some(ProcessError::new(ProcessErrorKind::Timeout, err))
+            }
+        };
+
+        if let Some(err) = err {
+            warn!("Processor '{}' failed: {}", name, err);
+            output_ctx.add_output(Err(err)).await?;
         }
 
-        processors
+        let report = recorder.report(name, process_type, fallback_level, started.elapsed());
+        let deferred = recorder.take_deferred();
+        if report.status != ProcessorStatus::Failed {
+            for err in deferred {
+                unrecorded_ctx.add_output(Err(err)).await?;
+            }
+        }
+        Ok(report)
     }
 
-    /// Find a processor to extract text based on the MIME type.
+    /// Finds the enabled processors for each of the requested types, ordered by precedence to form a fallback chain.
     ///
-    fn text_processor(&self, mimetype: &str) -> Option<Box<dyn Process>> {
-        match mimetype {
-            "text/plain " |
-            "text/css" |
-            "text/csv" |
-            "text/javascript" |
-            "application/zip" |
-            "application/mbox" => None,
+    fn determine_processors(&self, mimetype: &str, types: &[ProcessType]) -> Vec<(ProcessType, Vec<Arc<dyn Process>>)> {
+        ProcessType::all().iter()
+            .filter(|process_type| types.contains(process_type))
+            .map(|process_type| {
+                let chain = self.registry.lookup(mimetype, process_type)
+                    .into_iter()
+                    .filter(|processor| self.config.is_enabled(processor.name()))
+                    .collect::<Vec<_>>();
+                (process_type.clone(), chain)
+            })
+            .filter(|(_, chain)| !chain.is_empty())
+            .collect()
+    }
 
-            _ => Some(Box::<crate::text::DefaultTextProcessor>::default()),
+    /// Finds the processors to stream the file to, or [`None`] if it has to be written to disk first.
+    ///
+    /// Streaming requires a known MIME type, and a single processor supporting streaming for each requested type, as a
+    /// fallback would have to read the file again.
+    ///
+    fn determine_streaming_processors(&self, ctx: &ProcessContext) -> Option<Vec<(ProcessType, Arc<dyn Process>)>> {
+        if is_generic_mimetype(&ctx.mimetype) {
+            return None;
         }
+
+        self.determine_processors(&ctx.mimetype, &ctx.types)
+            .into_iter()
+            .map(|(process_type, mut chain)| match chain.len() {
+                1 if chain[0].supports_streaming() => Some((process_type, chain.remove(0))),
+                _ => None,
+            })
+            .collect()
     }
 
-    /// Find a processor to extract metadata based on the MIME type.
+    /// Waits for a permit to run a processor if the concurrency is limited.
     ///
-    fn metadata_processor(&self, _mimetype: &str) -> Option<Box<dyn Process>> {
-        Some(Box::<crate::metadata::DefaultMetadataProcessor>::default())
+    async fn acquire_permit(&self) -> Result<Option<SemaphorePermit<'_>>, ProcessError> {
+        match &self.permits {
+            Some(permits) => Ok(Some(permits.acquire().await.context("failed to acquire permit")?)),
+            None => Ok(None),
+        }
     }
+}
+
+/// The state shared by the files processed as part of a tree, see [`Processor::process_recursive`].
+///
+struct ProcessTree {
+    /// The checksums of the files processed so far, to skip duplicates.
+    ///
+    seen: Mutex<HashSet<String>>,
 
-    /// Find a processor to render to a PDF based on the MIME type.
+    /// The messages found in the tree, if their threads were requested.
     ///
-    fn pdf_processor(&self, mimetype: &str) -> Option<Box<dyn Process>> {
-        match mimetype {
-            "message/rfc822" => Some(Box::<crate::pdf::Rfc822PdfProcessor>::default()),
+    threads: Option<Mutex<ThreadBuilder>>,
+}
 
-            _ => None
+impl ProcessTree {
+    fn new(ctx: &ProcessContext, checksum: Option<String>) -> Self {
+        Self {
+            seen: Mutex::new(HashSet::from_iter(checksum)),
+            threads: ctx.types.contains(&ProcessType::Threads).then(Mutex::default),
         }
     }
 
-    /// Find a processor to extract embedded files based on the MIME type.
+    /// Adds the file to the threads of the tree if it is a message and threads were requested.
     ///
-    fn embedded_processor(&self, mimetype: &str) -> Option<Box<dyn Process>> {
-        match mimetype {
-            "application/zip" => Some(Box::<crate::embedded::ZipEmbeddedProcessor>::default()),
-            "application/mbox" => Some(Box::<crate::embedded::MboxEmbeddedProcessor>::default()),
-            "message/rfc822" => Some(Box::<crate::embedded::Rfc822EmbeddedProcessor>::default()),
+    async fn add_message(&self, mimetype: &str, path: &Path, checksum: &str) {
+        let Some(threads) = &self.threads else {
+            return;
+        };
+        if mimetype != "message/rfc822" {
+            return;
+        }
 
-            _ => None
+        match ThreadMessage::read(checksum, path).await {
+            Ok(Some(message)) => threads.lock().unwrap().add(message),
+            Ok(None) => {},
+            Err(err) => warn!("Failed to read message {} to thread it: {}", checksum, err),
         }
     }
 }
 
-/// Creates a temporary file and returns its path.
+/// Processes a stream with the processor, sending the files it created once the checksum of the stream is known.
 ///
-#[inline]
-fn temp_path() -> io::Result<TempPath> {
-    Ok(NamedTempFile::new()?.into_temp_path())
-}
\ No newline at end of file
+async fn process_stream(
+    processor: &dyn Process,
+    ctx: ProcessContext,
+    stream: InputStream,
+    output_path: TempPath,
+    checksum: Shared<BoxFuture<'static, Option<String>>>,
+) -> Result<(), ProcessError> {
+    let outputs = processor.process_stream(ctx.clone(), stream, output_path).await?;
+    let checksum = checksum.await
+        .ok_or_else(|| ProcessError::new(ProcessErrorKind::Io, anyhow!("failed to calculate checksum")))?;
+
+    for output in outputs {
+        ctx.add_output(Ok(output.into_processed(&ctx, &checksum))).await?;
+    }
+    Ok(())
+}
+
+/// Runs the future until the deadline, returning [`None`] if the deadline passed first.
+///
+async fn run_until<F: Future>(deadline: Option<Instant>, future: F) -> Option<F::Output> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, future).await.ok(),
+        None => Some(future.await),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use tokio::sync::mpsc::Receiver;
+
+    use crate::processing::{CancellationToken, OutputKind, ProcessContextBuilder};
+
+    use super::*;
+
+    struct SleepingProcessor(&'static str, Duration);
+
+    #[async_trait]
+    impl Process for SleepingProcessor {
+        async fn process(
+            &self,
+            ctx: ProcessContext,
+            _: &Path,
+            output_path: TempPath,
+            checksum: &str,
+        ) -> Result<(), ProcessError> {
+            tokio::time::sleep(self.1).await;
+            ctx.add_output(Ok(ProcessOutput::processed(&ctx, self.0, output_path, "text/plain", checksum))).await
+        }
+
+        fn name(&self) -> &'static str {
+            self.0
+        }
+    }
+
+    struct FailingProcessor(ProcessErrorKind);
+
+    #[async_trait]
+    impl Process for FailingProcessor {
+        async fn process(&self, _: ProcessContext, _: &Path, _: TempPath, _: &str) -> Result<(), ProcessError> {
+            Err(ProcessError::new(self.0, anyhow!("failed")))
+        }
+
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+    }
+
+    /// Copies the input to the output, from a stream if possible.
+    ///
+    struct CopyingProcessor;
+
+    #[async_trait]
+    impl Process for CopyingProcessor {
+        async fn process(&self, ctx: ProcessContext, input_path: &Path, output_path: TempPath, checksum: &str) -> Result<(), ProcessError> {
+            std::fs::copy(input_path, &output_path)?;
+            ctx.add_output(Ok(ProcessOutput::processed(&ctx, "copy", output_path, "image/jpeg", checksum))).await
+        }
+
+        fn supports_streaming(&self) -> bool {
+            true
+        }
+
+        async fn process_stream(&self, _: ProcessContext, mut input: InputStream, output_path: TempPath) -> Result<Vec<StreamedOutput>, ProcessError> {
+            let mut file = tokio::fs::File::create(&output_path).await?;
+            tokio::io::copy(&mut input, &mut file).await?;
+            file.flush().await?;
+            Ok(vec![StreamedOutput {
+                name: "streamed".to_string(),
+                path: output_path,
+                mimetype: "image/jpeg".to_string(),
+                kind: None,
+            }])
+        }
+
+        fn name(&self) -> &'static str {
+            "copying"
+        }
+    }
+
+    /// Embeds a copy of the input file and a new file for the next level of the tree.
+    ///
+    struct NestingProcessor;
+
+    #[async_trait]
+    impl Process for NestingProcessor {
+        async fn process(&self, ctx: ProcessContext, input_path: &Path, _: TempPath, checksum: &str) -> Result<(), ProcessError> {
+            let copy = ctx.temp_file()?.into_temp_path();
+            std::fs::copy(input_path, &copy)?;
+            ctx.add_output(Ok(ProcessOutput::embedded(&ctx, "copy.txt", copy, "text/plain", checksum))).await?;
+
+            let level = ctx.state.depth() + 1;
+            let nested = ctx.temp_file()?.into_temp_path();
+            std::fs::write(&nested, format!("level {}", level))?;
+            let nested_checksum = dedupe_checksum_from_path(&nested, "text/plain").await?;
+            ctx.add_output(Ok(ProcessOutput::embedded(&ctx, format!("level-{}.txt", level), nested, "text/plain", nested_checksum))).await
+        }
+
+        fn name(&self) -> &'static str {
+            "nesting"
+        }
+    }
+
+    /// Renders a copy of the input as a PDF, counting the renders.
+    ///
+    struct CountingPdfProcessor(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Process for CountingPdfProcessor {
+        async fn process(&self, ctx: ProcessContext, input_path: &Path, output_path: TempPath, checksum: &str) -> Result<(), ProcessError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            std::fs::copy(input_path, &output_path)?;
+            ctx.add_output(Ok(ProcessOutput::processed(&ctx, "rendered.pdf", output_path, "application/pdf", checksum))).await
+        }
+
+        fn name(&self) -> &'static str {
+            "counting pdf"
+        }
+    }
+
+    /// Copies the rendered PDF to a page, rendering it only if the PDF type did not, as the page images processor does.
+    ///
+    struct PageProcessor(Arc<AtomicUsize>);
+
+    #[async_trait]
+    impl Process for PageProcessor {
+        async fn process(&self, ctx: ProcessContext, input_path: &Path, output_path: TempPath, checksum: &str) -> Result<(), ProcessError> {
+            let pdf = match ctx.rendered_pdf().await {
+                Some(pdf) => pdf,
+                None => Arc::new(crate::image::render_pdf(&CountingPdfProcessor(self.0.clone()), &ctx, input_path, checksum).await?),
+            };
+            std::fs::copy(&*pdf, &output_path)?;
+            ctx.add_output(Ok(ProcessOutput::processed(&ctx, "page-1.png", output_path, "image/png", checksum))).await
+        }
+
+        fn name(&self) -> &'static str {
+            "page"
+        }
+    }
+
+    fn sleeping_processor(timeout: Duration) -> Processor {
+        let mut registry = ProcessorRegistry::empty();
+        registry
+            .register("*/*", ProcessType::Text, 0, SleepingProcessor("fast", Duration::ZERO))
+            .register("*/*", ProcessType::Metadata, 0, SleepingProcessor("slow", Duration::from_secs(60)));
+
+        ProcessorBuilder::new()
+            .registry(registry)
+            .processor_timeout("slow", timeout)
+            .build()
+    }
+
+    async fn collect(mut outputs: Receiver<Result<ProcessOutput, ProcessError>>) -> Vec<Result<ProcessOutput, ProcessError>> {
+        let mut collected = vec![];
+        while let Some(output) = outputs.recv().await {
+            collected.push(output);
+        }
+        collected
+    }
+
+    fn processed_names(outputs: &[Result<ProcessOutput, ProcessError>]) -> Vec<String> {
+        outputs.iter()
+            .filter_map(|output| match output {
+                Ok(ProcessOutput::Processed(_, data)) => Some(data.name.clone()),
+                Ok(ProcessOutput::Report(_, _)) | Err(_) => None,
+                _ => panic!("Expected processed output"),
+            })
+            .collect()
+    }
+
+    fn read_report(outputs: &[Result<ProcessOutput, ProcessError>]) -> ProcessReport {
+        match outputs.last() {
+            Some(Ok(ProcessOutput::Report(_, data))) => {
+                let file = std::fs::File::open(&data.path).unwrap();
+                serde_json::from_reader(file).unwrap()
+            }
+            _ => panic!("Expected report as the last output"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_timeout_does_not_stop_siblings() -> anyhow::Result<()> {
+        let processor = sleeping_processor(Duration::from_millis(10));
+        let (output_sink, outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new(
+            "image/jpeg",
+            vec![ProcessType::Text, ProcessType::Metadata],
+            output_sink,
+        ).build();
+
+        processor.process(ctx, PathBuf::from("../resources/jpg/PA280041.JPG")).await?;
+        let outputs = collect(outputs).await;
+
+        assert_eq!(outputs.len(), 3);
+        assert_eq!(processed_names(&outputs), vec!["fast"]);
+
+        let timeout = outputs.iter()
+            .find_map(|output| output.as_ref().err())
+            .expect("Expected timeout error");
+        assert_eq!(timeout.kind, ProcessErrorKind::Timeout);
+        assert_eq!(timeout.processor.as_deref(), Some("slow"));
+        assert_eq!(timeout.mimetype, "image/jpeg");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_error_does_not_stop_siblings() -> anyhow::Result<()> {
+        let mut registry = ProcessorRegistry::empty();
+        registry
+            .register("*/*", ProcessType::Text, 0, FailingProcessor(ProcessErrorKind::Corrupt))
+            .register("*/*", ProcessType::Metadata, 0, SleepingProcessor("fast", Duration::ZERO));
+        let processor = ProcessorBuilder::new().registry(registry).build();
+
+        let (output_sink, outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new(
+            "image/jpeg",
+            vec![ProcessType::Text, ProcessType::Metadata],
+            output_sink,
+        )
+            .id_chain(vec!["parent".to_string()])
+            .build();
+
+        processor.process(ctx, PathBuf::from("../resources/jpg/PA280041.JPG")).await?;
+        let outputs = collect(outputs).await;
+
+        assert_eq!(processed_names(&outputs), vec!["fast"]);
+        let err = outputs.iter()
+            .find_map(|output| output.as_ref().err())
+            .expect("Expected processor error");
+        assert_eq!(err.kind, ProcessErrorKind::Corrupt);
+        assert_eq!(err.processor.as_deref(), Some("failing"));
+        assert_eq!(err.id_chain, vec!["parent".to_string()]);
+        assert_eq!(err.mimetype, "image/jpeg");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_report() -> anyhow::Result<()> {
+        let mut registry = ProcessorRegistry::empty();
+        registry
+            .register("*/*", ProcessType::Text, 0, FailingProcessor(ProcessErrorKind::ServiceUnavailable))
+            .register("*/*", ProcessType::Metadata, 0, SleepingProcessor("fast", Duration::ZERO));
+        let processor = ProcessorBuilder::new().registry(registry).build();
+
+        let (output_sink, outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new(
+            "image/jpeg",
+            vec![ProcessType::Text, ProcessType::Metadata],
+            output_sink,
+        )
+            .id_chain(vec!["parent".to_string()])
+            .build();
+
+        processor.process(ctx, PathBuf::from("../resources/jpg/PA280041.JPG")).await?;
+        let report = read_report(&collect(outputs).await);
+
+        assert_eq!(report.mimetype, "image/jpeg");
+        assert!(report.checksum.is_some());
+        assert_eq!(report.id_chain, vec!["parent".to_string()]);
+        assert_eq!(report.error, None);
+        assert_eq!(report.processors.len(), 2);
+
+        let failing = &report.processors[0];
+        assert_eq!(failing.name, "failing");
+        assert_eq!(failing.process_type, ProcessType::Text);
+        assert_eq!(failing.status, ProcessorStatus::Failed);
+        assert_eq!(failing.errors[0].kind, ProcessErrorKind::ServiceUnavailable);
+
+        let fast = &report.processors[1];
+        assert_eq!(fast.name, "fast");
+        assert_eq!(fast.process_type, ProcessType::Metadata);
+        assert_eq!(fast.status, ProcessorStatus::Succeeded);
+        assert_eq!(fast.outputs.len(), 1);
+        assert_eq!(fast.outputs[0].name, "fast");
+        assert_eq!(fast.outputs[0].size, Some(0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_output_kind() -> anyhow::Result<()> {
+        let mut registry = ProcessorRegistry::empty();
+        registry.register("*/*", ProcessType::Metadata, 0, SleepingProcessor("fast", Duration::ZERO));
+        let processor = ProcessorBuilder::new().registry(registry).build();
+
+        let (output_sink, outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("image/jpeg", vec![ProcessType::Metadata], output_sink).build();
+
+        processor.process(ctx, PathBuf::from("../resources/jpg/PA280041.JPG")).await?;
+        let outputs = collect(outputs).await;
+
+        let kinds = outputs.iter()
+            .map(|output| match output {
+                Ok(ProcessOutput::Processed(_, data)) | Ok(ProcessOutput::Report(_, data)) => data.kind,
+                _ => panic!("Expected processed output or report"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(kinds, vec![OutputKind::Metadata, OutputKind::Report]);
+
+        match &outputs[0] {
+            Ok(ProcessOutput::Processed(_, data)) => {
+                assert_eq!(data.content_checksum.as_deref(), Some("d41d8cd98f00b204e9800998ecf8427e"));
+                assert_ne!(data.content_checksum.as_ref(), Some(&data.checksum));
+            }
+            _ => panic!("Expected processed output"),
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_fallback() -> anyhow::Result<()> {
+        let mut registry = ProcessorRegistry::empty();
+        registry
+            .register("*/*", ProcessType::Text, 0, SleepingProcessor("fallback", Duration::ZERO))
+            .register("*/*", ProcessType::Text, 1, FailingProcessor(ProcessErrorKind::ServiceUnavailable));
+        let processor = ProcessorBuilder::new().registry(registry).build();
+
+        let (output_sink, outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("image/jpeg", vec![ProcessType::Text], output_sink).build();
+
+        processor.process(ctx, PathBuf::from("../resources/jpg/PA280041.JPG")).await?;
+        let outputs = collect(outputs).await;
+
+        assert!(outputs.iter().all(Result::is_ok), "Expected the error of the failing processor to be held back");
+        let fallback_levels = outputs.iter()
+            .filter_map(|output| match output {
+                Ok(ProcessOutput::Processed(_, data)) => Some((data.name.as_str(), data.fallback_level)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(fallback_levels, vec![("fallback", 1)]);
+
+        let report = read_report(&outputs);
+        let chain = report.processors.iter()
+            .map(|processor| (processor.name.as_str(), processor.fallback_level, processor.status))
+            .collect::<Vec<_>>();
+        assert_eq!(chain, vec![("failing", 0, ProcessorStatus::Failed), ("fallback", 1, ProcessorStatus::Succeeded)]);
+        assert_eq!(report.processors[0].errors[0].kind, ProcessErrorKind::ServiceUnavailable);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_fallback_exhausted() -> anyhow::Result<()> {
+        let mut registry = ProcessorRegistry::empty();
+        registry
+            .register("*/*", ProcessType::Text, 0, FailingProcessor(ProcessErrorKind::Corrupt))
+            .register("*/*", ProcessType::Text, 1, FailingProcessor(ProcessErrorKind::ServiceUnavailable));
+        let processor = ProcessorBuilder::new().registry(registry).build();
+
+        let (output_sink, outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("image/jpeg", vec![ProcessType::Text], output_sink).build();
+
+        processor.process(ctx, PathBuf::from("../resources/jpg/PA280041.JPG")).await?;
+        let outputs = collect(outputs).await;
+
+        let errors = outputs.iter()
+            .filter_map(|output| output.as_ref().err())
+            .map(|err| err.kind)
+            .collect::<Vec<_>>();
+        assert_eq!(errors, vec![ProcessErrorKind::Corrupt]);
+        assert_eq!(read_report(&outputs).processors.len(), 2);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_reader_streams() -> anyhow::Result<()> {
+        let mut registry = ProcessorRegistry::empty();
+        registry
+            .register("*/*", ProcessType::Text, 0, CopyingProcessor)
+            .register("*/*", ProcessType::Metadata, 0, CopyingProcessor);
+        let processor = ProcessorBuilder::new().registry(registry).max_concurrency(NonZeroUsize::MIN).build();
+
+        let path = Path::new("../resources/jpg/PA280041.JPG");
+        let (output_sink, outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("image/jpeg", vec![ProcessType::Text, ProcessType::Metadata], output_sink).build();
+
+        processor.process_reader(ctx, tokio::fs::File::open(path).await?, None).await?;
+        let outputs = collect(outputs).await;
+
+        assert_eq!(processed_names(&outputs), vec!["streamed", "streamed"]);
+        let checksum = dedupe_checksum_from_path(path, "image/jpeg").await?;
+        for output in outputs.iter().filter_map(|output| output.as_ref().ok()) {
+            if let ProcessOutput::Processed(_, data) = output {
+                assert_eq!(data.checksum, checksum);
+                assert_eq!(std::fs::read(&data.path)?, std::fs::read(path)?);
+            }
+        }
+        assert_eq!(read_report(&outputs).checksum, Some(checksum));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_reader_spools() -> anyhow::Result<()> {
+        let mut registry = ProcessorRegistry::empty();
+        registry
+            .register("*/*", ProcessType::Text, 0, CopyingProcessor)
+            .register("*/*", ProcessType::Metadata, 0, SleepingProcessor("fast", Duration::ZERO));
+        let processor = ProcessorBuilder::new().registry(registry).build();
+
+        let path = Path::new("../resources/jpg/PA280041.JPG");
+        let (output_sink, outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("image/jpeg", vec![ProcessType::Text, ProcessType::Metadata], output_sink).build();
+
+        processor.process_reader(ctx, tokio::fs::File::open(path).await?, None).await?;
+        let outputs = collect(outputs).await;
+
+        let mut names = processed_names(&outputs);
+        names.sort();
+        assert_eq!(names, vec!["copy", "fast"]);
+        assert_eq!(read_report(&outputs).checksum, Some(dedupe_checksum_from_path(path, "image/jpeg").await?));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_reader_too_large() {
+        let mut registry = ProcessorRegistry::empty();
+        registry.register("*/*", ProcessType::Text, 0, CopyingProcessor);
+        let processor = ProcessorBuilder::new().registry(registry).max_input_size(1000).build();
+
+        let path = Path::new("../resources/jpg/PA280041.JPG");
+        let (output_sink, outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("image/jpeg", vec![ProcessType::Text], output_sink).build();
+
+        let err = processor.process_reader(ctx, tokio::fs::File::open(path).await.unwrap(), None).await.unwrap_err();
+
+        assert_eq!(err.kind, ProcessErrorKind::LimitExceeded);
+        let report = read_report(&collect(outputs).await);
+        assert_eq!(report.error.map(|err| err.kind), Some(ProcessErrorKind::LimitExceeded));
+    }
+
+    #[tokio::test]
+    async fn test_process_recursive() -> anyhow::Result<()> {
+        let mut registry = ProcessorRegistry::empty();
+        registry
+            .register("*/*", ProcessType::Text, 0, SleepingProcessor("fast", Duration::ZERO))
+            .register("*/*", ProcessType::Embedded, 0, NestingProcessor);
+        let processor = ProcessorBuilder::new().registry(registry).max_depth(2).build();
+
+        let input_path = crate::processing::default_config().temp_file()?.into_temp_path();
+        std::fs::write(&input_path, "level 0")?;
+        let (output_sink, outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new(
+            "text/plain",
+            vec![ProcessType::Text, ProcessType::Embedded],
+            output_sink,
+        ).build();
+
+        let processing = tokio::spawn(async move {
+            processor.process_recursive(ctx, input_path.to_path_buf()).await
+        });
+
+        // Embedded outputs hold a sender of the channel, so they are dropped as they are received
+        let mut outputs = outputs;
+        let mut processed_depths = vec![];
+        let mut embedded = vec![];
+        let mut skipped = vec![];
+        while let Some(output) = outputs.recv().await {
+            match output? {
+                ProcessOutput::Processed(state, _) => processed_depths.push(state.depth()),
+                ProcessOutput::Embedded(state, data, _) => embedded.push((state.depth(), data.name)),
+                ProcessOutput::Skipped(state, data) => skipped.push((state.depth(), data.reason)),
+                ProcessOutput::Report(_, _) | ProcessOutput::Progress(_, _) => (),
+            }
+        }
+
+        processing.await??;
+
+        processed_depths.sort();
+        assert_eq!(processed_depths, vec![0, 1, 2]);
+        embedded.sort();
+        assert_eq!(embedded, vec![(0, "level-1.txt".to_string()), (1, "level-2.txt".to_string())]);
+        skipped.sort_by_key(|(depth, _)| *depth);
+        assert_eq!(skipped.len(), 4);
+        assert!(matches!(skipped[0], (0, SkipReason::Duplicate(_))));
+        assert!(matches!(skipped[1], (1, SkipReason::Duplicate(_))));
+        assert_eq!(skipped[2..], [(2, SkipReason::MaxDepth(2)), (2, SkipReason::MaxDepth(2))]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_recursive_threads() -> anyhow::Result<()> {
+        let mut registry = ProcessorRegistry::empty();
+        registry
+            .register("application/mbox", ProcessType::Threads, 0, crate::threading::MboxThreadsProcessor::default())
+            .register("application/mbox", ProcessType::Embedded, 0, crate::embedded::MboxEmbeddedProcessor);
+        let processor = ProcessorBuilder::new().registry(registry).build();
+
+        let (output_sink, outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new(
+            "application/mbox",
+            vec![ProcessType::Threads, ProcessType::Embedded],
+            output_sink,
+        ).build();
+
+        let processing = tokio::spawn(async move {
+            processor.process_recursive(ctx, PathBuf::from("../resources/mbox/ubuntu-no-small.mbox")).await
+        });
+
+        let mut outputs = outputs;
+        let mut threads = vec![];
+        while let Some(output) = outputs.recv().await {
+            if let ProcessOutput::Processed(state, data) = output? {
+                assert!(state.id_chain.is_empty());
+                assert_eq!(data.kind, OutputKind::Threads);
+                let content: crate::threading::Threads = serde_json::from_reader(std::fs::File::open(&data.path)?)?;
+                threads.push((data.name, content));
+            }
+        }
+        processing.await??;
+
+        threads.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(threads.len(), 2);
+        assert_eq!(threads[0].0, "threads.json");
+        assert_eq!(threads[1].0, "tree-threads.json");
+        assert_eq!(threads[0].1, threads[1].1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_process_progress() -> anyhow::Result<()> {
+        let mut registry = ProcessorRegistry::empty();
+        registry.register("*/*", ProcessType::Text, 0, SleepingProcessor("fast", Duration::ZERO));
+        let processor = ProcessorBuilder::new().registry(registry).build();
+
+        let (output_sink, outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("image/jpeg", vec![ProcessType::Text], output_sink)
+            .progress(true)
+            .build();
+
+        processor.process(ctx, PathBuf::from("../resources/jpg/PA280041.JPG")).await?;
+        let outputs = collect(outputs).await;
+
+        match outputs.first() {
+            Some(Ok(ProcessOutput::Progress(_, progress))) => assert_eq!(progress.processor.as_deref(), Some("fast")),
+            _ => panic!("Expected progress as the first output"),
+        }
+        assert_eq!(read_report(&outputs).processors[0].outputs.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_detect_mimetype() {
+        let processor = Processor::default();
+        let (output_sink, _) = tokio::sync::mpsc::channel(1);
+        let path = Path::new("../resources/jpg/PA280041.JPG");
+
+        let ctx = ProcessContextBuilder::new("image/jpeg", vec![], output_sink.clone()).build();
+        let ctx = processor.detect_mimetype(ctx, path).await;
+        assert_eq!(ctx.mimetype, "image/jpeg");
+        assert_eq!(ctx.state.detected_mimetype, None);
+
+        let ctx = ProcessContextBuilder::new("", vec![], output_sink).build();
+        let ctx = processor.detect_mimetype(ctx, path).await;
+        match ctx.state.detected_mimetype {
+            Some(detected) => assert_eq!(detected.mimetype, ctx.mimetype),
+            None => assert_eq!(ctx.mimetype, "application/octet-stream"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_process_input_too_large() {
+        let processor = ProcessorBuilder::new().max_input_size(1).build();
+        let (output_sink, outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("image/jpeg", vec![ProcessType::Metadata], output_sink).build();
+
+        let err = processor.process(ctx, PathBuf::from("../resources/jpg/PA280041.JPG")).await.unwrap_err();
+
+        assert_eq!(err.kind, ProcessErrorKind::LimitExceeded);
+        assert_eq!(err.mimetype, "image/jpeg");
+
+        let report = read_report(&collect(outputs).await);
+        assert!(report.processors.is_empty());
+        assert_eq!(report.error.map(|err| err.kind), Some(ProcessErrorKind::LimitExceeded));
+    }
+
+    #[tokio::test]
+    async fn test_process_cancelled() {
+        let processor = sleeping_processor(Duration::from_secs(60));
+        let (output_sink, outputs) = tokio::sync::mpsc::channel(10);
+        let cancellation = CancellationToken::new();
+        let ctx = ProcessContextBuilder::new("image/jpeg", vec![ProcessType::Metadata], output_sink)
+            .cancellation_token(cancellation.clone())
+            .build();
+
+        let processing = tokio::spawn(async move {
+            processor.process(ctx, PathBuf::from("../resources/jpg/PA280041.JPG")).await
+        });
+        cancellation.cancel();
+
+        let result = processing.await.unwrap();
+        assert_eq!(result.unwrap_err().kind, ProcessErrorKind::Cancelled);
+
+        let outputs = collect(outputs).await;
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(read_report(&outputs).error.map(|err| err.kind), Some(ProcessErrorKind::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn test_child_context_cancelled_by_parent() {
+        let (output_sink, _outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("application/zip", vec![], output_sink).build();
+        let child = ctx.child();
+
+        child.cancellation_token().cancel();
+        assert!(!ctx.is_cancelled());
+
+        let child = ctx.child();
+        ctx.cancellation_token().cancel();
+        assert!(child.is_cancelled());
+    }
+
+    #[tokio::test]
+    async fn test_process_reuses_rendered_pdf() -> anyhow::Result<()> {
+        for types in [vec![ProcessType::Pdf, ProcessType::Image], vec![ProcessType::Image]] {
+            let renders = Arc::new(AtomicUsize::new(0));
+            let mut registry = ProcessorRegistry::empty();
+            registry
+                .register("*/*", ProcessType::Pdf, 0, CountingPdfProcessor(renders.clone()))
+                .register("*/*", ProcessType::Image, 0, PageProcessor(renders.clone()));
+            let processor = ProcessorBuilder::new().registry(registry).max_concurrency(NonZeroUsize::MIN).build();
+
+            let path = Path::new("../resources/jpg/PA280041.JPG");
+            let (output_sink, outputs) = tokio::sync::mpsc::channel(10);
+            let ctx = ProcessContextBuilder::new("image/jpeg", types.clone(), output_sink).build();
+            processor.process(ctx, path.to_path_buf()).await?;
+            let outputs = collect(outputs).await;
+
+            assert_eq!(renders.load(Ordering::SeqCst), 1, "{:?}", types);
+            for output in outputs.iter().filter_map(|output| output.as_ref().ok()) {
+                if let ProcessOutput::Processed(_, data) = output {
+                    assert_eq!(std::fs::read(&data.path)?, std::fs::read(path)?);
+                }
+            }
+            let mut names = processed_names(&outputs);
+            names.sort();
+            let expected = match types.len() {
+                2 => vec!["page-1.png", "rendered.pdf"],
+                _ => vec!["page-1.png"],
+            };
+            assert_eq!(names, expected);
+        }
+        Ok(())
+    }
+}
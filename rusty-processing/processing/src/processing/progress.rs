@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+
+/// Progress of a processor working through a file, sent as a [`crate::processing::ProcessOutput::Progress`] output.
+///
+/// Processors report whichever measure of progress they know, e.g. the bytes read of a stream of messages, or the
+/// entries enumerated of an archive with a known number of entries. A progress without any measure is sent when a
+/// processor starts.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Progress {
+    /// The name of the processor reporting the progress.
+    ///
+    pub processor: Option<String>,
+
+    /// The number of bytes of the file read so far.
+    ///
+    pub bytes_read: Option<u64>,
+
+    /// The size of the file, if known.
+    ///
+    pub total_bytes: Option<u64>,
+
+    /// The number of entries of the file enumerated so far, such as files in an archive.
+    ///
+    pub entries: Option<u64>,
+
+    /// The number of entries in the file, if known.
+    ///
+    pub total_entries: Option<u64>,
+}
+
+impl Progress {
+    /// Creates a progress of the bytes read of the file.
+    ///
+    pub fn bytes(bytes_read: u64, total_bytes: Option<u64>) -> Self {
+        Self {
+            bytes_read: Some(bytes_read),
+            total_bytes,
+            ..Self::default()
+        }
+    }
+
+    /// Creates a progress of the entries enumerated of the file.
+    ///
+    pub fn entries(entries: u64, total_entries: Option<u64>) -> Self {
+        Self {
+            entries: Some(entries),
+            total_entries,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the fraction of the file processed, between 0 and 1, if the total is known.
+    ///
+    /// Entries are preferred over bytes, as they are the more accurate measure when both are known.
+    ///
+    pub fn fraction(&self) -> Option<f64> {
+        let (done, total) = match (self.entries, self.total_entries, self.bytes_read, self.total_bytes) {
+            (Some(entries), Some(total_entries), _, _) => (entries, total_entries),
+            (_, _, Some(bytes_read), Some(total_bytes)) => (bytes_read, total_bytes),
+            _ => return None,
+        };
+        match total {
+            0 => Some(1.0),
+            total => Some((done as f64 / total as f64).min(1.0)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fraction() {
+        assert_eq!(Progress::default().fraction(), None);
+        assert_eq!(Progress::bytes(10, None).fraction(), None);
+        assert_eq!(Progress::bytes(10, Some(40)).fraction(), Some(0.25));
+        assert_eq!(Progress::bytes(50, Some(40)).fraction(), Some(1.0));
+        assert_eq!(Progress::entries(0, Some(0)).fraction(), Some(1.0));
+
+        let progress = Progress {
+            entries: Some(1),
+            total_entries: Some(2),
+            ..Progress::bytes(10, Some(40))
+        };
+        assert_eq!(progress.fraction(), Some(0.5));
+    }
+}
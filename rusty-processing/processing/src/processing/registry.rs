@@ -0,0 +1,490 @@
+use std::fmt;
+use std::fmt::Formatter;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::processing::{Process, ProcessType};
+
+/// The priority given to all processors registered by default.
+///
+/// Registering a processor with a higher priority for the same MIME type and [`ProcessType`] will take precedence over
+/// the built-in processor.
+///
+pub const BUILTIN_PRIORITY: i32 = 0;
+
+/// The priority given to built-in processors that only run when the processor before them in the fallback chain fails.
+///
+pub const BUILTIN_FALLBACK_PRIORITY: i32 = -1;
+
+/// Text MIME types with markup, which are left to Tika instead of being read as plain text.
+///
+const MARKUP_TEXT_MIMETYPES: [&str; 5] = ["text/html", "text/xml", "text/rtf", "text/richtext", "text/enriched"];
+
+/// A pattern used to match MIME types against registered processors.
+///
+/// Patterns are matched case-insensitively and ignore any parameters on the MIME type (e.g. `; charset=utf-8`).
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum MimePattern {
+    /// Matches a single MIME type exactly, e.g. `message/rfc822`.
+    ///
+    Exact(String),
+
+    /// Matches all MIME types of a top-level type, e.g. `image/*`.
+    ///
+    Type(String),
+
+    /// Matches every MIME type, i.e. `*` or `*/*`.
+    ///
+    Any,
+}
+
+impl MimePattern {
+    /// Returns whether the given MIME type matches this pattern.
+    ///
+    pub fn matches(&self, mimetype: &str) -> bool {
+        let mimetype = essence(mimetype);
+        match self {
+            MimePattern::Exact(exact) => *exact == mimetype,
+            MimePattern::Type(ctype) => mimetype
+                .split_once('/')
+                .is_some_and(|(mime_ctype, _)| mime_ctype == ctype),
+            MimePattern::Any => true,
+        }
+    }
+
+    /// How specific this pattern is, where a larger value is more specific.
+    ///
+    /// Used to resolve registrations of equal priority.
+    ///
+    fn specificity(&self) -> u8 {
+        match self {
+            MimePattern::Exact(_) => 2,
+            MimePattern::Type(_) => 1,
+            MimePattern::Any => 0,
+        }
+    }
+}
+
+impl From<&str> for MimePattern {
+    fn from(pattern: &str) -> Self {
+        let pattern = essence(pattern);
+        match pattern.as_str() {
+            "*" | "*/*" => MimePattern::Any,
+            _ => match pattern.strip_suffix("/*") {
+                Some(ctype) => MimePattern::Type(ctype.to_string()),
+                None => MimePattern::Exact(pattern),
+            }
+        }
+    }
+}
+
+impl From<String> for MimePattern {
+    fn from(pattern: String) -> Self {
+        MimePattern::from(pattern.as_str())
+    }
+}
+
+impl FromStr for MimePattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(MimePattern::from(s))
+    }
+}
+
+impl fmt::Display for MimePattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            MimePattern::Exact(exact) => write!(f, "{}", exact),
+            MimePattern::Type(ctype) => write!(f, "{}/*", ctype),
+            MimePattern::Any => write!(f, "*/*"),
+        }
+    }
+}
+
+/// A single entry in the registry.
+///
+/// An entry without a processor is an exclusion, which stops lookups from falling through to lower priority entries.
+///
+#[derive(Clone)]
+struct Registration {
+    pattern: MimePattern,
+    process_type: ProcessType,
+    priority: i32,
+    processor: Option<Arc<dyn Process>>,
+}
+
+/// Registry of [`Process`] implementations keyed by MIME type pattern and [`ProcessType`].
+///
+/// The default registry contains all processors built into this library. Downstream crates can add their own
+/// processors, either for new MIME types or to take precedence over the built-in ones by using a higher priority.
+///
+/// When looking up processors for a MIME type, matching registrations are ordered by priority (highest first), then by
+/// how specific their pattern is, and then by the order they were registered in (latest first).
+///
+#[derive(Clone)]
+pub struct ProcessorRegistry {
+    registrations: Vec<Registration>,
+}
+
+impl ProcessorRegistry {
+    /// Creates a registry with no registered processors.
+    ///
+    pub fn empty() -> Self {
+        Self { registrations: vec![] }
+    }
+
+    /// Registers a processor.
+    ///
+    /// # Arguments
+    ///
+    /// * `pattern` - The MIME type or MIME glob (e.g. `image/*`) the processor handles.
+    /// * `process_type` - The type of output the processor generates.
+    /// * `priority` - The priority of the processor over others matching the same MIME type and process type.
+    /// * `processor` - The processor implementation.
+    ///
+    pub fn register(
+        &mut self,
+        pattern: impl Into<MimePattern>,
+        process_type: ProcessType,
+        priority: i32,
+        processor: impl Process + 'static,
+    ) -> &mut Self {
+        self.registrations.push(Registration {
+            pattern: pattern.into(),
+            process_type,
+            priority,
+            processor: Some(Arc::new(processor)),
+        });
+        self
+    }
+
+    /// Excludes MIME types matching the pattern from being processed for the process type.
+    ///
+    /// Only processors registered with a lower priority (or equal priority and a less specific pattern) are excluded.
+    ///
+    pub fn exclude(
+        &mut self,
+        pattern: impl Into<MimePattern>,
+        process_type: ProcessType,
+        priority: i32,
+    ) -> &mut Self {
+        self.registrations.push(Registration {
+            pattern: pattern.into(),
+            process_type,
+            priority,
+            processor: None,
+        });
+        self
+    }
+
+    /// Finds all processors for the MIME type and process type, ordered by precedence.
+    ///
+    pub fn lookup(&self, mimetype: &str, process_type: &ProcessType) -> Vec<Arc<dyn Process>> {
+        let mut matches = self.registrations.iter()
+            .enumerate()
+            .filter(|(_, reg)| reg.process_type == *process_type && reg.pattern.matches(mimetype))
+            .collect::<Vec<(usize, &Registration)>>();
+
+        matches.sort_by(|(i0, r0), (i1, r1)| {
+            r1.priority.cmp(&r0.priority)
+                .then(r1.pattern.specificity().cmp(&r0.pattern.specificity()))
+                .then(i1.cmp(i0))
+        });
+
+        matches.into_iter()
+            .map(|(_, reg)| reg.processor.clone())
+            .take_while(Option::is_some)
+            .flatten()
+            .collect()
+    }
+}
+
+impl Default for ProcessorRegistry {
+    /// Creates a registry containing the built-in processors.
+    ///
+    fn default() -> Self {
+        let mut registry = Self::empty();
+
+        registry
+            .register("*/*", ProcessType::Text, BUILTIN_PRIORITY, crate::text::DefaultTextProcessor)
+            .exclude("application/zip", ProcessType::Text, BUILTIN_PRIORITY)
+            .exclude("application/mbox", ProcessType::Text, BUILTIN_PRIORITY)
+            // Text files never need Tika, so the exclusion stops them from falling back to it
+            .exclude("text/*", ProcessType::Text, BUILTIN_PRIORITY)
+            .register("text/*", ProcessType::Text, BUILTIN_PRIORITY, crate::text::PlainTextProcessor)
+            .register("text/*", ProcessType::Text, BUILTIN_PRIORITY, crate::text::NativeTextProcessor);
+
+        registry
+            .register("message/rfc822", ProcessType::Text, BUILTIN_PRIORITY, crate::text::Rfc822TextProcessor::default());
+
+        registry
+            .register("*/*", ProcessType::Metadata, BUILTIN_PRIORITY, crate::metadata::DefaultMetadataProcessor)
+            .register("text/*", ProcessType::Metadata, BUILTIN_PRIORITY, crate::metadata::TextMetadataProcessor)
+            .register("message/rfc822", ProcessType::Metadata, BUILTIN_PRIORITY, crate::metadata::Rfc822MetadataProcessor::default())
+            .register("application/mbox", ProcessType::Metadata, BUILTIN_PRIORITY, crate::metadata::MboxMetadataProcessor::default());
+
+        // The exclusions stop the native text processors from being used as fallbacks for markup, and are registered
+        // before the Tika processors so they take precedence over them.
+        for mimetype in MARKUP_TEXT_MIMETYPES {
+            registry
+                .exclude(mimetype, ProcessType::Text, BUILTIN_PRIORITY)
+                .register(mimetype, ProcessType::Text, BUILTIN_PRIORITY, crate::text::DefaultTextProcessor)
+                .exclude(mimetype, ProcessType::Metadata, BUILTIN_PRIORITY)
+                .register(mimetype, ProcessType::Metadata, BUILTIN_PRIORITY, crate::metadata::DefaultMetadataProcessor)
+                .exclude(mimetype, ProcessType::Entities, BUILTIN_PRIORITY)
+                .register(
+                    mimetype,
+                    ProcessType::Entities,
+                    BUILTIN_PRIORITY,
+                    crate::entities::EntitiesProcessor::scanning(crate::text::DefaultTextProcessor),
+                )
+                .exclude(mimetype, ProcessType::Redaction, BUILTIN_PRIORITY)
+                .register(
+                    mimetype,
+                    ProcessType::Redaction,
+                    BUILTIN_PRIORITY,
+                    crate::redaction::RedactionProcessor::scanning(crate::text::DefaultTextProcessor),
+                );
+        }
+
+        for mimetype in ["text/html", "application/xhtml+xml"] {
+            registry
+                .register(mimetype, ProcessType::Text, BUILTIN_PRIORITY, crate::text::HtmlTextProcessor)
+                .register(mimetype, ProcessType::Metadata, BUILTIN_PRIORITY, crate::metadata::HtmlMetadataProcessor);
+        }
+
+        registry
+            .register("message/rfc822", ProcessType::Pdf, BUILTIN_PRIORITY, crate::pdf::Rfc822PdfProcessor::default())
+            .register("message/rfc822", ProcessType::Pdf, BUILTIN_FALLBACK_PRIORITY, crate::pdf::Rfc822TextPdfProcessor::default());
+
+        registry
+            .register("application/pdf", ProcessType::Image, BUILTIN_PRIORITY, crate::image::PageImageProcessor::default())
+            .register(
+                "message/rfc822",
+                ProcessType::Image,
+                BUILTIN_PRIORITY,
+                crate::image::PageImageProcessor::rendering(crate::pdf::Rfc822PdfProcessor::default()),
+            )
+            .register(
+                "message/rfc822",
+                ProcessType::Image,
+                BUILTIN_FALLBACK_PRIORITY,
+                crate::image::PageImageProcessor::rendering(crate::pdf::Rfc822TextPdfProcessor::default()),
+            );
+
+        registry
+            .register("application/pdf", ProcessType::Ocr, BUILTIN_PRIORITY, crate::ocr::OcrProcessor::rendering_pages())
+            .register("image/jpeg", ProcessType::Ocr, BUILTIN_PRIORITY, crate::ocr::OcrProcessor::default())
+            .register("image/png", ProcessType::Ocr, BUILTIN_PRIORITY, crate::ocr::OcrProcessor::default())
+            .register("image/tiff", ProcessType::Ocr, BUILTIN_PRIORITY, crate::ocr::OcrProcessor::default())
+            .register("image/bmp", ProcessType::Ocr, BUILTIN_PRIORITY, crate::ocr::OcrProcessor::default());
+
+        registry
+            .register("*/*", ProcessType::Hashes, BUILTIN_PRIORITY, crate::hashes::HashesProcessor);
+
+        registry
+            .register(
+                "*/*",
+                ProcessType::Entities,
+                BUILTIN_PRIORITY,
+                crate::entities::EntitiesProcessor::scanning(crate::text::DefaultTextProcessor),
+            )
+            .exclude("application/zip", ProcessType::Entities, BUILTIN_PRIORITY)
+            .exclude("application/mbox", ProcessType::Entities, BUILTIN_PRIORITY)
+            .register(
+                "text/*",
+                ProcessType::Entities,
+                BUILTIN_PRIORITY,
+                crate::entities::EntitiesProcessor::scanning(crate::text::NativeTextProcessor),
+            )
+            .register("message/rfc822", ProcessType::Entities, BUILTIN_PRIORITY, crate::entities::Rfc822EntitiesProcessor::default());
+
+        registry
+            .register(
+                "*/*",
+                ProcessType::Redaction,
+                BUILTIN_PRIORITY,
+                crate::redaction::RedactionProcessor::scanning(crate::text::DefaultTextProcessor),
+            )
+            .exclude("application/zip", ProcessType::Redaction, BUILTIN_PRIORITY)
+            .exclude("application/mbox", ProcessType::Redaction, BUILTIN_PRIORITY)
+            .register(
+                "text/*",
+                ProcessType::Redaction,
+                BUILTIN_PRIORITY,
+                crate::redaction::RedactionProcessor::scanning(crate::text::NativeTextProcessor),
+            )
+            .register(
+                "message/rfc822",
+                ProcessType::Redaction,
+                BUILTIN_PRIORITY,
+                crate::redaction::RedactionProcessor::scanning(crate::text::Rfc822TextProcessor::default())
+                    .rendering(crate::pdf::Rfc822RedactedPdfProcessor::default()),
+            );
+
+        registry
+            .register("application/mbox", ProcessType::Threads, BUILTIN_PRIORITY, crate::threading::MboxThreadsProcessor::default());
+
+        registry
+            .register("application/zip", ProcessType::Embedded, BUILTIN_PRIORITY, crate::embedded::ZipEmbeddedProcessor)
+            .register("application/mbox", ProcessType::Embedded, BUILTIN_PRIORITY, crate::embedded::MboxEmbeddedProcessor)
+            .register("message/rfc822", ProcessType::Embedded, BUILTIN_PRIORITY, crate::embedded::Rfc822EmbeddedProcessor::default());
+
+        registry
+    }
+}
+
+impl fmt::Debug for ProcessorRegistry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut list = f.debug_list();
+        for reg in &self.registrations {
+            let name = reg.processor.as_ref().map(|processor| processor.name()).unwrap_or("<excluded>");
+            list.entry(&format_args!("({}, {:?}, {}, {})", reg.pattern, reg.process_type, reg.priority, name));
+        }
+        list.finish()
+    }
+}
+
+/// Returns the MIME type without parameters, trimmed and lowercased.
+///
+fn essence(mimetype: &str) -> String {
+    mimetype
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use async_trait::async_trait;
+    use tempfile::TempPath;
+
+    use crate::processing::{ProcessContext, ProcessError};
+
+    use super::*;
+
+    struct NamedProcessor(&'static str);
+
+    #[async_trait]
+    impl Process for NamedProcessor {
+        async fn process(&self, _: ProcessContext, _: &Path, _: TempPath, _: &str) -> Result<(), ProcessError> {
+            Ok(())
+        }
+
+        fn name(&self) -> &'static str {
+            self.0
+        }
+    }
+
+    fn names(processors: Vec<Arc<dyn Process>>) -> Vec<&'static str> {
+        processors.iter().map(|processor| processor.name()).collect()
+    }
+
+    #[test]
+    fn test_mime_pattern_from_str() {
+        assert_eq!(MimePattern::from("*"), MimePattern::Any);
+        assert_eq!(MimePattern::from("*/*"), MimePattern::Any);
+        assert_eq!(MimePattern::from("Image/*"), MimePattern::Type("image".to_string()));
+        assert_eq!(MimePattern::from(" text/plain "), MimePattern::Exact("text/plain".to_string()));
+    }
+
+    #[test]
+    fn test_mime_pattern_matches() {
+        let cases = vec![
+            ("message/rfc822", "message/rfc822", true),
+            ("message/rfc822", "MESSAGE/RFC822", true),
+            ("text/plain", "text/plain; charset=utf-8", true),
+            ("text/plain", "text/html", false),
+            ("image/*", "image/jpeg", true),
+            ("image/*", "images/jpeg", false),
+            ("*/*", "application/x-in-house", true),
+        ];
+
+        for (pattern, mimetype, expected) in cases {
+            assert_eq!(MimePattern::from(pattern).matches(mimetype), expected, "{} ~ {}", pattern, mimetype);
+        }
+    }
+
+    #[test]
+    fn test_lookup_orders_by_priority_then_specificity() {
+        let mut registry = ProcessorRegistry::empty();
+        registry
+            .register("*/*", ProcessType::Text, 0, NamedProcessor("any"))
+            .register("image/*", ProcessType::Text, 0, NamedProcessor("image"))
+            .register("image/jpeg", ProcessType::Text, 0, NamedProcessor("jpeg"))
+            .register("*/*", ProcessType::Text, 10, NamedProcessor("priority"))
+            .register("image/jpeg", ProcessType::Metadata, 0, NamedProcessor("metadata"));
+
+        let processors = registry.lookup("image/jpeg", &ProcessType::Text);
+
+        assert_eq!(names(processors), vec!["priority", "jpeg", "image", "any"]);
+    }
+
+    #[test]
+    fn test_lookup_prefers_latest_registration() {
+        let mut registry = ProcessorRegistry::empty();
+        registry
+            .register("application/x-in-house", ProcessType::Embedded, 0, NamedProcessor("first"))
+            .register("application/x-in-house", ProcessType::Embedded, 0, NamedProcessor("second"));
+
+        let processors = registry.lookup("application/x-in-house", &ProcessType::Embedded);
+
+        assert_eq!(names(processors), vec!["second", "first"]);
+    }
+
+    #[test]
+    fn test_lookup_stops_at_exclusion() {
+        let mut registry = ProcessorRegistry::empty();
+        registry
+            .register("*/*", ProcessType::Text, 0, NamedProcessor("any"))
+            .exclude("application/zip", ProcessType::Text, 0);
+
+        assert!(registry.lookup("application/zip", &ProcessType::Text).is_empty());
+        assert_eq!(names(registry.lookup("application/pdf", &ProcessType::Text)), vec!["any"]);
+
+        registry.register("application/zip", ProcessType::Text, 1, NamedProcessor("zip"));
+        assert_eq!(names(registry.lookup("application/zip", &ProcessType::Text)), vec!["zip"]);
+    }
+
+    #[test]
+    fn test_default_registry() {
+        let registry = ProcessorRegistry::default();
+
+        assert_eq!(names(registry.lookup("message/rfc822", &ProcessType::Pdf)), vec!["RFC 822 PDF", "RFC 822 Text PDF"]);
+        assert_eq!(names(registry.lookup("text/plain", &ProcessType::Text)), vec!["Native Text", "Plain Text"]);
+        assert_eq!(names(registry.lookup("text/html", &ProcessType::Text)), vec!["HTML Text", "Default Text"]);
+        assert_eq!(names(registry.lookup("application/xhtml+xml", &ProcessType::Text)), vec!["HTML Text", "Default Text"]);
+        assert_eq!(names(registry.lookup("text/xml", &ProcessType::Text)), vec!["Default Text"]);
+        assert_eq!(names(registry.lookup("text/plain", &ProcessType::Metadata)), vec!["Text Metadata", "Default Metadata"]);
+        assert_eq!(names(registry.lookup("text/html", &ProcessType::Metadata)), vec!["HTML Metadata", "Default Metadata"]);
+        assert_eq!(names(registry.lookup("message/rfc822", &ProcessType::Metadata)), vec!["RFC 822 Metadata", "Default Metadata"]);
+        assert_eq!(names(registry.lookup("text/css", &ProcessType::Text)), vec!["Native Text", "Plain Text"]);
+        assert_eq!(names(registry.lookup("message/rfc822", &ProcessType::Text)), vec!["RFC 822 Text", "Default Text"]);
+        assert_eq!(names(registry.lookup("application/zip", &ProcessType::Embedded)), vec!["zip"]);
+        assert!(registry.lookup("application/mbox", &ProcessType::Text).is_empty());
+        assert_eq!(names(registry.lookup("application/mbox", &ProcessType::Metadata)), vec!["Mbox Metadata", "Default Metadata"]);
+        assert!(registry.lookup("application/pdf", &ProcessType::Pdf).is_empty());
+        assert_eq!(names(registry.lookup("application/pdf", &ProcessType::Image)), vec!["Page Images"]);
+        assert_eq!(names(registry.lookup("message/rfc822", &ProcessType::Image)), vec!["Page Images", "Page Images"]);
+        assert!(registry.lookup("image/jpeg", &ProcessType::Image).is_empty());
+        assert_eq!(names(registry.lookup("image/jpeg", &ProcessType::Ocr)), vec!["OCR"]);
+        assert_eq!(names(registry.lookup("application/pdf", &ProcessType::Ocr)), vec!["OCR"]);
+        assert!(registry.lookup("message/rfc822", &ProcessType::Ocr).is_empty());
+        assert_eq!(names(registry.lookup("application/zip", &ProcessType::Hashes)), vec!["Hashes"]);
+        assert_eq!(names(registry.lookup("message/rfc822", &ProcessType::Entities)), vec!["RFC 822 Entities", "Entities"]);
+        assert_eq!(names(registry.lookup("text/plain", &ProcessType::Entities)), vec!["Entities", "Entities"]);
+        assert_eq!(names(registry.lookup("text/html", &ProcessType::Entities)), vec!["Entities"]);
+        assert_eq!(names(registry.lookup("text/csv", &ProcessType::Redaction)), vec!["Redaction", "Redaction"]);
+        assert!(registry.lookup("application/zip", &ProcessType::Entities).is_empty());
+        assert_eq!(names(registry.lookup("message/rfc822", &ProcessType::Redaction)), vec!["Redaction", "Redaction"]);
+        assert!(registry.lookup("application/mbox", &ProcessType::Redaction).is_empty());
+        assert_eq!(names(registry.lookup("application/mbox", &ProcessType::Threads)), vec!["Mbox Threads"]);
+        assert!(registry.lookup("message/rfc822", &ProcessType::Threads).is_empty());
+    }
+}
@@ -0,0 +1,344 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::processing::{ProcessContext, ProcessError, ProcessErrorKind, ProcessOutput, ProcessType, SkippedOutputData};
+
+/// Record of processing a single file, sent as `report.json` once all processors finished.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessReport {
+    /// The MIME type the file was processed as.
+    ///
+    pub mimetype: String,
+
+    /// The deduplication checksum of the file, or [`None`] if it could not be calculated.
+    ///
+    pub checksum: Option<String>,
+
+    /// The ID chain of the file.
+    ///
+    /// See `ProcessState.id_chain` for more information.
+    ///
+    pub id_chain: Vec<String>,
+
+    /// The processors that ran for the file, in the order of their process types.
+    ///
+    pub processors: Vec<ProcessorReport>,
+
+    /// The error that stopped the file from being processed, if any.
+    ///
+    pub error: Option<ReportError>,
+}
+
+/// Record of a single processor running for a file.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessorReport {
+    /// The name of the processor.
+    ///
+    pub name: String,
+
+    /// The type of output the processor was run for.
+    ///
+    pub process_type: ProcessType,
+
+    /// The position of the processor in the fallback chain of the process type, where 0 is the processor with the
+    /// highest precedence.
+    ///
+    pub fallback_level: usize,
+
+    /// Whether the processor succeeded.
+    ///
+    pub status: ProcessorStatus,
+
+    /// How long the processor ran for, in milliseconds.
+    ///
+    pub duration_ms: u64,
+
+    /// The files the processor created or discovered.
+    ///
+    pub outputs: Vec<ReportOutput>,
+
+    /// The embedded files the processor skipped.
+    ///
+    pub skipped: Vec<SkippedOutputData>,
+
+    /// The errors of the processor.
+    ///
+    pub errors: Vec<ReportError>,
+}
+
+/// The outcome of a processor.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessorStatus {
+    /// The processor finished without errors.
+    ///
+    Succeeded,
+
+    /// The processor had errors, but still produced outputs.
+    ///
+    PartiallySucceeded,
+
+    /// The processor had errors and produced no outputs.
+    ///
+    Failed,
+}
+
+/// A file created or discovered by a processor.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportOutput {
+    /// The name of the file.
+    ///
+    pub name: String,
+
+    /// Whether the file was created or discovered.
+    ///
+    pub kind: ReportOutputKind,
+
+    /// The MIME type of the file.
+    ///
+    pub mimetype: String,
+
+    /// The size of the file in bytes, or [`None`] if it could not be read.
+    ///
+    pub size: Option<u64>,
+
+    /// The deduplication checksum of the file.
+    ///
+    pub checksum: String,
+}
+
+/// Whether a file in a report was created or discovered.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportOutputKind {
+    /// A file created by processing, see [`ProcessOutput::Processed`].
+    ///
+    Processed,
+
+    /// A file embedded in the processed file, see [`ProcessOutput::Embedded`].
+    ///
+    Embedded,
+}
+
+/// An error in a report.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportError {
+    /// The class of failure.
+    ///
+    pub kind: ProcessErrorKind,
+
+    /// The error message, including its sources.
+    ///
+    pub message: String,
+}
+
+impl From<&ProcessError> for ReportError {
+    fn from(err: &ProcessError) -> Self {
+        let mut message = err.to_string();
+        let mut source = std::error::Error::source(err);
+        while let Some(err) = source {
+            message.push_str(&format!(": {}", err));
+            source = err.source();
+        }
+        Self { kind: err.kind, message }
+    }
+}
+
+impl ProcessReport {
+    /// Creates an empty report for the file being processed in the given context.
+    ///
+    pub(crate) fn new(ctx: &ProcessContext) -> Self {
+        Self {
+            mimetype: ctx.mimetype.clone(),
+            checksum: None,
+            id_chain: ctx.state.id_chain.clone(),
+            processors: vec![],
+            error: None,
+        }
+    }
+}
+
+/// Records the outputs sent by a processor through its [`ProcessContext`].
+///
+/// When the processor has a fallback, its errors are held back by the recorder instead of being sent, as they are only
+/// of interest if the fallback does not run.
+///
+#[derive(Debug, Default)]
+pub(crate) struct OutputRecorder {
+    record: Mutex<OutputRecord>,
+    defer_errors: bool,
+}
+
+#[derive(Debug, Default)]
+struct OutputRecord {
+    outputs: Vec<ReportOutput>,
+    skipped: Vec<SkippedOutputData>,
+    errors: Vec<ReportError>,
+    deferred: Vec<ProcessError>,
+}
+
+impl OutputRecorder {
+    /// Creates a recorder that holds back errors, see [`OutputRecorder::take_deferred`].
+    ///
+    pub fn deferring_errors() -> Self {
+        Self {
+            defer_errors: true,
+            ..Self::default()
+        }
+    }
+
+    /// Returns whether errors are held back instead of being sent.
+    ///
+    pub fn defers_errors(&self) -> bool {
+        self.defer_errors
+    }
+
+    /// Holds back an error that was recorded.
+    ///
+    pub fn defer(&self, err: ProcessError) {
+        self.record.lock().unwrap().deferred.push(err);
+    }
+
+    /// Takes the errors held back so far.
+    ///
+    pub fn take_deferred(&self) -> Vec<ProcessError> {
+        std::mem::take(&mut self.record.lock().unwrap().deferred)
+    }
+
+    /// Records an output about to be sent.
+    ///
+    pub fn record(&self, result: &Result<ProcessOutput, ProcessError>) {
+        let mut record = self.record.lock().unwrap();
+        match result {
+            Ok(ProcessOutput::Processed(_, data)) | Ok(ProcessOutput::Report(_, data)) => {
+                record.outputs.push(ReportOutput {
+                    name: data.name.clone(),
+                    kind: ReportOutputKind::Processed,
+                    mimetype: data.mimetype.clone(),
+                    size: std::fs::metadata(&data.path).ok().map(|metadata| metadata.len()),
+                    checksum: data.checksum.clone(),
+                });
+            }
+            Ok(ProcessOutput::Embedded(_, data, _)) => {
+                record.outputs.push(ReportOutput {
+                    name: data.name.clone(),
+                    kind: ReportOutputKind::Embedded,
+                    mimetype: data.mimetype.clone(),
+                    size: std::fs::metadata(&data.path).ok().map(|metadata| metadata.len()),
+                    checksum: data.checksum.clone(),
+                });
+            }
+            Ok(ProcessOutput::Skipped(_, data)) => record.skipped.push(data.clone()),
+            Ok(ProcessOutput::Progress(_, _)) => (),
+            Err(err) => record.errors.push(ReportError::from(err)),
+        }
+    }
+
+    /// Creates the report of the processor from the recorded outputs.
+    ///
+    pub fn report(
+        &self,
+        name: &str,
+        process_type: ProcessType,
+        fallback_level: usize,
+        duration: Duration,
+    ) -> ProcessorReport {
+        let record = {
+            let mut record = self.record.lock().unwrap();
+            let deferred = std::mem::take(&mut record.deferred);
+            std::mem::replace(&mut *record, OutputRecord { deferred, ..OutputRecord::default() })
+        };
+        let status = match (record.errors.is_empty(), record.outputs.is_empty()) {
+            (true, _) => ProcessorStatus::Succeeded,
+            (false, false) => ProcessorStatus::PartiallySucceeded,
+            (false, true) => ProcessorStatus::Failed,
+        };
+
+        ProcessorReport {
+            name: name.to_string(),
+            process_type,
+            fallback_level,
+            status,
+            duration_ms: duration.as_millis() as u64,
+            outputs: record.outputs,
+            skipped: record.skipped,
+            errors: record.errors,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use test_utils::temp_path;
+
+    use crate::processing::{ProcessContextBuilder, SkipReason};
+
+    use super::*;
+
+    #[test]
+    fn test_recorder() -> anyhow::Result<()> {
+        let (output_sink, _) = tokio::sync::mpsc::channel(1);
+        let ctx = ProcessContextBuilder::new("application/zip", vec![ProcessType::Embedded], output_sink).build();
+        let recorder = OutputRecorder::default();
+
+        let path = temp_path()?;
+        std::fs::write(&path, b"hello")?;
+        recorder.record(&Ok(ProcessOutput::embedded(&ctx, "hello.txt", path, "text/plain", "abc")));
+        recorder.record(&Ok(ProcessOutput::skipped(&ctx, Some("bomb.txt".to_string()), SkipReason::MaxEntries(1))));
+        recorder.record(&Err(ProcessError::new(ProcessErrorKind::Corrupt, anyhow!("bad entry"))));
+
+        let report = recorder.report("zip", ProcessType::Embedded, 0, Duration::from_millis(42));
+
+        assert_eq!(report.name, "zip");
+        assert_eq!(report.status, ProcessorStatus::PartiallySucceeded);
+        assert_eq!(report.duration_ms, 42);
+        assert_eq!(report.outputs, vec![ReportOutput {
+            name: "hello.txt".to_string(),
+            kind: ReportOutputKind::Embedded,
+            mimetype: "text/plain".to_string(),
+            size: Some(5),
+            checksum: "abc".to_string(),
+        }]);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.errors, vec![ReportError {
+            kind: ProcessErrorKind::Corrupt,
+            message: "corrupt file: bad entry".to_string(),
+        }]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_recorder_failed() {
+        let recorder = OutputRecorder::default();
+        recorder.record(&Err(ProcessError::new(ProcessErrorKind::ServiceUnavailable, anyhow!("down"))));
+
+        let report = recorder.report("Default Text", ProcessType::Text, 1, Duration::ZERO);
+
+        assert_eq!(report.status, ProcessorStatus::Failed);
+        assert_eq!(report.fallback_level, 1);
+    }
+
+    #[test]
+    fn test_recorder_deferring_errors() {
+        let recorder = OutputRecorder::deferring_errors();
+        let err = ProcessError::new(ProcessErrorKind::ServiceUnavailable, anyhow!("down"));
+        recorder.record(&Err(ProcessError::new(err.kind, anyhow!("down"))));
+        recorder.defer(err);
+
+        let report = recorder.report("Default Text", ProcessType::Text, 0, Duration::ZERO);
+
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(recorder.take_deferred().len(), 1);
+        assert!(recorder.take_deferred().is_empty());
+    }
+}
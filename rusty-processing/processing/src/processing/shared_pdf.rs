@@ -0,0 +1,68 @@
+use std::fmt;
+use std::fmt::Formatter;
+use std::sync::{Arc, Mutex};
+
+use futures::future::{BoxFuture, Shared};
+use futures::FutureExt;
+use tempfile::TempPath;
+use tokio::sync::oneshot;
+
+/// The PDF a file is rendered to by the processors of the [`crate::processing::ProcessType::Pdf`], shared with the
+/// processors of the [`crate::processing::ProcessType::Image`] of the same file, so it is not rendered twice.
+///
+pub(crate) enum SharedPdf {
+    /// Shares the first PDF sent by the processors of the PDF type.
+    ///
+    Sending(Mutex<Option<oneshot::Sender<Arc<TempPath>>>>),
+
+    /// Receives the shared PDF, or [`None`] if the processors of the PDF type finished without one.
+    ///
+    Receiving(Shared<BoxFuture<'static, Option<Arc<TempPath>>>>),
+}
+
+impl SharedPdf {
+    /// Creates the sending and the receiving end of a shared PDF.
+    ///
+    pub(crate) fn new() -> (Self, Self) {
+        let (sender, receiver) = oneshot::channel();
+        let receiver = receiver.map(Result::ok).boxed().shared();
+        (Self::Sending(Mutex::new(Some(sender))), Self::Receiving(receiver))
+    }
+
+    /// Shares a copy of the PDF, unless one was shared already.
+    ///
+    pub(crate) fn send(&self, pdf: TempPath) {
+        if let Self::Sending(sender) = self {
+            if let Some(sender) = sender.lock().unwrap().take() {
+                let _ = sender.send(Arc::new(pdf));
+            }
+        }
+    }
+
+    /// Returns whether a PDF is still to be shared through the sending end.
+    ///
+    pub(crate) fn is_waiting(&self) -> bool {
+        match self {
+            Self::Sending(sender) => sender.lock().unwrap().is_some(),
+            Self::Receiving(_) => false,
+        }
+    }
+
+    /// Waits for the shared PDF through the receiving end, returning [`None`] if none was shared.
+    ///
+    pub(crate) async fn receive(&self) -> Option<Arc<TempPath>> {
+        match self {
+            Self::Sending(_) => None,
+            Self::Receiving(receiver) => receiver.clone().await,
+        }
+    }
+}
+
+impl fmt::Debug for SharedPdf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Sending(_) => write!(f, "SharedPdf::Sending"),
+            Self::Receiving(_) => write!(f, "SharedPdf::Receiving"),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::io;
+
+use anyhow::anyhow;
+use bytes::Bytes;
+use futures::StreamExt;
+use tempfile::TempPath;
+use tokio::io::AsyncRead;
+use tokio::sync::mpsc::Sender;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::io::{ReaderStream, StreamReader};
+
+use crate::processing::{OutputKind, ProcessContext, ProcessError, ProcessErrorKind, ProcessOutput};
+
+/// The number of chunks buffered for each reader of a teed stream before the slowest reader holds up the others.
+///
+const TEE_BUFFER: usize = 16;
+
+/// A stream of the bytes of a file, given to [`crate::processing::Process::process_stream`].
+///
+pub type InputStream = Box<dyn AsyncRead + Send + Sync + Unpin>;
+
+/// A file created by [`crate::processing::Process::process_stream`].
+///
+/// It is sent as a [`crate::processing::ProcessOutput::Processed`] output once the checksum of the processed file is
+/// known.
+///
+#[derive(Debug)]
+pub struct StreamedOutput {
+    /// The name to give the file.
+    ///
+    pub name: String,
+
+    /// The created file.
+    ///
+    pub path: TempPath,
+
+    /// The MIME type of the created file.
+    ///
+    pub mimetype: String,
+
+    /// The kind of the created file, or [`None`] for the kind of the process type of the processor.
+    ///
+    pub kind: Option<OutputKind>,
+}
+
+impl StreamedOutput {
+    /// Creates the output of the created file with the given checksum.
+    ///
+    pub(crate) fn into_processed(self, ctx: &ProcessContext, checksum: &str) -> ProcessOutput {
+        let output = ProcessOutput::processed(ctx, self.name, self.path, self.mimetype, checksum);
+        match self.kind {
+            Some(kind) => output.with_kind(kind),
+            None => output,
+        }
+    }
+}
+
+/// Copies the reader into the given number of streams, reading it only once.
+///
+/// The streams are fed in lockstep, so a slow reader holds up the others rather than the input being buffered. Streams
+/// that are dropped are skipped, and reading stops once all of them are dropped.
+///
+/// Returns a task resolving to the number of bytes read, failing if reading the input fails or it exceeds the maximum
+/// size. The streams fail with an I/O error in that case, rather than ending early.
+///
+pub(crate) fn tee(
+    reader: impl AsyncRead + Send + Unpin + 'static,
+    count: usize,
+    max_size: Option<u64>,
+) -> (JoinHandle<Result<u64, ProcessError>>, Vec<InputStream>) {
+    let (senders, streams): (Vec<_>, Vec<_>) = (0..count)
+        .map(|_| {
+            let (sender, receiver) = tokio::sync::mpsc::channel::<io::Result<Bytes>>(TEE_BUFFER);
+            let stream: InputStream = Box::new(StreamReader::new(ReceiverStream::new(receiver)));
+            (sender, stream)
+        })
+        .unzip();
+
+    let task = tokio::spawn(async move {
+        let mut chunks = ReaderStream::new(reader);
+        let mut size = 0;
+        while let Some(chunk) = chunks.next().await {
+            let chunk = match chunk {
+                Ok(chunk) => chunk,
+                Err(err) => {
+                    fail(&senders, err.kind(), "failed to read input").await;
+                    return Err(ProcessError::from(err).context("failed to read input"));
+                }
+            };
+
+            size += chunk.len() as u64;
+            if let Some(max_size) = max_size.filter(|max_size| size > *max_size) {
+                fail(&senders, io::ErrorKind::InvalidInput, "input exceeds the maximum size").await;
+                return Err(ProcessError::new(
+                    ProcessErrorKind::LimitExceeded,
+                    anyhow!("input file size exceeds the maximum of {}", max_size),
+                ));
+            }
+
+            let mut open = false;
+            for sender in &senders {
+                open |= sender.send(Ok(chunk.clone())).await.is_ok();
+            }
+            if !open {
+                break;
+            }
+        }
+        Ok(size)
+    });
+
+    (task, streams)
+}
+
+/// Fails all streams still being read.
+///
+async fn fail(senders: &[Sender<io::Result<Bytes>>], kind: io::ErrorKind, message: &str) {
+    for sender in senders {
+        let _ = sender.send(Err(io::Error::new(kind, message.to_string()))).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_tee() -> anyhow::Result<()> {
+        let content = vec![7u8; 200_000];
+        let (task, streams) = tee(io::Cursor::new(content.clone()), 2, None);
+
+        let reads = streams.into_iter().map(|mut stream| async move {
+            let mut read = vec![];
+            stream.read_to_end(&mut read).await.map(|_| read)
+        });
+        let reads = futures::future::try_join_all(reads).await?;
+
+        assert_eq!(task.await??, 200_000);
+        assert_eq!(reads, vec![content.clone(), content]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tee_max_size() {
+        let (task, mut streams) = tee(io::Cursor::new(vec![7u8; 200_000]), 1, Some(1000));
+
+        let mut read = vec![];
+        let result = streams[0].read_to_end(&mut read).await;
+
+        assert!(result.is_err());
+        assert_eq!(task.await.unwrap().unwrap_err().kind, ProcessErrorKind::LimitExceeded);
+    }
+}
@@ -0,0 +1,269 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use tempfile::TempPath;
+
+use crate::entities::find_entities;
+use crate::image::render_pdf;
+use crate::processing::{EntityRule, Process, ProcessContext, ProcessError, ProcessErrorKind, ProcessOutput};
+
+/// The log of the redactions made in the extracted text of a file, written to `redactions.json`.
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RedactionLog {
+    /// The number of redactions of each entity type.
+    ///
+    pub counts: BTreeMap<String, usize>,
+
+    /// The redactions made, in the order they occur in the text.
+    ///
+    pub redactions: Vec<Redaction>,
+}
+
+/// An entity replaced with a placeholder in a text.
+///
+/// The entity itself is not recorded, so the log can be shared along with the redacted text.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Redaction {
+    /// The type of the entity, as given by the [`EntityRule`] that found it.
+    ///
+    #[serde(rename = "type")]
+    pub entity_type: String,
+
+    /// The placeholder the entity was replaced with, e.g. `[REDACTED EMAIL]`.
+    ///
+    pub placeholder: String,
+
+    /// The offset in characters of the start of the entity in the original text.
+    ///
+    pub start: usize,
+
+    /// The offset in characters of the end of the entity in the original text, exclusive.
+    ///
+    pub end: usize,
+
+    /// The offset in characters of the start of the placeholder in the redacted text.
+    ///
+    pub redacted_start: usize,
+
+    /// The offset in characters of the end of the placeholder in the redacted text, exclusive.
+    ///
+    pub redacted_end: usize,
+}
+
+/// Returns the placeholder entities of the type are replaced with, e.g. `[REDACTED CREDIT_CARD]`.
+///
+pub(crate) fn placeholder(entity_type: &str) -> String {
+    format!("[REDACTED {}]", entity_type.to_uppercase())
+}
+
+/// Replaces the entities the rules find in the text with placeholders, returning the redacted text and the redactions
+/// made.
+///
+/// Entities are found as for [`crate::processing::ProcessType::Entities`], so matches overlapping an entity found by
+/// an earlier rule are left to the earlier rule.
+///
+pub(crate) fn redact(rules: &[EntityRule], text: &str) -> (String, Vec<Redaction>) {
+    let mut redacted = String::with_capacity(text.len());
+    let mut redactions = vec![];
+    let (mut byte_offset, mut char_shift) = (0, 0isize);
+    for found in find_entities(rules, text) {
+        redacted.push_str(&text[byte_offset..found.bytes.start]);
+        byte_offset = found.bytes.end;
+
+        let placeholder = placeholder(found.entity_type);
+        let placeholder_chars = placeholder.chars().count();
+        let redacted_start = found.chars.start.saturating_add_signed(char_shift);
+        char_shift += placeholder_chars as isize - found.chars.len() as isize;
+        redacted.push_str(&placeholder);
+
+        redactions.push(Redaction {
+            entity_type: found.entity_type.to_string(),
+            placeholder,
+            start: found.chars.start,
+            end: found.chars.end,
+            redacted_start,
+            redacted_end: redacted_start + placeholder_chars,
+        });
+    }
+    redacted.push_str(&text[byte_offset..]);
+    (redacted, redactions)
+}
+
+/// Redacts the text extracted from a file by a processor of the [`crate::processing::ProcessType::Text`], replacing
+/// the entities found by the [`crate::processing::ProcessorConfig::redaction_rules`] with placeholders.
+///
+/// Creates `redacted.txt` and a log of the redactions made in `redactions.json`. If the processor is also given a PDF
+/// processor redacting the file as it renders it, its PDF is added as `redacted.pdf`.
+///
+pub struct RedactionProcessor {
+    text_processor: Arc<dyn Process>,
+    pdf_processor: Option<Arc<dyn Process>>,
+}
+
+impl RedactionProcessor {
+    /// Creates a processor redacting the text extracted by the given processor.
+    ///
+    pub fn scanning(text_processor: impl Process + 'static) -> Self {
+        Self {
+            text_processor: Arc::new(text_processor),
+            pdf_processor: None,
+        }
+    }
+
+    /// Sets the processor rendering a redacted PDF of the file, such as
+    /// [`crate::pdf::Rfc822RedactedPdfProcessor`].
+    ///
+    pub fn rendering(mut self, pdf_processor: impl Process + 'static) -> Self {
+        self.pdf_processor = Some(Arc::new(pdf_processor));
+        self
+    }
+}
+
+#[async_trait]
+impl Process for RedactionProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        output_path: TempPath,
+        checksum: &str,
+    ) -> Result<(), ProcessError> {
+        let outputs = ctx.run_nested(self.text_processor.as_ref(), input_path, checksum).await?;
+        let text_path = outputs.iter()
+            .find_map(|output| match output {
+                ProcessOutput::Processed(_, data) if data.name == "extracted.txt" => Some(&data.path),
+                _ => None,
+            })
+            .ok_or_else(|| ProcessError::new(
+                ProcessErrorKind::Unsupported,
+                anyhow!("processor '{}' did not extract text", self.text_processor.name()),
+            ))?;
+        let text = std::fs::read(text_path)
+            .context("failed to read extracted text")?;
+
+        let (redacted, redactions) = redact(ctx.config().redaction_rules(), &String::from_utf8_lossy(&text));
+        std::fs::write(&output_path, redacted)
+            .context("failed to write redacted text")?;
+        ctx.add_output(Ok(ProcessOutput::processed(&ctx, "redacted.txt", output_path, "text/plain", checksum))).await?;
+
+        let mut log = RedactionLog::default();
+        for redaction in &redactions {
+            *log.counts.entry(redaction.entity_type.clone()).or_default() += 1;
+        }
+        log.redactions = redactions;
+
+        let log_path = ctx.temp_file()
+            .context("failed to create temporary file")?
+            .into_temp_path();
+        let file = std::fs::File::create(&log_path)
+            .context("failed to create redaction log file")?;
+        serde_json::to_writer_pretty(file, &log)
+            .context("failed to write redaction log to file")?;
+        ctx.add_output(Ok(ProcessOutput::processed(&ctx, "redactions.json", log_path, "application/json", checksum))).await?;
+
+        if let Some(pdf_processor) = &self.pdf_processor {
+            let pdf_path = render_pdf(pdf_processor.as_ref(), &ctx, input_path, checksum).await?;
+            ctx.add_output(Ok(ProcessOutput::processed(&ctx, "redacted.pdf", pdf_path, "application/pdf", checksum))).await?;
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "Redaction"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::processing::{ProcessContextBuilder, ProcessType};
+
+    use super::*;
+
+    /// Extracts the input as the text, as if it was extracted from another type of file.
+    ///
+    struct CopyingTextProcessor;
+
+    #[async_trait]
+    impl Process for CopyingTextProcessor {
+        async fn process(&self, ctx: ProcessContext, input_path: &Path, output_path: TempPath, checksum: &str) -> Result<(), ProcessError> {
+            std::fs::copy(input_path, &output_path)?;
+            ctx.add_output(Ok(ProcessOutput::processed(&ctx, "extracted.txt", output_path, "text/plain", checksum))).await
+        }
+
+        fn name(&self) -> &'static str {
+            "copying"
+        }
+    }
+
+    #[test]
+    fn test_redact() {
+        let rules = EntityRule::defaults();
+
+        let (redacted, redactions) = redact(&rules, "Café: mail bob@example.com, card 4111 1111 1111 1111.");
+
+        assert_eq!(redacted, "Café: mail [REDACTED EMAIL], card [REDACTED CREDIT_CARD].");
+        assert_eq!(redactions, vec![
+            Redaction {
+                entity_type: "email".to_string(),
+                placeholder: "[REDACTED EMAIL]".to_string(),
+                start: 11,
+                end: 26,
+                redacted_start: 11,
+                redacted_end: 27,
+            },
+            Redaction {
+                entity_type: "credit_card".to_string(),
+                placeholder: "[REDACTED CREDIT_CARD]".to_string(),
+                start: 33,
+                end: 52,
+                redacted_start: 34,
+                redacted_end: 56,
+            },
+        ]);
+    }
+
+    #[test]
+    fn test_redact_nothing() {
+        let (redacted, redactions) = redact(&EntityRule::defaults(), "Nothing to see here");
+
+        assert_eq!(redacted, "Nothing to see here");
+        assert!(redactions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_process() -> anyhow::Result<()> {
+        let (output_sink, mut outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("text/plain", vec![ProcessType::Redaction], output_sink).build();
+        let input_path = tempfile::NamedTempFile::new()?.into_temp_path();
+        std::fs::write(&input_path, "Call (713) 853-6197 or mail jeff@enron.com")?;
+        let output_path = tempfile::NamedTempFile::new()?.into_temp_path();
+
+        RedactionProcessor::scanning(CopyingTextProcessor).process(ctx, &input_path, output_path, "checksum").await?;
+
+        let Some(Ok(ProcessOutput::Processed(_, text))) = outputs.recv().await else {
+            panic!("Expected the redacted text");
+        };
+        let Some(Ok(ProcessOutput::Processed(_, log_data))) = outputs.recv().await else {
+            panic!("Expected the redaction log");
+        };
+        let log_json = std::fs::read_to_string(&log_data.path)?;
+        let log: RedactionLog = serde_json::from_str(&log_json)?;
+
+        assert_eq!(text.name, "redacted.txt");
+        assert_eq!(std::fs::read_to_string(&text.path)?, "Call [REDACTED PHONE] or mail [REDACTED EMAIL]");
+        assert_eq!(log.counts, BTreeMap::from([("email".to_string(), 1), ("phone".to_string(), 1)]));
+        assert_eq!(log.redactions.iter().map(|redaction| (redaction.start, redaction.end)).collect::<Vec<_>>(), vec![
+            (5, 19),
+            (28, 42),
+        ]);
+        assert!(!log_json.contains("jeff@enron.com"));
+        assert!(outputs.recv().await.is_none());
+        Ok(())
+    }
+}
@@ -0,0 +1,145 @@
+use chardetng::EncodingDetector;
+use encoding_rs::Encoding;
+use lazy_static::lazy_static;
+use regex::bytes::Regex;
+
+lazy_static! {
+    static ref META_CHARSET: Regex = Regex::new(r#"(?i)<meta[^>]*?charset\s*=\s*["']?\s*([a-z0-9_:.+-]+)"#).unwrap();
+}
+
+/// The number of bytes at the start of an HTML document searched for a declared charset, as in the HTML standard.
+///
+const HTML_PRESCAN_SIZE: usize = 1024;
+
+/// Text decoded from a file in a detected encoding.
+///
+#[derive(Debug)]
+pub(crate) struct DecodedText {
+    /// The decoded text, with the byte order mark removed and line endings normalized to `\n`.
+    ///
+    pub(crate) text: String,
+
+    /// The encoding the text was decoded from.
+    ///
+    pub(crate) encoding: &'static Encoding,
+
+    /// Whether the encoding was given by a byte order mark rather than detected from the content.
+    ///
+    pub(crate) bom: bool,
+
+    /// Whether the content had byte sequences invalid in the encoding, which were replaced.
+    ///
+    pub(crate) had_errors: bool,
+}
+
+/// Decodes the content of a text file to UTF-8.
+///
+/// The encoding is taken from a UTF-8 or UTF-16 byte order mark if there is one, and detected from the content
+/// otherwise, e.g. Windows-1252 or Shift-JIS.
+///
+pub(crate) fn decode(content: &[u8]) -> DecodedText {
+    decode_with_declared(content, None)
+}
+
+/// Decodes the content of an HTML document to UTF-8.
+///
+/// A byte order mark takes precedence over the charset declared by a `<meta>` tag at the start of the document, which
+/// takes precedence over the encoding detected from the content.
+///
+pub(crate) fn decode_html(content: &[u8]) -> DecodedText {
+    let prescan = &content[..content.len().min(HTML_PRESCAN_SIZE)];
+    let declared = META_CHARSET.captures(prescan)
+        .and_then(|captures| Encoding::for_label(&captures[1]));
+    decode_with_declared(content, declared)
+}
+
+fn decode_with_declared(content: &[u8], declared: Option<&'static Encoding>) -> DecodedText {
+    let (encoding, bom) = match Encoding::for_bom(content) {
+        Some((encoding, _)) => (encoding, true),
+        None => (declared.unwrap_or_else(|| detect_encoding(content)), false),
+    };
+    let (text, had_errors) = encoding.decode_with_bom_removal(content);
+
+    DecodedText {
+        text: normalize_newlines(&text),
+        encoding,
+        bom,
+        had_errors,
+    }
+}
+
+/// Detects the encoding of content without a byte order mark, preferring UTF-8 if it is valid.
+///
+fn detect_encoding(content: &[u8]) -> &'static Encoding {
+    let mut detector = EncodingDetector::new();
+    detector.feed(content, true);
+    detector.guess(None, true)
+}
+
+/// Replaces `\r\n` and lone `\r` line endings with `\n`.
+///
+fn normalize_newlines(text: &str) -> String {
+    if !text.contains('\r') {
+        return text.to_string();
+    }
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use encoding_rs::{SHIFT_JIS, UTF_16LE, UTF_8, WINDOWS_1252};
+
+    use super::*;
+
+    #[test]
+    fn test_decode_utf8() {
+        let decoded = decode("Ünïcödé\r\ntext\rhere\n".as_bytes());
+
+        assert_eq!(decoded.text, "Ünïcödé\ntext\nhere\n");
+        assert_eq!(decoded.encoding, UTF_8);
+        assert!(!decoded.bom);
+        assert!(!decoded.had_errors);
+    }
+
+    #[test]
+    fn test_decode_bom() {
+        let mut content = vec![0xFF, 0xFE];
+        content.extend("Hi\r\n".encode_utf16().flat_map(u16::to_le_bytes));
+
+        let decoded = decode(&content);
+
+        assert_eq!(decoded.text, "Hi\n");
+        assert_eq!(decoded.encoding, UTF_16LE);
+        assert!(decoded.bom);
+    }
+
+    #[test]
+    fn test_decode_html() {
+        let (content, _, _) = WINDOWS_1252.encode("<html><head><meta charset=\"iso-8859-1\"></head><body>Café</body></html>");
+        let decoded = decode_html(&content);
+        assert_eq!(decoded.encoding, WINDOWS_1252);
+        assert!(decoded.text.contains("Café"));
+
+        let content = b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=Shift_JIS\"><p>Hi</p>";
+        assert_eq!(decode_html(content).encoding, SHIFT_JIS);
+        assert_eq!(decode_html(b"<p>Hi</p>").encoding, UTF_8);
+    }
+
+    #[test]
+    fn test_decode_legacy_encodings() {
+        let cases = vec![
+            (WINDOWS_1252, "Le café coûte 3 € à la gare, s'il vous plaît. Très cher."),
+            (SHIFT_JIS, "これは日本語のテキストです。文字コードを検出してください。"),
+        ];
+
+        for (encoding, text) in cases {
+            let (content, _, _) = encoding.encode(text);
+
+            let decoded = decode(&content);
+
+            assert_eq!(decoded.encoding, encoding, "{}", text);
+            assert_eq!(decoded.text, text);
+            assert!(!decoded.bom);
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use tempfile::TempPath;
+
+use crate::processing::{Process, ProcessContext, ProcessError, ProcessErrorKind};
+use crate::text::add_text_outputs;
+use crate::text::charset::decode_html;
+
+/// Extracts readable text from HTML natively with `html2text`, wrapped to the
+/// [`crate::processing::ProcessorConfig::html_text_width`].
+///
+/// Unlike Tika's text, the text keeps the structure of the document: link targets are listed as numbered references,
+/// lists keep their bullets and numbers, and tables are laid out in columns.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct HtmlTextProcessor;
+
+#[async_trait]
+impl Process for HtmlTextProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        output_path: TempPath,
+        checksum: &str,
+    ) -> Result<(), ProcessError> {
+        let content = std::fs::read(input_path)
+            .context("failed to read input file")?;
+        let width = ctx.config().html_text_width();
+
+        // html2text panics on documents it can not lay out in the width, which is contained by running it on its own
+        // task.
+        let text = tokio::task::spawn_blocking(move || html_to_text(&content, width)).await
+            .map_err(|err| ProcessError::new(ProcessErrorKind::Corrupt, anyhow!("failed to render html: {}", err)))?;
+        std::fs::write(&output_path, text)
+            .context("failed to write text to file")?;
+
+        add_text_outputs(&ctx, output_path, checksum).await
+    }
+
+    fn name(&self) -> &'static str {
+        "HTML Text"
+    }
+}
+
+/// Renders the HTML as text wrapped to the width, decoding it from its declared or detected charset first.
+///
+pub(crate) fn html_to_text(content: &[u8], width: usize) -> String {
+    let html = decode_html(content).text;
+    html2text::from_read(html.as_bytes(), width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_to_text() {
+        let html = b"\
+<html><head><title>Report</title><style>p { color: red; }</style></head><body>
+<h1>Quarterly report</h1>
+<p>See the <a href=\"https://example.com/q3\">full report</a>.</p>
+<ul><li>Revenue</li><li>Costs</li></ul>
+<table><tr><td>Q1</td><td>10</td></tr><tr><td>Q2</td><td>20</td></tr></table>
+</body></html>";
+
+        let text = html_to_text(html, 80);
+
+        assert!(text.contains("Quarterly report"), "{}", text);
+        assert!(text.contains("[full report][1]"), "{}", text);
+        assert!(text.contains("[1]: https://example.com/q3"), "{}", text);
+        assert!(text.contains("* Revenue"), "{}", text);
+        assert!(text.lines().any(|line| line.contains("Q1") && line.contains("10")), "{}", text);
+        assert!(!text.contains("color: red"), "{}", text);
+    }
+
+    #[test]
+    fn test_html_to_text_width() {
+        let html = "<p>word word word word word word word word word word</p>";
+
+        let text = html_to_text(html.as_bytes(), 20);
+
+        assert!(text.lines().all(|line| line.chars().count() <= 20), "{}", text);
+        assert!(text.lines().count() > 1, "{}", text);
+    }
+}
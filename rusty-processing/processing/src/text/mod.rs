@@ -4,9 +4,15 @@ use anyhow::Context;
 use async_trait::async_trait;
 use tempfile::TempPath;
 
-use services::tika;
+use crate::language::language_output;
+use crate::processing::{InputStream, Process, ProcessContext, ProcessError, ProcessOutput, StreamedOutput};
 
-use crate::processing::{Process, ProcessContext, ProcessOutput};
+pub use html::*;
+pub use rfc822::*;
+
+pub(crate) mod charset;
+mod html;
+mod rfc822;
 
 #[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct DefaultTextProcessor;
@@ -19,15 +25,108 @@ impl Process for DefaultTextProcessor {
         input_path: &Path,
         output_path: TempPath,
         checksum: &str,
-    ) -> Result<(), anyhow::Error> {
-        tika().text_into_file(input_path, &output_path).await
+    ) -> Result<(), ProcessError> {
+        ctx.config().tika().text_into_file(input_path, &output_path).await
             .context("failed to extract text")?;
 
-        let output = ProcessOutput::processed(&ctx, "extracted.txt", output_path, "text/plain", checksum);
-        ctx.add_output(Ok(output)).await
+        add_text_outputs(&ctx, output_path, checksum).await
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    async fn process_stream(
+        &self,
+        ctx: ProcessContext,
+        input: InputStream,
+        output_path: TempPath,
+    ) -> Result<Vec<StreamedOutput>, ProcessError> {
+        ctx.config().tika().text_from_reader_into_file(input, &output_path).await
+            .context("failed to extract text")?;
+
+        let language = language_output(&ctx, &output_path)?;
+        let text = StreamedOutput {
+            name: "extracted.txt".to_string(),
+            path: output_path,
+            mimetype: "text/plain".to_string(),
+            kind: None,
+        };
+        let mut outputs = vec![text];
+        outputs.extend(language);
+        Ok(outputs)
     }
 
     fn name(&self) -> &'static str {
         "Default Text"
     }
-}
\ No newline at end of file
+}
+
+/// Extracts the text of text files natively, decoding it to UTF-8 from the encoding given by its byte order mark or
+/// detected from its content, and normalizing its line endings to `\n`.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NativeTextProcessor;
+
+#[async_trait]
+impl Process for NativeTextProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        output_path: TempPath,
+        checksum: &str,
+    ) -> Result<(), ProcessError> {
+        let content = std::fs::read(input_path)
+            .context("failed to read input file")?;
+        std::fs::write(&output_path, charset::decode(&content).text)
+            .context("failed to write text to file")?;
+
+        add_text_outputs(&ctx, output_path, checksum).await
+    }
+
+    fn name(&self) -> &'static str {
+        "Native Text"
+    }
+}
+
+/// Fallback for [`NativeTextProcessor`] for text files, reading the text of the file as is.
+///
+/// Invalid UTF-8 sequences are replaced, so the text is readable even if the file uses a different encoding.
+///
+#[derive(Debug, Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PlainTextProcessor;
+
+#[async_trait]
+impl Process for PlainTextProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        output_path: TempPath,
+        checksum: &str,
+    ) -> Result<(), ProcessError> {
+        let content = std::fs::read(input_path)
+            .context("failed to read input file")?;
+        std::fs::write(&output_path, String::from_utf8_lossy(&content).as_bytes())
+            .context("failed to write text to file")?;
+
+        add_text_outputs(&ctx, output_path, checksum).await
+    }
+
+    fn name(&self) -> &'static str {
+        "Plain Text"
+    }
+}
+
+/// Sends the extracted text as `extracted.txt`, followed by the languages detected in it if enabled.
+///
+pub(crate) async fn add_text_outputs(ctx: &ProcessContext, text_path: TempPath, checksum: &str) -> Result<(), ProcessError> {
+    let language = language_output(ctx, &text_path)?;
+
+    ctx.add_output(Ok(ProcessOutput::processed(ctx, "extracted.txt", text_path, "text/plain", checksum))).await?;
+    if let Some(language) = language {
+        ctx.add_output(Ok(language.into_processed(ctx, checksum))).await?;
+    }
+    Ok(())
+}
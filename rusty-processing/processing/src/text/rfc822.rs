@@ -0,0 +1,50 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context};
+use async_trait::async_trait;
+use mail_parser::MessageParser;
+use tempfile::TempPath;
+
+use crate::pdf::rfc822::text_message_visitor::TextMessageVisitor;
+use crate::pdf::rfc822::transformer::MessageTransformer;
+use crate::processing::{Process, ProcessContext, ProcessError, ProcessErrorKind};
+use crate::text::add_text_outputs;
+
+/// Extracts the text of a message natively, with its key headers, text bodies (HTML bodies converted to text), the names
+/// of its attachments and the text of attached messages.
+///
+/// The text only depends on the content of the message, so the same message always has the same text.
+///
+#[derive(Debug, Default)]
+pub struct Rfc822TextProcessor {
+    message_parser: MessageParser,
+}
+
+#[async_trait]
+impl Process for Rfc822TextProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        output_path: TempPath,
+        checksum: &str,
+    ) -> Result<(), ProcessError> {
+        let content = std::fs::read(input_path)
+            .context("failed to read input file")?;
+        let message = self.message_parser.parse(&content)
+            .ok_or_else(|| ProcessError::new(ProcessErrorKind::Corrupt, anyhow!("failed to parse message")))?;
+
+        let mut text = vec![];
+        MessageTransformer::new(Box::new(TextMessageVisitor)).text_only()
+            .transform(&message, &mut text)
+            .context("failed to transform message")?;
+        std::fs::write(&output_path, text)
+            .context("failed to write text to file")?;
+
+        add_text_outputs(&ctx, output_path, checksum).await
+    }
+
+    fn name(&self) -> &'static str {
+        "RFC 822 Text"
+    }
+}
@@ -0,0 +1,97 @@
+use std::io::Cursor;
+use std::path::Path;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use log::warn;
+use mail_parser::MessageParser;
+use tempfile::TempPath;
+
+use identify::deduplication::dedupe_checksum;
+
+use crate::embedded::MboxReader;
+use crate::processing::{Process, ProcessContext, ProcessError, ProcessOutput};
+use crate::threading::{ThreadBuilder, ThreadMessage};
+
+/// Builds the conversation threads of the messages in an mbox file, written to `threads.json`.
+///
+/// Messages are identified by the same checksums as the files [`crate::embedded::MboxEmbeddedProcessor`] embeds for
+/// them. Messages that can not be read from the mbox file are left out.
+///
+#[derive(Debug, Default)]
+pub struct MboxThreadsProcessor {
+    message_parser: MessageParser,
+}
+
+#[async_trait]
+impl Process for MboxThreadsProcessor {
+    async fn process(
+        &self,
+        ctx: ProcessContext,
+        input_path: &Path,
+        output_path: TempPath,
+        checksum: &str,
+    ) -> Result<(), ProcessError> {
+        let reader = MboxReader::open(&ctx, input_path)
+            .context("failed to open mbox file")?;
+
+        let mut builder = ThreadBuilder::default();
+        for message in reader {
+            if ctx.is_cancelled() {
+                return Err(ProcessError::cancelled());
+            }
+
+            let Ok(message) = message else {
+                warn!("Skipping message that failed to read from mbox");
+                continue;
+            };
+            let contents = message.contents;
+            let message_checksum = dedupe_checksum(&mut Cursor::new(&contents), "message/rfc822").await
+                .context("failed to calculate checksum")?;
+            if let Some(message) = self.message_parser.parse_headers(contents.as_slice()) {
+                builder.add(ThreadMessage::new(message_checksum, &message));
+            }
+        }
+
+        let file = std::fs::File::create(&output_path)
+            .context("failed to create threads file")?;
+        serde_json::to_writer_pretty(file, &builder.build())
+            .context("failed to write threads to file")?;
+
+        let output = ProcessOutput::processed(&ctx, "threads.json", output_path, "application/json", checksum);
+        ctx.add_output(Ok(output)).await
+    }
+
+    fn name(&self) -> &'static str {
+        "Mbox Threads"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::processing::{ProcessContextBuilder, ProcessType};
+    use crate::threading::Threads;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_process() -> anyhow::Result<()> {
+        let (output_sink, mut outputs) = tokio::sync::mpsc::channel(10);
+        let ctx = ProcessContextBuilder::new("application/mbox", vec![ProcessType::Threads], output_sink).build();
+        let output_path = tempfile::NamedTempFile::new()?.into_temp_path();
+
+        MboxThreadsProcessor::default()
+            .process(ctx, Path::new("../resources/mbox/ubuntu-no-small.mbox"), output_path, "checksum")
+            .await?;
+
+        let Some(Ok(ProcessOutput::Processed(_, data))) = outputs.recv().await else {
+            panic!("Expected a processed output");
+        };
+        let threads: Threads = serde_json::from_reader(std::fs::File::open(&data.path)?)?;
+        assert_eq!(data.name, "threads.json");
+        assert_eq!(threads.messages.len(), 2);
+        assert!(threads.messages.contains_key("88dde30cbe134ce0dd8aa0979546646a"));
+        assert!(threads.messages.contains_key("c694e99230b3cbf36d8aef4131596864"));
+        Ok(())
+    }
+}
@@ -0,0 +1,508 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io;
+use std::path::Path;
+
+use mail_parser::{Address, Message, MessageParser};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+
+pub use mbox::*;
+
+mod mbox;
+
+/// The maximum number of bytes of a message file read to thread it, which is more than its headers take up.
+///
+const MAX_HEADERS_SIZE: u64 = 256 * 1024;
+
+/// The conversation threads of a set of messages, written to `threads.json`.
+///
+/// Messages are identified by their dedupe checksums, the same checksums their embedded files have.
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Threads {
+    /// The threads, in order of their first message.
+    ///
+    pub threads: Vec<Thread>,
+
+    /// The position of each message in its thread, keyed by the checksum of the message.
+    ///
+    pub messages: BTreeMap<String, ThreadedMessage>,
+}
+
+/// A conversation thread.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Thread {
+    /// The ID of the thread, which is the checksum of its first message.
+    ///
+    pub id: String,
+
+    /// The subject of the first message of the thread without reply and forward prefixes, e.g. `Forecast` for
+    /// `Re: Forecast`.
+    ///
+    pub subject: Option<String>,
+
+    /// The checksums of the messages of the thread, in order of their dates.
+    ///
+    pub messages: Vec<String>,
+}
+
+/// The place of a message in its thread.
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThreadedMessage {
+    /// The ID of the thread the message belongs to.
+    ///
+    pub thread_id: String,
+
+    /// The checksum of the message this message replies to, if it is known.
+    ///
+    pub parent: Option<String>,
+
+    /// The checksums of the messages replying to this message, in order of their dates.
+    ///
+    pub children: Vec<String>,
+
+    /// The position of the message in its thread, in order of the dates of its messages, starting at 0.
+    ///
+    pub position: usize,
+
+    /// How the message was linked to its parent, if it has one.
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<ThreadLink>,
+}
+
+/// How a message was linked to the message it replies to.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThreadLink {
+    /// By the `In-Reply-To` or `References` header of the message.
+    ///
+    Headers,
+
+    /// By the subject and participants of the message, as it had no headers linking it to a known message.
+    ///
+    Subject,
+}
+
+/// The headers of a message needed to thread it.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct ThreadMessage {
+    checksum: String,
+    message_id: Option<String>,
+    in_reply_to: Vec<String>,
+    references: Vec<String>,
+    thread_name: Option<String>,
+    is_reply: bool,
+    participants: BTreeSet<String>,
+    timestamp: Option<i64>,
+}
+
+impl ThreadMessage {
+    /// Reads the headers needed to thread the message with the given checksum.
+    ///
+    pub(crate) fn new(checksum: impl Into<String>, message: &Message) -> Self {
+        let text_list = |value: &mail_parser::HeaderValue| value.as_text_list()
+            .unwrap_or_default()
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+
+        let mut participants = BTreeSet::new();
+        for address in [message.from(), message.to(), message.cc(), message.bcc()].into_iter().flatten() {
+            participants.extend(addresses(address));
+        }
+
+        let subject = message.subject().map(str::trim).unwrap_or_default();
+        let thread_name = message.thread_name().map(str::trim).filter(|name| !name.is_empty());
+
+        Self {
+            checksum: checksum.into(),
+            message_id: message.message_id().map(str::to_string),
+            in_reply_to: text_list(message.in_reply_to()),
+            references: text_list(message.references()),
+            is_reply: thread_name.is_some_and(|name| name != subject),
+            thread_name: thread_name.map(str::to_string),
+            participants,
+            timestamp: message.date().filter(|date| date.is_valid()).map(|date| date.to_timestamp()),
+        }
+    }
+
+    /// Reads the headers needed to thread the message in the file with the given checksum, without reading its body.
+    ///
+    /// Returns [`None`] if the headers fail to parse.
+    ///
+    pub(crate) async fn read(checksum: impl Into<String>, path: &Path) -> io::Result<Option<Self>> {
+        let file = tokio::fs::File::open(path).await?;
+        let mut reader = BufReader::new(file.take(MAX_HEADERS_SIZE));
+        let mut headers = vec![];
+        loop {
+            let line_start = headers.len();
+            if reader.read_until(b'\n', &mut headers).await? == 0 {
+                break;
+            }
+            if matches!(&headers[line_start..], b"\n" | b"\r\n") {
+                break;
+            }
+        }
+
+        let message = MessageParser::default().parse_headers(headers.as_slice());
+        Ok(message.map(|message| Self::new(checksum, &message)))
+    }
+
+    /// Returns the IDs of the messages this message replies to, from the closest to the furthest.
+    ///
+    fn ancestor_ids(&self) -> impl Iterator<Item = &String> {
+        self.in_reply_to.iter().rev().chain(self.references.iter().rev())
+    }
+}
+
+fn addresses(address: &Address) -> Vec<String> {
+    match address {
+        Address::List(addresses) => addresses.iter()
+            .filter_map(|addr| addr.address())
+            .map(str::to_lowercase)
+            .collect(),
+        Address::Group(groups) => groups.iter()
+            .flat_map(|group| group.addresses.iter())
+            .filter_map(|addr| addr.address())
+            .map(str::to_lowercase)
+            .collect(),
+    }
+}
+
+/// Collects messages and builds their conversation threads.
+///
+/// Messages are linked to the messages they reply to by their `Message-ID`, `In-Reply-To` and `References` headers.
+/// Messages referencing the same message are in the same thread, even if the referenced message is not one of the
+/// collected ones. A reply without headers linking it to a known message is linked to the latest earlier message with
+/// the same subject, ignoring reply and forward prefixes, that shares a participant with it.
+///
+#[derive(Debug, Default)]
+pub(crate) struct ThreadBuilder {
+    messages: Vec<ThreadMessage>,
+    checksums: HashSet<String>,
+}
+
+impl ThreadBuilder {
+    /// Adds a message to thread, ignoring it if a message with the same checksum was already added.
+    ///
+    pub(crate) fn add(&mut self, message: ThreadMessage) {
+        if self.checksums.insert(message.checksum.clone()) {
+            self.messages.push(message);
+        }
+    }
+
+    /// Returns whether no messages were added.
+    ///
+    pub(crate) fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Builds the threads of the added messages.
+    ///
+    pub(crate) fn build(&self) -> Threads {
+        let messages = &self.messages;
+        let order = |index: &usize| (messages[*index].timestamp.unwrap_or(i64::MAX), *index);
+
+        let mut ids = HashMap::new();
+        for (index, message) in messages.iter().enumerate() {
+            if let Some(message_id) = &message.message_id {
+                ids.entry(message_id.as_str()).or_insert(index);
+            }
+        }
+
+        let mut parents: Vec<Option<(usize, ThreadLink)>> = vec![None; messages.len()];
+        let mut components = UnionFind::new(messages.len());
+        // The trees formed by the links to parents, to not link a message to one of its descendants
+        let mut trees = UnionFind::new(messages.len());
+        let mut referencing = HashMap::new();
+        for (index, message) in messages.iter().enumerate() {
+            // The message has no parent yet, so it is the root of its tree and any message in the tree descends from it
+            parents[index] = message.ancestor_ids()
+                .filter_map(|id| ids.get(id.as_str()).copied())
+                .find(|&parent| trees.find(parent) != trees.find(index))
+                .map(|parent| (parent, ThreadLink::Headers));
+            if let Some((parent, _)) = parents[index] {
+                trees.union(parent, index);
+            }
+
+            for id in message.ancestor_ids().chain(message.message_id.iter()) {
+                let first = *referencing.entry(id.as_str()).or_insert(index);
+                components.union(first, index);
+            }
+        }
+
+        let mut by_date = (0..messages.len()).collect::<Vec<_>>();
+        by_date.sort_by_key(order);
+        // The latest message so far with each subject and participant, with its position in order of date
+        let mut latest: HashMap<(&str, &str), (usize, usize)> = HashMap::new();
+        for (rank, &index) in by_date.iter().enumerate() {
+            let message = &messages[index];
+            let Some(thread_name) = message.thread_name.as_deref() else {
+                continue;
+            };
+            if parents[index].is_none() && message.is_reply {
+                let parent = message.participants.iter()
+                    .filter_map(|participant| latest.get(&(thread_name, participant.as_str())).copied())
+                    .filter(|&(_, other)| components.find(other) != components.find(index))
+                    .max_by_key(|&(other_rank, _)| other_rank)
+                    .map(|(_, other)| other);
+                if let Some(parent) = parent {
+                    parents[index] = Some((parent, ThreadLink::Subject));
+                    components.union(parent, index);
+                }
+            }
+            for participant in &message.participants {
+                latest.insert((thread_name, participant.as_str()), (rank, index));
+            }
+        }
+
+        let mut children = vec![vec![]; messages.len()];
+        for &index in &by_date {
+            if let Some((parent, _)) = parents[index] {
+                children[parent].push(index);
+            }
+        }
+
+        let mut thread_messages: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for &index in &by_date {
+            thread_messages.entry(components.find(index)).or_default().push(index);
+        }
+        let mut thread_messages = thread_messages.into_values().collect::<Vec<_>>();
+        thread_messages.sort_by_key(|indices| order(&indices[0]));
+
+        let mut threads = Threads::default();
+        for indices in thread_messages {
+            let first = &messages[indices[0]];
+            for (position, &index) in indices.iter().enumerate() {
+                threads.messages.insert(messages[index].checksum.clone(), ThreadedMessage {
+                    thread_id: first.checksum.clone(),
+                    parent: parents[index].map(|(parent, _)| messages[parent].checksum.clone()),
+                    children: children[index].iter().map(|&child| messages[child].checksum.clone()).collect(),
+                    position,
+                    link: parents[index].map(|(_, link)| link),
+                });
+            }
+            threads.threads.push(Thread {
+                id: first.checksum.clone(),
+                subject: first.thread_name.clone(),
+                messages: indices.iter().map(|&index| messages[index].checksum.clone()).collect(),
+            });
+        }
+        threads
+    }
+}
+
+/// Disjoint sets of messages, e.g. the threads.
+///
+struct UnionFind {
+    parents: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(size: usize) -> Self {
+        Self { parents: (0..size).collect() }
+    }
+
+    fn find(&mut self, index: usize) -> usize {
+        let mut root = index;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+        let mut current = index;
+        while self.parents[current] != root {
+            current = std::mem::replace(&mut self.parents[current], root);
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        // The smaller index is kept as the root to keep the order of the threads independent of the order of unions
+        if a < b {
+            self.parents[b] = a;
+        } else {
+            self.parents[a] = b;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mail_parser::MessageParser;
+
+    use super::*;
+
+    fn message(checksum: &str, headers: &str) -> ThreadMessage {
+        let content = format!("{}\n\nBody\n", headers.trim());
+        ThreadMessage::new(checksum, &MessageParser::default().parse(content.as_bytes()).unwrap())
+    }
+
+    fn build(messages: Vec<ThreadMessage>) -> Threads {
+        let mut builder = ThreadBuilder::default();
+        for message in messages {
+            builder.add(message);
+        }
+        builder.build()
+    }
+
+    #[test]
+    fn test_thread_by_headers() {
+        let threads = build(vec![
+            message("reply-2", "
+Message-ID: <reply-2@enron.com>
+In-Reply-To: <reply-1@enron.com>
+References: <original@enron.com> <reply-1@enron.com>
+Date: Tue, 15 May 2001 09:00:00 -0700
+From: phillip.allen@enron.com
+Subject: Re: Re: Forecast"),
+            message("original", "
+Message-ID: <original@enron.com>
+Date: Mon, 14 May 2001 16:39:00 -0700
+From: phillip.allen@enron.com
+To: tim.belden@enron.com
+Subject: Forecast"),
+            message("reply-1", "
+Message-ID: <reply-1@enron.com>
+In-Reply-To: <original@enron.com>
+Date: Mon, 14 May 2001 17:00:00 -0700
+From: tim.belden@enron.com
+Subject: RE: Forecast"),
+            message("other", "
+Message-ID: <other@enron.com>
+Date: Mon, 14 May 2001 18:00:00 -0700
+From: jeff@enron.com
+Subject: Lunch"),
+        ]);
+
+        assert_eq!(threads.threads, vec![
+            Thread {
+                id: "original".to_string(),
+                subject: Some("Forecast".to_string()),
+                messages: vec!["original".to_string(), "reply-1".to_string(), "reply-2".to_string()],
+            },
+            Thread {
+                id: "other".to_string(),
+                subject: Some("Lunch".to_string()),
+                messages: vec!["other".to_string()],
+            },
+        ]);
+        assert_eq!(threads.messages["original"], ThreadedMessage {
+            thread_id: "original".to_string(),
+            parent: None,
+            children: vec!["reply-1".to_string()],
+            position: 0,
+            link: None,
+        });
+        assert_eq!(threads.messages["reply-2"], ThreadedMessage {
+            thread_id: "original".to_string(),
+            parent: Some("reply-1".to_string()),
+            children: vec![],
+            position: 2,
+            link: Some(ThreadLink::Headers),
+        });
+    }
+
+    #[test]
+    fn test_thread_missing_parent() {
+        let threads = build(vec![
+            message("reply-1", "
+Message-ID: <reply-1@enron.com>
+In-Reply-To: <missing@enron.com>
+Date: Mon, 14 May 2001 17:00:00 -0700
+Subject: Re: Forecast"),
+            message("reply-2", "
+Message-ID: <reply-2@enron.com>
+References: <missing@enron.com>
+Date: Mon, 14 May 2001 18:00:00 -0700
+Subject: Re: Forecast"),
+        ]);
+
+        assert_eq!(threads.threads.len(), 1);
+        assert_eq!(threads.threads[0].messages, vec!["reply-1", "reply-2"]);
+        assert_eq!(threads.messages["reply-1"].parent, None);
+        assert_eq!(threads.messages["reply-2"].parent, None);
+    }
+
+    #[test]
+    fn test_thread_by_subject() {
+        let threads = build(vec![
+            message("original", "
+Date: Mon, 14 May 2001 16:39:00 -0700
+From: phillip.allen@enron.com
+To: tim.belden@enron.com
+Subject: Forecast"),
+            message("reply", "
+Date: Mon, 14 May 2001 17:00:00 -0700
+From: Tim Belden <Tim.Belden@enron.com>
+Subject: Fwd: Re: Forecast"),
+            message("stranger", "
+Date: Mon, 14 May 2001 18:00:00 -0700
+From: jeff@enron.com
+Subject: Re: Forecast"),
+            message("new", "
+Date: Tue, 15 May 2001 09:00:00 -0700
+From: phillip.allen@enron.com
+Subject: Forecast"),
+        ]);
+
+        assert_eq!(threads.threads.iter().map(|thread| thread.messages.len()).collect::<Vec<_>>(), vec![2, 1, 1]);
+        assert_eq!(threads.messages["reply"].parent.as_deref(), Some("original"));
+        assert_eq!(threads.messages["reply"].link, Some(ThreadLink::Subject));
+        assert_eq!(threads.messages["stranger"].parent, None);
+        assert_eq!(threads.messages["new"].parent, None);
+    }
+
+    #[test]
+    fn test_thread_cycle() {
+        let threads = build(vec![
+            message("a", "
+Message-ID: <a@enron.com>
+In-Reply-To: <b@enron.com>
+Subject: Loop"),
+            message("b", "
+Message-ID: <b@enron.com>
+In-Reply-To: <a@enron.com>
+Subject: Loop"),
+        ]);
+
+        assert_eq!(threads.threads.len(), 1);
+        assert_eq!(threads.messages["a"].parent.as_deref(), Some("b"));
+        assert_eq!(threads.messages["b"].parent, None);
+    }
+
+    #[tokio::test]
+    async fn test_read() -> anyhow::Result<()> {
+        let path = tempfile::NamedTempFile::new()?.into_temp_path();
+        let body = "Message-ID: <body@enron.com>\n".repeat(100_000);
+        std::fs::write(&path, format!("Message-ID: <a@enron.com>\nIn-Reply-To: <b@enron.com>\n\n{}", body))?;
+
+        let message = ThreadMessage::read("a", &path).await?.unwrap();
+
+        assert_eq!(message.checksum, "a");
+        assert_eq!(message.message_id.as_deref(), Some("a@enron.com"));
+        assert_eq!(message.in_reply_to, vec!["b@enron.com".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_thread_long_chain() {
+        let messages = (0..10_000)
+            .map(|index| message(&index.to_string(), &format!("
+Message-ID: <{index}@enron.com>
+In-Reply-To: <{}@enron.com>
+Subject: Re: Chain", index.max(1) - 1)))
+            .collect();
+
+        let threads = build(messages);
+
+        assert_eq!(threads.threads.len(), 1);
+        assert_eq!(threads.messages["0"].parent, None);
+        assert_eq!(threads.messages["9999"].parent.as_deref(), Some("9998"));
+        assert_eq!(threads.messages["9998"].children, vec!["9999".to_string()]);
+    }
+}
@@ -2,7 +2,7 @@ use std::process::ExitStatus;
 
 use lazy_static::lazy_static;
 use tokio::io::{AsyncRead, AsyncWrite};
-use crate::{stream_command, trim_to_string};
+use crate::{stream_command, trim_to_string, ServiceError};
 
 const PROGRAM: &str = "wkhtmltopdf";
 
@@ -68,7 +68,7 @@ impl HtmlToPdf {
     /// * `Ok(HtmlToPdfOutput)` - If the `HtmlToPdf` CLI tool was run successfully.
     /// * `Err(_)` - If there was an error running the `PdfToImage` CLI tool.
     ///
-    pub async fn run<R, W>(&self, mut input: R, mut output: W) -> Result<HtmlToPdfOutput, anyhow::Error>
+    pub async fn run<R, W>(&self, mut input: R, mut output: W) -> Result<HtmlToPdfOutput, ServiceError>
     where
         R: AsyncRead + Unpin,
         W: AsyncWrite + Unpin,
@@ -17,6 +17,7 @@ use tokio::join;
 pub use archive_builder::*;
 pub use config::*;
 pub use html_to_pdf::*;
+pub use ocr::*;
 pub use pdf_to_image::*;
 pub use tika::*;
 pub use xdg_mime::*;
@@ -24,6 +25,7 @@ pub use xdg_mime::*;
 mod archive_builder;
 mod config;
 mod html_to_pdf;
+mod ocr;
 mod pdf_to_image;
 mod tika;
 mod xdg_mime;
@@ -114,6 +116,129 @@ impl std::error::Error for CommandError {
     }
 }
 
+/// The class of failure of a call to a service.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ServiceErrorKind {
+    /// The service could not be reached or is temporarily unable to handle requests.
+    ///
+    Unavailable,
+
+    /// The service does not support the type of the input.
+    ///
+    Unsupported,
+
+    /// The input is encrypted and cannot be read without a password.
+    ///
+    Encrypted,
+
+    /// The service could not parse the input, e.g. because it is corrupt.
+    ///
+    Unprocessable,
+
+    /// Any other failure.
+    ///
+    Failed,
+}
+
+impl fmt::Display for ServiceErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceErrorKind::Unavailable => write!(f, "service unavailable"),
+            ServiceErrorKind::Unsupported => write!(f, "unsupported input"),
+            ServiceErrorKind::Encrypted => write!(f, "encrypted input"),
+            ServiceErrorKind::Unprocessable => write!(f, "unprocessable input"),
+            ServiceErrorKind::Failed => write!(f, "service failed"),
+        }
+    }
+}
+
+/// Error type for when a call to a service fails.
+///
+#[derive(Debug)]
+pub struct ServiceError {
+    /// The class of failure.
+    ///
+    pub kind: ServiceErrorKind,
+
+    inner: anyhow::Error,
+}
+
+impl ServiceError {
+    /// Create a new [`ServiceError`] of the given kind.
+    ///
+    pub fn new(kind: ServiceErrorKind, err: impl Into<anyhow::Error>) -> Self {
+        Self {
+            kind,
+            inner: err.into(),
+        }
+    }
+
+    /// Wraps the underlying error with additional context, keeping the kind of failure.
+    ///
+    pub fn context<C>(self, context: C) -> Self
+    where
+        C: fmt::Display + Send + Sync + 'static,
+    {
+        Self {
+            kind: self.kind,
+            inner: self.inner.context(context),
+        }
+    }
+
+    /// Returns the underlying error if it, or any context wrapping it, is of type `E`.
+    ///
+    pub fn downcast_ref<E>(&self) -> Option<&E>
+    where
+        E: fmt::Display + fmt::Debug + Send + Sync + 'static,
+    {
+        self.inner.downcast_ref::<E>()
+    }
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.inner)
+    }
+}
+
+impl std::error::Error for ServiceError {
+    /// The underlying error is part of the message, so the source continues from its source instead.
+    ///
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.inner.source()
+    }
+}
+
+impl From<reqwest::Error> for ServiceError {
+    fn from(err: reqwest::Error) -> Self {
+        let kind = if err.is_connect() || err.is_timeout() {
+            ServiceErrorKind::Unavailable
+        } else {
+            ServiceErrorKind::Failed
+        };
+        Self::new(kind, err)
+    }
+}
+
+impl From<CommandError> for ServiceError {
+    /// A command failing before it exits means the program could not be run, e.g. because it isn't installed.
+    ///
+    fn from(err: CommandError) -> Self {
+        let kind = match err.exit_status {
+            None => ServiceErrorKind::Unavailable,
+            Some(_) => ServiceErrorKind::Failed,
+        };
+        Self::new(kind, err)
+    }
+}
+
+impl From<std::io::Error> for ServiceError {
+    fn from(err: std::io::Error) -> Self {
+        Self::new(ServiceErrorKind::Failed, err)
+    }
+}
+
 /// Run a command and return the exit status.
 ///
 /// This function streams the input into stdin, stdout to the metadata.json, and stderr to the error asynchronously.
@@ -248,7 +373,9 @@ mod test_utils {
 mod tests {
     use std::io::Cursor;
 
-    use crate::{stream_command, trim_to_string};
+    use anyhow::anyhow;
+
+    use crate::{stream_command, trim_to_string, ServiceError, ServiceErrorKind};
 
     fn buffers(data: &[u8]) -> (Cursor<Vec<u8>>, Vec<u8>, Vec<u8>) {
         let input = Cursor::new(data.to_vec());
@@ -352,4 +479,13 @@ mod tests {
         assert!(output.is_empty());
         assert!(error.is_empty());
     }
+
+    #[test]
+    fn test_service_error_display() {
+        let err = ServiceError::new(ServiceErrorKind::Unavailable, anyhow!("connection refused"))
+            .context("failed to call tika");
+
+        assert_eq!(err.to_string(), "service unavailable: failed to call tika");
+        assert_eq!(format!("{:#}", anyhow::Error::new(err)), "service unavailable: failed to call tika: connection refused");
+    }
 }
@@ -0,0 +1,187 @@
+use std::ffi::OsString;
+use std::fmt;
+use std::fmt::Formatter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use lazy_static::lazy_static;
+
+use crate::{stream_command, trim_to_string, ServiceError};
+
+const PROGRAM: &str = "tesseract";
+
+/// The file format of text recognized by the `Ocr` service.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OcrFormat {
+    /// Plain text, with pages separated by form feeds.
+    ///
+    Text,
+
+    /// hOCR, an HTML document with the bounding box of each word, line and paragraph.
+    ///
+    Hocr,
+
+    /// A searchable PDF of the input images, with the recognized text as an invisible layer.
+    ///
+    Pdf,
+}
+
+impl OcrFormat {
+    /// Returns the file extension of files in this format.
+    ///
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OcrFormat::Text => "txt",
+            OcrFormat::Hocr => "hocr",
+            OcrFormat::Pdf => "pdf",
+        }
+    }
+
+    /// Returns the MIME type of files in this format.
+    ///
+    pub fn mimetype(&self) -> &'static str {
+        match self {
+            OcrFormat::Text => "text/plain",
+            OcrFormat::Hocr => "text/html",
+            OcrFormat::Pdf => "application/pdf",
+        }
+    }
+
+    /// Returns the name of the tesseract config creating files in this format.
+    ///
+    fn config(&self) -> &'static str {
+        match self {
+            OcrFormat::Text => "txt",
+            OcrFormat::Hocr => "hocr",
+            OcrFormat::Pdf => "pdf",
+        }
+    }
+}
+
+impl FromStr for OcrFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "txt" | "text" => Ok(OcrFormat::Text),
+            "hocr" => Ok(OcrFormat::Hocr),
+            "pdf" => Ok(OcrFormat::Pdf),
+            _ => Err(format!("Can not convert {} to OcrFormat", s)),
+        }
+    }
+}
+
+impl fmt::Display for OcrFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
+/// The type of the singleton instance of the `Ocr` service.
+///
+pub type OcrService = Box<Ocr>;
+
+lazy_static! {
+    static ref OCR: OcrService = Box::<Ocr>::default();
+}
+
+/// Returns the singleton instance of the `Ocr` service.
+///
+pub fn ocr() -> &'static OcrService {
+    &OCR
+}
+
+/// The `Ocr` service, recognizing text in images with tesseract.
+///
+#[derive(Default)]
+pub struct Ocr {}
+
+impl Ocr {
+    /// Recognize the text in an image, or in each image listed in a text file.
+    ///
+    /// # Arguments
+    ///
+    /// * `input_path` - The path to the image, or to a text file listing the paths to multiple images, one per line.
+    /// * `output_base` - The path to write the files to, without an extension.
+    /// * `formats` - The formats of the files to write.
+    /// * `language` - The tesseract languages to recognize, e.g. `eng+deu`, or [`None`] for tesseract's default.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<PathBuf>)` - The paths to the files, in the order of the formats.
+    /// * `Err(_)` - If there was an error running tesseract.
+    ///
+    pub async fn recognize(
+        &self,
+        input_path: impl AsRef<Path>,
+        output_base: impl AsRef<Path>,
+        formats: &[OcrFormat],
+        language: Option<&str>,
+    ) -> Result<Vec<PathBuf>, ServiceError> {
+        let output_base = output_base.as_ref();
+        let mut args: Vec<OsString> = vec![input_path.as_ref().into(), output_base.into()];
+        if let Some(language) = language {
+            args.push("-l".into());
+            args.push(language.into());
+        }
+        args.extend(formats.iter().map(|format| format.config().into()));
+
+        let mut error = vec![];
+        stream_command(
+            PROGRAM,
+            &args,
+            None::<&[u8]>,
+            None::<&mut Vec<u8>>,
+            Some(&mut error),
+        ).await
+            .map_err(|err| ServiceError::from(err).context(trim_to_string(&error)))?;
+
+        Ok(formats.iter()
+            .map(|format| output_base.with_extension(format.extension()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::{Any, TypeId};
+
+    use crate::test_utils::assert_command_successful;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn check_tesseract_installed() {
+        assert_command_successful("which tesseract").await.unwrap();
+    }
+
+    #[test]
+    fn check_singleton() {
+        assert_eq!(ocr().type_id(), TypeId::of::<Box<Ocr>>());
+    }
+
+    #[test]
+    fn test_ocr_format_from_str() {
+        assert_eq!("txt".parse(), Ok(OcrFormat::Text));
+        assert_eq!("hOCR".parse(), Ok(OcrFormat::Hocr));
+        assert_eq!("pdf".parse(), Ok(OcrFormat::Pdf));
+        assert!("docx".parse::<OcrFormat>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_recognize() {
+        let input_path = "../resources/jpg/jQuery-text.jpg";
+        let output_dir = tempfile::tempdir().unwrap();
+        let output_base = output_dir.path().join("ocr");
+
+        let outputs = ocr()
+            .recognize(input_path, &output_base, &[OcrFormat::Text, OcrFormat::Hocr], None).await
+            .unwrap();
+
+        assert_eq!(outputs, vec![output_base.with_extension("txt"), output_base.with_extension("hocr")]);
+        let text = std::fs::read_to_string(&outputs[0]).unwrap();
+        assert!(text.contains("jQuery"), "Expected text to be recognized, got: {}", text);
+        assert!(std::fs::read_to_string(&outputs[1]).unwrap().contains("ocrx_word"));
+    }
+}
@@ -1,9 +1,14 @@
+use std::fmt;
+use std::fmt::Formatter;
+use std::path::{Path, PathBuf};
 use std::process::ExitStatus;
+use std::str::FromStr;
 
+use anyhow::anyhow;
 use lazy_static::lazy_static;
 use tokio::io::{AsyncRead, AsyncWrite};
 
-use crate::{stream_command, trim_to_string};
+use crate::{stream_command, trim_to_string, ServiceError, ServiceErrorKind};
 
 const PROGRAM: &str = "gs";
 
@@ -18,6 +23,78 @@ const DEFAULT_ARGS: [&str; 8] = [
     "-",              // Read input from stdin
 ];
 
+const PAGE_ARGS: [&str; 4] = [
+    "-q",        // No program metadata.json to stdout
+    "-dNOPAUSE", // Disable prompt/pause after end of each page
+    "-dBATCH",   // Exit after operation exits
+    "-dSAFER",   // Activate sandboxing; prevent I/O access outside specified files
+];
+
+/// The prefix of the names of the files rendered pages are written to, followed by the page number.
+///
+const PAGE_PREFIX: &str = "page-";
+
+/// The format of images rendered from the pages of a PDF.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    /// PNG images in 24-bit color.
+    ///
+    #[default]
+    Png,
+
+    /// JPEG images.
+    ///
+    Jpeg,
+}
+
+impl ImageFormat {
+    /// Returns the file extension of images in this format.
+    ///
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+        }
+    }
+
+    /// Returns the MIME type of images in this format.
+    ///
+    pub fn mimetype(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+        }
+    }
+
+    /// Returns the Ghostscript device rendering images in this format.
+    ///
+    fn device(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png16m",
+            ImageFormat::Jpeg => "jpeg",
+        }
+    }
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "png" => Ok(ImageFormat::Png),
+            "jpg" | "jpeg" => Ok(ImageFormat::Jpeg),
+            _ => Err(format!("Can not convert {} to ImageFormat", s)),
+        }
+    }
+}
+
+impl fmt::Display for ImageFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.extension())
+    }
+}
+
 /// The type of the singleton instance of the `PdfToImage` service.
 ///
 pub type PdfToImageService = Box<PdfToImage>;
@@ -62,7 +139,7 @@ impl PdfToImage {
     /// * `Ok(PdfToImageOutput)` - If the `PdfToImage` CLI tool was run successfully.
     /// * `Err(_)` - If there was an error running the `PdfToImage` CLI tool.
     ///
-    pub async fn run<R, W>(&self, mut input: R, mut output: W) -> Result<PdfToImageOutput, anyhow::Error>
+    pub async fn run<R, W>(&self, mut input: R, mut output: W) -> Result<PdfToImageOutput, ServiceError>
     where
         R: AsyncRead + Unpin,
         W: AsyncWrite + Unpin,
@@ -81,6 +158,72 @@ impl PdfToImage {
             error: trim_to_string(&error),
         })
     }
+
+    /// Render each page of a PDF to a separate image.
+    ///
+    /// # Arguments
+    ///
+    /// * `input_path` - The path to the PDF.
+    /// * `output_dir` - The directory to write the images to, named `page-<number>.<extension>`.
+    /// * `format` - The format of the images.
+    /// * `resolution` - The resolution of the images in dots per inch.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Vec<PathBuf>)` - The paths to the images, in the order of the pages.
+    /// * `Err(_)` - If there was an error running the `PdfToImage` CLI tool.
+    ///
+    pub async fn render_pages(
+        &self,
+        input_path: impl AsRef<Path>,
+        output_dir: impl AsRef<Path>,
+        format: ImageFormat,
+        resolution: u32,
+    ) -> Result<Vec<PathBuf>, ServiceError> {
+        let output_dir = output_dir.as_ref();
+        let output_file = output_dir.join(format!("{}%d.{}", PAGE_PREFIX, format.extension()));
+        let mut args = PAGE_ARGS.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        args.push(format!("-r{}", resolution));
+        args.push(format!("-sDEVICE={}", format.device()));
+        args.push(format!("-sOutputFile={}", output_file.display()));
+        args.push(input_path.as_ref().display().to_string());
+
+        let mut error = vec![];
+        stream_command(
+            PROGRAM,
+            &args,
+            None::<&[u8]>,
+            None::<&mut Vec<u8>>,
+            Some(&mut error),
+        ).await
+            .map_err(|err| ServiceError::from(err).context(trim_to_string(&error)))?;
+
+        let mut pages = vec![];
+        for entry in std::fs::read_dir(output_dir)? {
+            let path = entry?.path();
+            if let Some(page) = page_number(&path, format) {
+                pages.push((page, path));
+            }
+        }
+        if pages.is_empty() {
+            return Err(ServiceError::new(ServiceErrorKind::Unprocessable, anyhow!("no pages were rendered")));
+        }
+
+        pages.sort();
+        Ok(pages.into_iter().map(|(_, path)| path).collect())
+    }
+}
+
+/// Returns the page number of an image rendered by [`PdfToImage::render_pages`], or [`None`] if the file isn't one.
+///
+fn page_number(path: &Path, format: ImageFormat) -> Option<usize> {
+    path.file_name()?
+        .to_str()?
+        .strip_prefix(PAGE_PREFIX)?
+        .strip_suffix(format.extension())?
+        .strip_suffix('.')?
+        .parse()
+        .ok()
 }
 
 #[cfg(test)]
@@ -102,6 +245,27 @@ mod tests {
         assert_eq!(pdf_to_image().type_id(), TypeId::of::<Box<PdfToImage>>());
     }
 
+    #[test]
+    fn test_page_number() {
+        assert_eq!(page_number(Path::new("/tmp/page-1.png"), ImageFormat::Png), Some(1));
+        assert_eq!(page_number(Path::new("page-12.jpg"), ImageFormat::Jpeg), Some(12));
+        assert_eq!(page_number(Path::new("page-1.png"), ImageFormat::Jpeg), None);
+        assert_eq!(page_number(Path::new("page-x.png"), ImageFormat::Png), None);
+        assert_eq!(page_number(Path::new("cover.png"), ImageFormat::Png), None);
+    }
+
+    #[tokio::test]
+    async fn test_render_pages() {
+        let input_path = "../resources/pdf/Espresso Machine Cleaning Guide.pdf";
+        let output_dir = tempfile::tempdir().unwrap();
+
+        let pages = pdf_to_image().render_pages(input_path, output_dir.path(), ImageFormat::Png, 50).await.unwrap();
+
+        assert!(!pages.is_empty());
+        assert_eq!(pages[0], output_dir.path().join("page-1.png"));
+        assert!(pages.iter().all(|page| page.metadata().unwrap().len() > 0));
+    }
+
     #[tokio::test]
     async fn test_pdf_to_img() {
         let input_path_str = "../resources/pdf/Espresso Machine Cleaning Guide.pdf";
@@ -4,11 +4,11 @@ use anyhow::anyhow;
 use futures::StreamExt;
 use lazy_static::lazy_static;
 use log::{debug, info};
-use reqwest::{Body, Response};
+use reqwest::{Body, RequestBuilder, Response, StatusCode};
 use tokio::io::{AsyncRead, AsyncWriteExt};
 use tokio_util::codec::{BytesCodec, FramedRead};
 
-use crate::config;
+use crate::{config, ServiceError, ServiceErrorKind};
 
 /// The type of the singleton instance of the `Tika` service.
 ///
@@ -26,6 +26,7 @@ pub fn tika() -> &'static TikaService {
 
 /// The `Tika` service.
 ///
+#[derive(Debug)]
 pub struct Tika {
     http_client: reqwest::Client,
     tika_url: String,
@@ -35,16 +36,30 @@ impl Default for Tika {
     fn default() -> Self {
         let host = config().get_or("TIKA_HOST", "localhost");
         let port = config().get_or("TIKA_PORT", "9998");
-        let tika_url = format!("http://{}:{}", host, port);
+        Self::new(format!("http://{}:{}", host, port))
+    }
+}
 
+impl Tika {
+    /// Creates a new `Tika` service connecting to the server at the given URL.
+    ///
+    /// # Arguments
+    ///
+    /// * `tika_url` - The base URL of the Tika server, e.g. `http://localhost:9998`.
+    ///
+    pub fn new(tika_url: impl Into<String>) -> Self {
         Self {
             http_client: reqwest::Client::new(),
-            tika_url,
+            tika_url: tika_url.into().trim_end_matches('/').to_string(),
         }
     }
-}
 
-impl Tika {
+    /// Returns the base URL of the Tika server.
+    ///
+    pub fn tika_url(&self) -> &str {
+        &self.tika_url
+    }
+
     /// Checks if the Tika server is running.
     ///
     pub async fn is_connected(&self) -> bool {
@@ -64,10 +79,11 @@ impl Tika {
     ///
     /// The text extracted from the input file.
     ///
-    pub async fn text(&self, path: impl AsRef<Path>) -> Result<String, anyhow::Error> {
+    pub async fn text(&self, path: impl AsRef<Path>) -> Result<String, ServiceError> {
         info!("Using Tika to extract text");
 
-        let response = self.request_text(path).await?;
+        let input = tokio::fs::File::open(path).await?;
+        let response = self.request_text(input).await?;
         debug!("Tika responded with {}", response.status());
 
         let bytes = response.bytes().await?;
@@ -85,10 +101,26 @@ impl Tika {
     ///
     /// The text extracted from the input file.
     ///
-    pub async fn text_into_file(&self, input_path: impl AsRef<Path>, output_path: impl AsRef<Path>) -> Result<(), anyhow::Error> {
+    pub async fn text_into_file(&self, input_path: impl AsRef<Path>, output_path: impl AsRef<Path>) -> Result<(), ServiceError> {
+        let input = tokio::fs::File::open(input_path).await?;
+        self.text_from_reader_into_file(input, output_path).await
+    }
+
+    /// Extracts the text from a stream of the input file and writes it to the output file.
+    ///
+    /// The input is streamed to Tika as it is read, without being buffered in memory or written to disk.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The reader of the input file.
+    /// * `output_path` - The path to the output text file.
+    ///
+    pub async fn text_from_reader_into_file<R>(&self, input: R, output_path: impl AsRef<Path>) -> Result<(), ServiceError>
+        where R: AsyncRead + Send + Sync + Unpin + 'static
+    {
         info!("Using Tika to extract text");
 
-        let response = self.request_text(input_path).await?;
+        let response = self.request_text(input).await?;
         debug!("Tika responded with {}", response.status());
 
         let mut stream = response.bytes_stream();
@@ -100,14 +132,15 @@ impl Tika {
         Ok(())
     }
 
-    async fn request_text(&self, input_path: impl AsRef<Path>) -> Result<Response, anyhow::Error> {
-        let input = tokio::fs::File::open(input_path).await?;
-        Ok(self.http_client
+    async fn request_text<R>(&self, input: R) -> Result<Response, ServiceError>
+        where R: AsyncRead + Send + Sync + Unpin + 'static
+    {
+        self.send(self.http_client
             .put(self.url("/tika"))
             .header("Accept", "text/plain")
             .header("X-Tika-Skip-Embedded", "true")
             .body(Self::body_from_input(input))
-            .send().await?)
+        ).await
     }
 
     /// Extracts the metadata from the input file.
@@ -120,16 +153,34 @@ impl Tika {
     ///
     /// The metadata extracted from the input file.
     ///
-    pub async fn metadata(&self, path: impl AsRef<Path>) -> Result<String, anyhow::Error> {
+    pub async fn metadata(&self, path: impl AsRef<Path>) -> Result<String, ServiceError> {
+        let input = tokio::fs::File::open(path).await?;
+        self.metadata_from_reader(input).await
+    }
+
+    /// Extracts the metadata from a stream of the input file.
+    ///
+    /// The input is streamed to Tika as it is read, without being buffered in memory or written to disk.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The reader of the input file.
+    ///
+    /// # Returns
+    ///
+    /// The metadata extracted from the input file.
+    ///
+    pub async fn metadata_from_reader<R>(&self, input: R) -> Result<String, ServiceError>
+        where R: AsyncRead + Send + Sync + Unpin + 'static
+    {
         info!("Using Tika to extract metadata");
 
-        let input = tokio::fs::File::open(path).await?;
-        let response = self.http_client
+        let response = self.send(self.http_client
             .put(self.url("/meta"))
             .header("Accept", "application/json")
             .header("X-Tika-Skip-Embedded", "true")
             .body(Self::body_from_input(input))
-            .send().await?;
+        ).await?;
         debug!("Tika responded with {}", response.status());
 
         Ok(response.text().await?)
@@ -145,16 +196,16 @@ impl Tika {
     ///
     /// The mimetype of the input file.
     ///
-    pub async fn detect(&self, path: impl AsRef<Path>) -> Result<String, anyhow::Error> {
+    pub async fn detect(&self, path: impl AsRef<Path>) -> Result<String, ServiceError> {
         info!("Using Tika to detect mimetype");
 
         let input = tokio::fs::File::open(path).await?;
-        let response = self.http_client
+        let response = self.send(self.http_client
             .put(self.url("/meta/Content-Type"))
             .header("Accept", "application/json")
             .header("X-Tika-Skip-Embedded", "true")
             .body(Self::body_from_input(input))
-            .send().await?;
+        ).await?;
         debug!("Tika responded with {}", response.status());
 
         let mimetype = self.parse_detect_response(response).await;
@@ -163,6 +214,20 @@ impl Tika {
         mimetype
     }
 
+    /// Sends the request, turning unsuccessful responses into an error classified by the status code.
+    ///
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ServiceError> {
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let body = response.text().await.unwrap_or_default();
+        debug!("Tika responded with {}: {}", status, body);
+        Err(ServiceError::new(error_kind(status, &body), anyhow!("Tika responded with {}", status)))
+    }
+
     #[inline]
     fn url(&self, endpoint: impl AsRef<str>) -> String {
         format!("{}{}", self.tika_url, endpoint.as_ref())
@@ -177,18 +242,36 @@ impl Tika {
     }
 
     #[inline]
-    async fn parse_detect_response(&self, response: reqwest::Response) -> Result<String, anyhow::Error> {
+    async fn parse_detect_response(&self, response: reqwest::Response) -> Result<String, ServiceError> {
         let body = response
             .json::<serde_json::Value>()
             .await?;
 
         match body["Content-Type"].as_str() {
             Some(mimetype) => Ok(mimetype.to_string()),
-            None => Err(anyhow!("error parsing detect response"))?,
+            None => Err(ServiceError::new(ServiceErrorKind::Failed, anyhow!("error parsing detect response"))),
         }
     }
 }
 
+/// Classifies an unsuccessful response from Tika.
+///
+/// Tika responds with `415` when it has no parser for the input and `422` when the parser failed, where the body
+/// contains the stack trace of the exception thrown by the parser.
+///
+fn error_kind(status: StatusCode, body: &str) -> ServiceErrorKind {
+    match status {
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => ServiceErrorKind::Unsupported,
+        StatusCode::UNPROCESSABLE_ENTITY if body.contains("EncryptedDocumentException") => ServiceErrorKind::Encrypted,
+        StatusCode::UNPROCESSABLE_ENTITY => ServiceErrorKind::Unprocessable,
+        StatusCode::TOO_MANY_REQUESTS
+        | StatusCode::BAD_GATEWAY
+        | StatusCode::SERVICE_UNAVAILABLE
+        | StatusCode::GATEWAY_TIMEOUT => ServiceErrorKind::Unavailable,
+        _ => ServiceErrorKind::Failed,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::any::{Any, TypeId};
@@ -200,6 +283,28 @@ mod tests {
         assert_eq!(tika().type_id(), TypeId::of::<Box<Tika>>());
     }
 
+    #[test]
+    fn test_new() {
+        let tika = Tika::new("http://tika.internal:9998/");
+        assert_eq!(tika.tika_url(), "http://tika.internal:9998");
+        assert_eq!(tika.url("/meta"), "http://tika.internal:9998/meta");
+    }
+
+    #[test]
+    fn test_error_kind() {
+        let cases = vec![
+            (StatusCode::UNSUPPORTED_MEDIA_TYPE, "", ServiceErrorKind::Unsupported),
+            (StatusCode::UNPROCESSABLE_ENTITY, "org.apache.tika.exception.EncryptedDocumentException", ServiceErrorKind::Encrypted),
+            (StatusCode::UNPROCESSABLE_ENTITY, "org.apache.tika.exception.TikaException", ServiceErrorKind::Unprocessable),
+            (StatusCode::SERVICE_UNAVAILABLE, "", ServiceErrorKind::Unavailable),
+            (StatusCode::INTERNAL_SERVER_ERROR, "", ServiceErrorKind::Failed),
+        ];
+
+        for (status, body, expected) in cases {
+            assert_eq!(error_kind(status, body), expected, "{}", status);
+        }
+    }
+
     #[test]
     fn test_parse_detect_response() {
         // todo!()
@@ -1,10 +1,9 @@
-use anyhow::Context;
 use std::io::Cursor;
 use std::path::Path;
 
 use lazy_static::lazy_static;
 
-use crate::{stream_command, trim_to_string};
+use crate::{stream_command, trim_to_string, ServiceError};
 
 /// The type of the singleton instance of the `XdgMime` service.
 ///
@@ -35,7 +34,7 @@ impl XdgMime {
     ///
     /// The mimetype of the file.
     ///
-    pub async fn query_filetype(&self, path: impl AsRef<Path>) -> Result<String, anyhow::Error> {
+    pub async fn query_filetype(&self, path: impl AsRef<Path>) -> Result<String, ServiceError> {
         let path_str = path.as_ref().to_string_lossy().to_string();
 
         let mut output = vec![];
@@ -48,11 +47,10 @@ impl XdgMime {
             Some(&mut error),
         )
         .await
-        .map_err(|error| anyhow::anyhow!("{}", error))
-        .context(format!(
+        .map_err(|err| ServiceError::from(err).context(format!(
             "'xdg-mime' failed to detect mimetype: {}",
             trim_to_string(&error)
-        ))?;
+        )))?;
 
         Ok(trim_to_string(&output))
     }
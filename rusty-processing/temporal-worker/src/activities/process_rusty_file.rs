@@ -2,31 +2,46 @@ use std::fs;
 use std::path::{Path, PathBuf};
 
 use anyhow::anyhow;
-use log::{debug, info};
+use log::{debug, info, warn};
 use serde::{Deserialize, Serialize};
 use tap::Tap;
 use temporal_sdk::ActContext;
+use temporal_sdk_core_protos::coresdk::AsJsonPayloadExt;
+use tokio::io::AsyncRead;
 use tokio::sync::mpsc::Receiver;
+use tokio::sync::watch;
 
-use processing::processing::{processor, ProcessContextBuilder, ProcessOutput, ProcessType};
+use processing::processing::{
+    CancellationToken, ProcessContextBuilder, ProcessError, ProcessOutput, ProcessType, Progress,
+};
 use services::log_err;
 
-use crate::util::{BatchEntry, ProcessOutputBatcher};
+use crate::{processor, s3_client};
+use crate::util::{BatchEntry, parse_s3_uri, ProcessOutputBatcher};
 
 /// Input to the `process_rusty_file` activity.
 ///
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ProcessRustyFileInput {
-    /// The local path to the file to process.
+    /// The local path to the file to process, if it was downloaded.
     ///
+    #[serde(default)]
     path: PathBuf,
 
+    /// The S3 URI of the file to process, to stream it from S3 instead of reading it from `path`.
+    ///
+    /// Ignored when recursing, as embedded files are extracted from the downloaded file.
+    ///
+    #[serde(default)]
+    s3_uri: Option<String>,
+
     /// The local path to the directory where output files should be written to.
     ///
     directory: PathBuf,
 
-    /// The MIME type of the file to process.
+    /// The MIME type of the file to process, detected from its content if empty or `application/octet-stream`.
     ///
+    #[serde(default)]
     mimetype: String,
 
     /// The types of output to generate.
@@ -36,6 +51,11 @@ pub struct ProcessRustyFileInput {
     /// The name of the Redis stream to send output to.
     ///
     output_stream_name: String,
+
+    /// Whether to process embedded files within this activity instead of sending them to the Redis stream.
+    ///
+    #[serde(default)]
+    recurse: bool,
 }
 
 /// Output of the `process_rusty_file` activity.
@@ -48,24 +68,61 @@ pub struct ProcessRustyFileOutput {}
 /// This activity downloads a file from S3, processes it, and uploads the
 /// result back to S3 in the form of an archive.
 ///
+/// If an S3 URI is given and embedded files are not processed recursively, the file is streamed from S3 instead, and
+/// is only written to disk if a processor needs the whole file.
+///
+/// The latest progress of processing is recorded as the heartbeat details of the activity.
+///
 pub async fn process_rusty_file(
-    _ctx: ActContext,
+    act_ctx: ActContext,
     input: ProcessRustyFileInput,
 ) -> Result<ProcessRustyFileOutput, anyhow::Error> {
     info!("Processing rusty file '{:?}'", input);
 
     let (output_sink, outputs) = tokio::sync::mpsc::channel(100);
-    let ctx = ProcessContextBuilder::new(input.mimetype, input.types, output_sink).build();
-
-    let processing = tokio::spawn(processor().process(ctx, input.path));
+    let cancellation = CancellationToken::new();
+    let ctx = ProcessContextBuilder::new(input.mimetype, input.types, output_sink)
+        .cancellation_token(cancellation.clone())
+        .progress(true)
+        .build();
+
+    let mut processing = match input.s3_uri {
+        Some(s3_uri) if !input.recurse => {
+            let (object, size_hint) = open_s3_object(s3_uri).await?;
+            tokio::spawn(processor().process_reader(ctx, object, size_hint))
+        }
+        _ if input.recurse => tokio::spawn(processor().process_recursive(ctx, input.path)),
+        _ => tokio::spawn(processor().process(ctx, input.path)),
+    };
+    let (progress_sink, mut progress) = watch::channel(None);
     let output_handling = tokio::spawn(handle_outputs(
         outputs,
         input.directory,
         input.output_stream_name,
+        input.recurse,
+        progress_sink,
     ));
 
-    processing
-        .await?
+    let result = loop {
+        tokio::select! {
+            result = &mut processing => break result,
+            Ok(()) = progress.changed() => {
+                // Failing to serialize the progress must not return before processing finished, so it is skipped
+                match progress.borrow_and_update().as_ref().map(|progress| progress.as_json_payload()) {
+                    Some(Ok(payload)) => act_ctx.record_heartbeat(vec![payload]),
+                    Some(Err(err)) => warn!("Failed to serialize progress, skipping heartbeat: {}", err),
+                    None => {},
+                }
+            }
+            _ = act_ctx.cancelled() => {
+                info!("Activity cancelled, cancelling processing");
+                cancellation.cancel();
+                break processing.await;
+            }
+        }
+    };
+
+    result?
         .tap(log_err!("Failed to process file"))
         .map_err(|err| anyhow!("Unexpected error: {:?}", err))?;
     output_handling.await??;
@@ -73,28 +130,61 @@ pub async fn process_rusty_file(
     Ok(ProcessRustyFileOutput {})
 }
 
+/// Opens a stream of the body of an S3 object, along with its size if known.
+///
+async fn open_s3_object(s3_uri: impl AsRef<str>) -> anyhow::Result<(impl AsyncRead + Send + Unpin, Option<u64>)> {
+    let (bucket, key) = parse_s3_uri(s3_uri.as_ref())?;
+    let object = s3_client().await
+        .get_object()
+        .bucket(bucket)
+        .key(key)
+        .send()
+        .await?;
+
+    let size_hint = u64::try_from(object.content_length()).ok();
+    Ok((object.body.into_async_read(), size_hint))
+}
+
+/// Writes the outputs to the output directory and sends embedded files to the Redis stream.
+///
+/// Outputs are written to a directory per file in the tree, following their ID chain. Embedded files are not sent to
+/// the Redis stream when recursing, as they were already processed.
+///
+/// Progress is not written, but passed on to be recorded as the heartbeat of the activity.
+///
+/// Errors are logged, and the first error that may not happen again when retrying, e.g. because Tika was unavailable,
+/// is returned once all outputs were handled so the activity is retried.
+///
 async fn handle_outputs(
-    mut outputs: Receiver<anyhow::Result<ProcessOutput>>,
+    mut outputs: Receiver<Result<ProcessOutput, ProcessError>>,
     output_dir: impl AsRef<Path>,
     output_stream_name: impl AsRef<str>,
+    recurse: bool,
+    progress_sink: watch::Sender<Option<Progress>>,
 ) -> anyhow::Result<()> {
     let output_dir = output_dir.as_ref();
 
     info!("Handling outputs");
     let mut batcher = ProcessOutputBatcher::new(output_stream_name.as_ref(), 25);
+    let mut retryable_err = None;
     while let Some(output) = outputs.recv().await {
         debug!("Received metadata.json: {:?}", output);
 
-        if let Ok(output) = output.tap(log_err!("Error processing file")) {
-            match output {
-                ProcessOutput::Processed(_, data) => {
-                    let output_path = output_dir.join(data.name);
+        match output.tap(log_err!("Error processing file")) {
+            Err(err) if err.kind.is_retryable() && retryable_err.is_none() => retryable_err = Some(err),
+            Err(_) => (),
+            Ok(output) => match output {
+                ProcessOutput::Processed(state, data) | ProcessOutput::Report(state, data) => {
+                    let output_path = tree_dir(output_dir, &state.id_chain).join(data.name);
                     copy_making_dirs(&data.path, &output_path)?;
                 }
 
-                ProcessOutput::Embedded(_, data, _) => {
-                    let output_path = output_dir.join(&data.checksum).join(&data.name);
+                ProcessOutput::Embedded(state, data, _) => {
+                    let output_path = tree_dir(output_dir, &state.id_chain).join(&data.checksum).join(&data.name);
                     copy_making_dirs(&data.path, &output_path)?;
+                    if recurse {
+                        continue;
+                    }
 
                     info!("Adding embedded file to Redis stream: {:?}", &data.path);
                     batcher
@@ -105,13 +195,30 @@ async fn handle_outputs(
                         })
                         .await?;
                 }
-            }
+
+                ProcessOutput::Skipped(_, data) => {
+                    info!("Skipped embedded file {:?}: {}", data.name, data.reason);
+                }
+
+                ProcessOutput::Progress(_, progress) => {
+                    progress_sink.send_replace(Some(progress));
+                }
+            },
         }
     }
 
     // TODO - Utilize drop trait once async capabilities are available
     batcher.flush().await?;
-    Ok(())
+    match retryable_err {
+        Some(err) => Err(anyhow::Error::new(err).context("retryable error processing file")),
+        None => Ok(()),
+    }
+}
+
+/// The directory of the file with the given ID chain within the output directory.
+///
+fn tree_dir(output_dir: &Path, id_chain: &[String]) -> PathBuf {
+    id_chain.iter().fold(output_dir.to_path_buf(), |dir, id| dir.join(id))
 }
 
 fn copy_making_dirs(source_path: &Path, output_path: &Path) -> anyhow::Result<()> {
@@ -10,13 +10,14 @@ use async_once::AsyncOnce;
 use aws_sdk_s3 as s3;
 use gethostname::gethostname;
 use lazy_static::lazy_static;
-use log::info;
+use log::{info, warn};
 use temporal_sdk::{sdk_client_options, Worker};
 use temporal_sdk_core::{Client, CoreRuntime, init_worker, RetryClient};
 use temporal_sdk_core_api::telemetry::TelemetryOptionsBuilder;
 use temporal_sdk_core_api::worker::WorkerConfigBuilder;
 use url::Url;
 
+use processing::processing::{ExtractionLimits, Processor, ProcessorBuilder};
 use services::config;
 
 /// Temporal activity definitions.
@@ -45,6 +46,30 @@ lazy_static! {
         redis::Client::open(REDIS_ADDRESS.as_ref()).unwrap()
     };
 
+    static ref PROCESSOR: Processor = {
+        let mut builder = ProcessorBuilder::new().tika_url(format!(
+            "http://{}:{}",
+            config().get_or("TIKA_HOST", "localhost"),
+            config().get_or("TIKA_PORT", "9998"),
+        ));
+        if let Some(temp_dir) = config().get("PROCESSING_TEMP_DIR") {
+            builder = builder.temp_dir(temp_dir);
+        }
+        if let Some(concurrency) = config().get("PROCESSING_CONCURRENCY") {
+            match concurrency.parse() {
+                Ok(concurrency) => builder = builder.max_concurrency(concurrency),
+                Err(_) => warn!("Ignoring PROCESSING_CONCURRENCY '{}', expected a positive number", concurrency),
+            }
+        }
+        builder = builder.limits(ExtractionLimits {
+            max_depth: config().get("PROCESSING_MAX_DEPTH").and_then(|value| value.parse().ok()),
+            max_entries: config().get("PROCESSING_MAX_ENTRIES").and_then(|value| value.parse().ok()),
+            max_total_bytes: config().get("PROCESSING_MAX_TOTAL_BYTES").and_then(|value| value.parse().ok()),
+            max_compression_ratio: config().get("PROCESSING_MAX_COMPRESSION_RATIO").and_then(|value| value.parse().ok()),
+        });
+        builder.build()
+    };
+
     static ref HOSTNAME: String = gethostname().to_string_lossy().to_string();
 
     static ref TEMPORAL_ADDRESS: String = format!(
@@ -68,6 +93,10 @@ pub(crate) fn redis() -> &'static redis::Client {
     &REDIS
 }
 
+pub(crate) fn processor() -> &'static Processor {
+    &PROCESSOR
+}
+
 pub(crate) fn hostname() -> &'static str {
     HOSTNAME.as_str()
 }